@@ -0,0 +1,146 @@
+use crate::timecode::format_time;
+
+/// Width of the timeline bar, in characters.
+const TIMELINE_WIDTH: usize = 40;
+
+/// An A-B repeat range within one queue entry: once playback passes B it
+/// goes back to A. Times are seconds into the media.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AbLoop {
+    /// The queue entry the points were set in.
+    index: usize,
+    a: f64,
+    b: Option<f64>,
+}
+
+impl AbLoop {
+    /// Sets A at `time` in entry `index`, keeping a B still after it.
+    pub fn set_a(current: Option<AbLoop>, index: usize, time: f64) -> Self {
+        let b = current
+            .filter(|current| current.index == index)
+            .and_then(|current| current.b)
+            .filter(|&b| b > time);
+        AbLoop { index, a: time, b }
+    }
+
+    /// Sets B at `time`, which has to come after A in the same entry.
+    pub fn set_b(&mut self, index: usize, time: f64) -> bool {
+        if index != self.index || time <= self.a {
+            return false;
+        }
+        self.b = Some(time);
+        true
+    }
+
+    pub fn is_for(&self, index: Option<usize>) -> bool {
+        index == Some(self.index)
+    }
+
+    pub fn a(&self) -> f64 {
+        self.a
+    }
+
+    /// Whether both points are set in entry `index`, which then repeats
+    /// rather than letting the queue move on.
+    pub fn holds(&self, index: Option<usize>) -> bool {
+        self.b.is_some() && self.is_for(index)
+    }
+
+    /// Seconds from `time` until B in entry `index`, negative once past it.
+    pub fn until_b(&self, index: Option<usize>, time: f64) -> Option<f64> {
+        let b = self.b.filter(|_| self.is_for(index))?;
+        Some(b - time)
+    }
+
+    /// Where to seek to once playback of entry `index` is at `time`: A if
+    /// that's past B.
+    pub fn wrap(&self, index: Option<usize>, time: f64) -> Option<f64> {
+        let b = self.b.filter(|_| self.is_for(index))?;
+        (time >= b).then_some(self.a)
+    }
+
+    /// The loop drawn over the entry's `span` of the media, with the
+    /// playback position, e.g. `0:00 [---A===|==B-----] 4:00  A 1:00  B 1:30`.
+    pub fn timeline(&self, time: Option<f64>, span: Option<(f64, f64)>) -> String {
+        let points = match self.b {
+            Some(b) => format!("A {}  B {}", format_time(self.a), format_time(b)),
+            None => format!("A {}  B -", format_time(self.a)),
+        };
+        let Some((start, end)) = span.filter(|(start, end)| end > start) else {
+            return format!("Loop: {points}");
+        };
+        let column = |time: f64| {
+            let share = (time - start) / (end - start);
+            (share * (TIMELINE_WIDTH - 1) as f64)
+                .round()
+                .clamp(0.0, (TIMELINE_WIDTH - 1) as f64) as usize
+        };
+        let mut bar = vec!['-'; TIMELINE_WIDTH];
+        let a = column(self.a);
+        let b = self.b.map(column);
+        if let Some(b) = b {
+            bar[a..=b].fill('=');
+        }
+        if let Some(time) = time {
+            bar[column(time)] = '|';
+        }
+        bar[a] = 'A';
+        if let Some(b) = b {
+            bar[b] = 'B';
+        }
+        format!(
+            "{} [{}] {}  {points}",
+            format_time(start),
+            bar.into_iter().collect::<String>(),
+            format_time(end)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_points() {
+        let mut ab_loop = AbLoop::set_a(None, 2, 60.0);
+        assert_eq!(ab_loop.wrap(Some(2), 500.0), None);
+        assert!(!ab_loop.holds(Some(2)));
+        assert!(!ab_loop.set_b(2, 50.0));
+        assert!(!ab_loop.set_b(3, 90.0));
+        assert!(ab_loop.set_b(2, 90.0));
+
+        assert_eq!(ab_loop.wrap(Some(2), 89.0), None);
+        assert_eq!(ab_loop.wrap(Some(2), 90.5), Some(60.0));
+        // Another entry is playing.
+        assert_eq!(ab_loop.wrap(Some(3), 90.5), None);
+        assert!(ab_loop.holds(Some(2)));
+        assert!(!ab_loop.holds(Some(3)));
+        assert_eq!(ab_loop.until_b(Some(2), 89.5), Some(0.5));
+        assert_eq!(ab_loop.until_b(Some(3), 89.5), None);
+
+        // Moving A keeps B if it's still after it.
+        let moved = AbLoop::set_a(Some(ab_loop), 2, 70.0);
+        assert_eq!(moved.wrap(Some(2), 90.0), Some(70.0));
+        let moved = AbLoop::set_a(Some(ab_loop), 2, 95.0);
+        assert_eq!(moved.wrap(Some(2), 500.0), None);
+        let elsewhere = AbLoop::set_a(Some(ab_loop), 4, 70.0);
+        assert_eq!(elsewhere.wrap(Some(4), 500.0), None);
+    }
+
+    #[test]
+    fn test_timeline() {
+        let mut ab_loop = AbLoop::set_a(None, 0, 60.0);
+        assert_eq!(ab_loop.timeline(Some(60.0), None), "Loop: A 1:00  B -");
+        ab_loop.set_b(0, 117.0);
+        let timeline = ab_loop.timeline(Some(90.0), Some((0.0, 195.0)));
+        assert_eq!(
+            timeline,
+            "0:00 [------------A=====|====B----------------] 3:15  A 1:00  B 1:57"
+        );
+        assert_eq!(
+            ab_loop.timeline(None, Some((0.0, 0.0))),
+            "Loop: A 1:00  B 1:57"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use crate::fetch::HttpClient;
+use crate::html;
+use crate::metadata;
+use percent_encoding::percent_decode_str;
+use scraper::{Html, Selector};
+use std::collections::HashSet;
+use std::path::Path;
+use url::Url;
+
+/// Which files of a listing are queued.
+#[derive(Clone, Debug, Default)]
+pub struct ExtensionFilter {
+    /// Lowercase extensions without the dot; `None` accepts any media type
+    /// the receiver is known to play.
+    extensions: Option<Vec<String>>,
+}
+
+impl ExtensionFilter {
+    /// Parses a comma-separated list such as `mp3,.flac,OGG`.
+    pub fn new(extensions: Option<&str>) -> Self {
+        ExtensionFilter {
+            extensions: extensions.map(|list| {
+                list.split(',')
+                    .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+                    .filter(|extension| !extension.is_empty())
+                    .collect()
+            }),
+        }
+    }
+
+    pub fn matches(&self, url: &Url) -> bool {
+        let name = percent_decode_str(url.path()).decode_utf8_lossy();
+        let path = Path::new(name.as_ref());
+        match &self.extensions {
+            Some(extensions) => path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .is_some_and(|extension| extensions.contains(&extension)),
+            None => metadata::content_type_for_path(path).is_some(),
+        }
+    }
+}
+
+/// Whether a page is a server-generated directory listing (Apache, nginx,
+/// lighttpd and Python's `http.server` all title it "Index of ...").
+pub fn is_autoindex(content: &str) -> bool {
+    let document = Html::parse_document(content);
+    let selector = Selector::parse("title, h1").unwrap();
+    document.select(&selector).any(|element| {
+        let text: String = element.text().collect();
+        let text = text.trim_start();
+        text.starts_with("Index of") || text.starts_with("Directory listing for")
+    })
+}
+
+/// Splits a listing's links into subdirectories and files. Parent-directory
+/// links, Apache's column-sorting links and links leaving the listed
+/// directory are dropped.
+pub fn listing_entries(content: &str, page_url: &Url) -> (Vec<Url>, Vec<Url>) {
+    let document = Html::parse_document(content);
+    let selector = Selector::parse("a[href]").unwrap();
+
+    let (mut directories, mut files) = (Vec::new(), Vec::new());
+    for element in document.select(&selector) {
+        let Some(href) = element.value().attr("href") else {
+            continue;
+        };
+        if href.starts_with('?') || href.starts_with('#') {
+            continue;
+        }
+        let Ok(mut url) = page_url.join(href) else {
+            continue;
+        };
+        url.set_query(None);
+        url.set_fragment(None);
+        if url.origin() != page_url.origin()
+            || !url.path().starts_with(page_url.path())
+            || url.path() == page_url.path()
+        {
+            continue;
+        }
+        let list = if url.path().ends_with('/') {
+            &mut directories
+        } else {
+            &mut files
+        };
+        if !list.contains(&url) {
+            list.push(url);
+        }
+    }
+    (directories, files)
+}
+
+pub struct Crawler {
+    client: HttpClient,
+    filter: ExtensionFilter,
+    max_depth: usize,
+}
+
+impl Crawler {
+    pub fn new(filter: ExtensionFilter, max_depth: usize) -> Self {
+        Crawler {
+            client: HttpClient::new(),
+            filter,
+            max_depth,
+        }
+    }
+
+    /// Finds the media at `url`: a media file itself, the files of a
+    /// directory listing and its subdirectories down to the maximum depth,
+    /// or the media linked from an ordinary page.
+    pub async fn media_urls(&self, url: &Url) -> anyhow::Result<Vec<String>> {
+        let page = self.client.get(url).await?;
+        let Some(body) = &page.body else {
+            let is_media = page.content_type.as_deref().is_some_and(|content_type| {
+                content_type.starts_with("audio/") || content_type.starts_with("video/")
+            });
+            return Ok(if is_media || self.filter.matches(&page.url) {
+                vec![page.url.to_string()]
+            } else {
+                Vec::new()
+            });
+        };
+        if !is_autoindex(body) {
+            return Ok(html::find_media_links(body, Some(&page.url)));
+        }
+
+        let mut media = Vec::new();
+        let mut visited = HashSet::from([page.url.clone()]);
+        self.crawl_listing(&page.url, body, 0, &mut visited, &mut media)
+            .await;
+        Ok(media)
+    }
+
+    /// Collects a listing's files, then descends into its subdirectories in
+    /// listing order. Subdirectories that can't be read are reported and
+    /// skipped.
+    async fn crawl_listing(
+        &self,
+        page_url: &Url,
+        body: &str,
+        depth: usize,
+        visited: &mut HashSet<Url>,
+        media: &mut Vec<String>,
+    ) {
+        let (directories, files) = listing_entries(body, page_url);
+        media.extend(
+            files
+                .into_iter()
+                .filter(|file| self.filter.matches(file))
+                .map(String::from),
+        );
+        if depth >= self.max_depth {
+            return;
+        }
+
+        for directory in directories {
+            if !visited.insert(directory.clone()) {
+                continue;
+            }
+            let page = match self.client.get(&directory).await {
+                Ok(page) => page,
+                Err(e) => {
+                    eprintln!("Skipping {directory}: {e}");
+                    continue;
+                }
+            };
+            if let Some(body) = page.body.filter(|body| is_autoindex(body)) {
+                Box::pin(self.crawl_listing(&page.url, &body, depth + 1, visited, media)).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{self, Resource, Resources};
+
+    const APACHE: &str = include_str!("../tests/fixtures/autoindex/apache.html");
+    const NGINX: &str = include_str!("../tests/fixtures/autoindex/nginx.html");
+
+    #[test]
+    fn test_is_autoindex() {
+        assert!(is_autoindex(APACHE));
+        assert!(is_autoindex(NGINX));
+        assert!(!is_autoindex(
+            "<title>My music</title><a href='a.mp3'>a</a>"
+        ));
+    }
+
+    #[test]
+    fn test_listing_entries() {
+        let page = Url::parse("http://nas/music/").unwrap();
+        let (directories, files) = listing_entries(APACHE, &page);
+        let directories: Vec<&str> = directories.iter().map(Url::as_str).collect();
+        let files: Vec<&str> = files.iter().map(Url::as_str).collect();
+        assert_eq!(directories, ["http://nas/music/Album%20One/"]);
+        assert_eq!(
+            files,
+            [
+                "http://nas/music/01%20Intro.mp3",
+                "http://nas/music/02%20Song.flac",
+                "http://nas/music/cover.jpg",
+                "http://nas/music/notes.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extension_filter() {
+        let url = |path: &str| Url::parse(&format!("http://nas{path}")).unwrap();
+        let any = ExtensionFilter::new(None);
+        assert!(any.matches(&url("/a/01%20Song.FLAC")));
+        assert!(any.matches(&url("/a/film.mkv")));
+        assert!(!any.matches(&url("/a/cover.jpg")));
+
+        let flac = ExtensionFilter::new(Some("flac, .OGG"));
+        assert!(flac.matches(&url("/a/01%20Song.FLAC")));
+        assert!(flac.matches(&url("/a/b.ogg")));
+        assert!(!flac.matches(&url("/a/c.mp3")));
+    }
+
+    async fn serve_listings() -> (Url, tokio::sync::oneshot::Sender<()>) {
+        let resources = Resources::default();
+        {
+            let mut resources = resources.write().unwrap();
+            resources.insert("/music/".to_string(), Resource::new("text/html", APACHE));
+            resources.insert(
+                "/music/Album%20One/".to_string(),
+                Resource::new("text/html", NGINX),
+            );
+            resources.insert(
+                "/music/Album%20One/Disc%202/".to_string(),
+                Resource::new(
+                    "text/html",
+                    "<title>Index of /music/Album One/Disc 2/</title>\
+                     <a href=\"../\">../</a><a href=\"09%20Deep.mp3\">09 Deep.mp3</a>",
+                ),
+            );
+            resources.insert(
+                "/music/page.html".to_string(),
+                Resource::new("text/html", "<audio src=\"02%20Song.flac\"></audio>"),
+            );
+            resources.insert(
+                "/music/01%20Intro.mp3".to_string(),
+                Resource::new("audio/mpeg", "ID3"),
+            );
+        }
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let (addr, _handle) = server::start_resource_server(resources, rx).await.unwrap();
+        (Url::parse(&format!("http://{addr}/music/")).unwrap(), tx)
+    }
+
+    #[tokio::test]
+    async fn test_crawl_listing() {
+        let (base, _shutdown) = serve_listings().await;
+        let paths = |urls: Vec<String>| -> Vec<String> {
+            urls.iter()
+                .map(|url| url.trim_start_matches(base.as_str()).to_string())
+                .collect()
+        };
+
+        let crawler = Crawler::new(ExtensionFilter::new(None), 2);
+        assert_eq!(
+            paths(crawler.media_urls(&base).await.unwrap()),
+            [
+                "01%20Intro.mp3",
+                "02%20Song.flac",
+                "Album%20One/03%20Track.ogg",
+                "Album%20One/Disc%202/09%20Deep.mp3",
+            ]
+        );
+
+        let crawler = Crawler::new(ExtensionFilter::new(Some("ogg,mp3")), 1);
+        assert_eq!(
+            paths(crawler.media_urls(&base).await.unwrap()),
+            ["01%20Intro.mp3", "Album%20One/03%20Track.ogg"]
+        );
+
+        let crawler = Crawler::new(ExtensionFilter::new(None), 0);
+        assert_eq!(
+            paths(
+                crawler
+                    .media_urls(&base.join("page.html").unwrap())
+                    .await
+                    .unwrap()
+            ),
+            ["02%20Song.flac"]
+        );
+        assert_eq!(
+            paths(
+                crawler
+                    .media_urls(&base.join("01%20Intro.mp3").unwrap())
+                    .await
+                    .unwrap()
+            ),
+            ["01%20Intro.mp3"]
+        );
+        assert!(crawler
+            .media_urls(&base.join("missing/").unwrap())
+            .await
+            .is_err());
+    }
+}
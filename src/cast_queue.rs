@@ -0,0 +1,415 @@
+use crate::chromecast;
+use crate::messenger::Messenger;
+use crate::player_controls::PlayerContext;
+use crate::queue::Queue;
+use crate::queue_engine::RepeatMode;
+use crate::subtitles::SubtitleTrack;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+/// How long before an item ends the receiver starts buffering the next one.
+const PRELOAD_TIME_SECS: f64 = 20.0;
+/// How many items past the current one are kept on the receiver. It only
+/// needs the next one, `PRELOAD_TIME_SECS` before the current one ends.
+const LOOKAHEAD_ITEMS: usize = 2;
+
+/// The play queue as loaded on the receiver (`--native-queue`), which then
+/// moves from item to item by itself. Only the current item and the next
+/// few are on the receiver; more are appended as playback moves on. Keeps
+/// the local [`Queue`] in step with the receiver's current item.
+#[derive(Debug, Default)]
+pub struct CastQueue {
+    /// The receiver's item ids, by position in the current loop.
+    item_ids: BTreeMap<usize, i32>,
+    /// Item ids by position in the next loop, once its first items are
+    /// appended.
+    next_loop_ids: BTreeMap<usize, i32>,
+    /// Items of a finished loop, to be removed from the receiver.
+    stale_ids: Vec<i32>,
+    /// Subtitle tracks by entry index.
+    tracks: HashMap<usize, Vec<SubtitleTrack>>,
+}
+
+impl CastQueue {
+    /// Loads the queue on the receiver from its current entry, `start_time`
+    /// seconds in if given.
+    pub fn load(
+        messenger: &Messenger,
+        session_id: &str,
+        context: &mut PlayerContext,
+        repeat_mode: RepeatMode,
+        start_time: Option<f64>,
+    ) -> anyhow::Result<Self> {
+        let position = context.queue.position();
+        let cast_queue = Self::load_from(
+            messenger,
+            session_id,
+            context,
+            position,
+            repeat_mode,
+            start_time,
+        )?;
+        cast_queue.show_tracks(context);
+        Ok(cast_queue)
+    }
+
+    /// Loads the entry at `position` and the next few on the receiver, in
+    /// place of whatever it had queued.
+    fn load_from(
+        messenger: &Messenger,
+        session_id: &str,
+        context: &PlayerContext,
+        position: usize,
+        repeat_mode: RepeatMode,
+        start_time: Option<f64>,
+    ) -> anyhow::Result<Self> {
+        let mut cast_queue = CastQueue::default();
+        let order: Vec<usize> = context
+            .queue
+            .indexed()
+            .map(|(index, _)| index)
+            .skip(position)
+            .take(1 + LOOKAHEAD_ITEMS)
+            .collect();
+        let mut items = cast_queue.items(&order, context)?;
+        if let (Some(start_time), Some(item)) = (start_time, items.first_mut()) {
+            item["startTime"] = serde_json::json!(start_time);
+        }
+        let (_, item_ids) = chromecast::load_queue(
+            messenger,
+            session_id,
+            &items,
+            0,
+            receiver_repeat(repeat_mode),
+        )?;
+        cast_queue.item_ids = (position..).zip(item_ids).collect();
+        Ok(cast_queue)
+    }
+
+    /// Queue items for the entries at `indexes`, keeping their subtitle
+    /// tracks for when they play.
+    fn items(&mut self, indexes: &[usize], context: &PlayerContext) -> anyhow::Result<Vec<Value>> {
+        let Some(loader) = &context.loader else {
+            return Ok(Vec::new());
+        };
+        let mut items = Vec::with_capacity(indexes.len());
+        for &index in indexes {
+            let Some(entry) = context.queue.entry(index) else {
+                continue;
+            };
+            let (request, tracks) = loader.request_for(entry, index, context)?;
+            self.tracks.insert(index, tracks);
+            items.push(request.to_queue_item(PRELOAD_TIME_SECS));
+        }
+        Ok(items)
+    }
+
+    /// Points the subtitle keys at the current entry's tracks.
+    fn show_tracks(&self, context: &mut PlayerContext) {
+        context.subtitle_tracks = context
+            .queue
+            .current_index()
+            .and_then(|index| self.tracks.get(&index))
+            .cloned()
+            .unwrap_or_default();
+        context.active_subtitle = context.subtitle_tracks.first().map(|track| track.id);
+        context.subtitle_offset_ms = 0;
+    }
+
+    /// The receiver's id for the item at `position` in the current loop, if
+    /// it has been loaded.
+    pub fn item_id(&self, position: usize) -> Option<i32> {
+        self.item_ids.get(&position).copied()
+    }
+
+    /// Switches the receiver to the item at `position`, loading the queue
+    /// again from there when that item isn't on the receiver. The queue
+    /// follows once the receiver has switched.
+    pub fn jump_to(
+        &mut self,
+        messenger: &Messenger,
+        session_id: &str,
+        media_session_id: i32,
+        context: &PlayerContext,
+        position: usize,
+        repeat_mode: RepeatMode,
+    ) -> anyhow::Result<()> {
+        match self.item_id(position) {
+            Some(item_id) => {
+                messenger.queue_jump_to_item(media_session_id, item_id)?;
+            }
+            None => {
+                *self =
+                    Self::load_from(messenger, session_id, context, position, repeat_mode, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the queue to the receiver's current item. Returns `None` when
+    /// that hasn't changed, or else whether the queue wrapped around.
+    pub fn follow(&mut self, context: &mut PlayerContext, item_id: i32) -> Option<bool> {
+        let queue = &mut context.queue;
+        let find = |item_ids: &BTreeMap<usize, i32>| {
+            item_ids
+                .iter()
+                .find(|&(_, &id)| id == item_id)
+                .map(|(&position, _)| position)
+        };
+        let looped = if let Some(position) = find(&self.item_ids) {
+            if position == queue.position() {
+                return None;
+            }
+            let looped = position == 0 && queue.position() + 1 == queue.len();
+            queue.jump_to(position);
+            looped
+        } else {
+            let position = find(&self.next_loop_ids)?;
+            queue.start_next_loop();
+            queue.jump_to(position);
+            self.stale_ids.extend(self.item_ids.values());
+            self.item_ids = std::mem::take(&mut self.next_loop_ids);
+            true
+        };
+        self.show_tracks(context);
+        Some(looped)
+    }
+
+    /// Positions in the current loop and in the next one whose items are due
+    /// on the receiver but not appended yet.
+    fn missing(&self, queue: &Queue) -> (Range<usize>, Range<usize>) {
+        let ahead = queue.position() + 1 + LOOKAHEAD_ITEMS;
+        let appended = |item_ids: &BTreeMap<usize, i32>| {
+            item_ids
+                .last_key_value()
+                .map_or(0, |(&position, _)| position + 1)
+        };
+        let current = appended(&self.item_ids)..ahead.min(queue.len());
+        let next_end = if queue.loops() {
+            ahead.saturating_sub(queue.len()).min(queue.len())
+        } else {
+            0
+        };
+        (current, appended(&self.next_loop_ids)..next_end)
+    }
+
+    /// The entries to append, as indexes in the original order, with their
+    /// positions in the current loop and in the next one.
+    fn due(&self, queue: &mut Queue) -> (Vec<usize>, Range<usize>, Range<usize>) {
+        let (current, next) = self.missing(queue);
+        let order: Vec<usize> = queue.indexed().map(|(index, _)| index).collect();
+        let next_order = if next.is_empty() {
+            Vec::new()
+        } else {
+            queue.next_loop()
+        };
+        let indexes = order
+            .get(current.clone())
+            .into_iter()
+            .chain(next_order.get(next.clone()))
+            .flatten()
+            .copied()
+            .collect();
+        (indexes, current, next)
+    }
+
+    /// Appends the items coming up within the lookahead, starting on the next
+    /// loop near the end of a looping queue, and removes the items of a loop
+    /// that is over.
+    pub fn fill(
+        &mut self,
+        messenger: &Messenger,
+        media_session_id: i32,
+        context: &mut PlayerContext,
+    ) -> anyhow::Result<()> {
+        if !self.stale_ids.is_empty() {
+            messenger.queue_remove(media_session_id, &std::mem::take(&mut self.stale_ids))?;
+        }
+        let (indexes, current, next) = self.due(&mut context.queue);
+        if indexes.is_empty() {
+            return Ok(());
+        }
+        let items = self.items(&indexes, context)?;
+        let mut item_ids =
+            chromecast::append_to_queue(messenger, media_session_id, &items)?.into_iter();
+        self.item_ids.extend(current.zip(item_ids.by_ref()));
+        self.next_loop_ids.extend(next.zip(item_ids));
+        Ok(())
+    }
+
+    /// Passes a new repeat mode on to the receiver.
+    pub fn set_repeat(
+        &mut self,
+        messenger: &Messenger,
+        media_session_id: i32,
+        repeat_mode: RepeatMode,
+    ) -> anyhow::Result<()> {
+        if repeat_mode != RepeatMode::All && !self.next_loop_ids.is_empty() {
+            let next_loop_ids: Vec<i32> = std::mem::take(&mut self.next_loop_ids)
+                .into_values()
+                .collect();
+            messenger.queue_remove(media_session_id, &next_loop_ids)?;
+        }
+        messenger.queue_set_repeat(media_session_id, receiver_repeat(repeat_mode))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl CastQueue {
+    /// A queue the receiver gave `item_ids`, by position in the current loop.
+    pub fn with_item_ids(item_ids: impl IntoIterator<Item = (usize, i32)>) -> Self {
+        CastQueue {
+            item_ids: item_ids.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The repeat mode the receiver runs with. It only has the next few items,
+/// so rather than have it repeat those, the next loop is appended as the
+/// current one ends (see [`CastQueue::fill`]). That also lets a shuffled
+/// queue be shuffled again for each loop.
+fn receiver_repeat(repeat_mode: RepeatMode) -> RepeatMode {
+    if repeat_mode == RepeatMode::All {
+        RepeatMode::Off
+    } else {
+        repeat_mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::{MediaSource, QueueEntry};
+    use crate::shuffle::{ShuffleMode, Shuffler};
+
+    fn context(names: &[&str], shuffler: Option<Shuffler>) -> PlayerContext {
+        let entries = names
+            .iter()
+            .map(|name| QueueEntry::new(MediaSource::Remote(name.to_string())))
+            .collect();
+        PlayerContext {
+            queue: Queue::new(entries, true, shuffler),
+            ..Default::default()
+        }
+    }
+
+    fn track(id: u32) -> SubtitleTrack {
+        SubtitleTrack {
+            id,
+            name: None,
+            language: None,
+            cues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_follows_receiver_items() {
+        let mut context = context(&["a", "b", "c"], None);
+        let mut cast_queue = CastQueue {
+            item_ids: BTreeMap::from([(0, 7), (1, 8), (2, 9)]),
+            tracks: HashMap::from([(1, vec![track(1), track(2)])]),
+            ..Default::default()
+        };
+
+        assert_eq!(cast_queue.follow(&mut context, 7), None);
+        assert_eq!(cast_queue.follow(&mut context, 8), Some(false));
+        assert_eq!(context.queue.position(), 1);
+        assert_eq!(context.active_subtitle, Some(1));
+        assert_eq!(context.subtitle_tracks.len(), 2);
+
+        assert_eq!(cast_queue.follow(&mut context, 9), Some(false));
+        assert!(context.subtitle_tracks.is_empty());
+        assert_eq!(context.active_subtitle, None);
+        assert_eq!(cast_queue.follow(&mut context, 7), Some(true));
+        assert_eq!(context.queue.position(), 0);
+        assert_eq!(cast_queue.follow(&mut context, 42), None);
+    }
+
+    #[test]
+    fn test_follows_into_the_next_loop() {
+        let shuffler = Shuffler::new(ShuffleMode::Uniform, Some(1));
+        let mut context = context(&["a", "b", "c"], Some(shuffler));
+        let next = context.queue.next_loop();
+        let mut cast_queue = CastQueue {
+            item_ids: BTreeMap::from([(0, 1), (1, 2), (2, 3)]),
+            next_loop_ids: BTreeMap::from([(0, 4), (1, 5)]),
+            ..Default::default()
+        };
+        context.queue.jump_to(2);
+
+        assert_eq!(cast_queue.follow(&mut context, 4), Some(true));
+        assert_eq!(context.queue.current_index(), Some(next[0]));
+        assert_eq!(cast_queue.follow(&mut context, 5), Some(false));
+        assert_eq!(context.queue.current_index(), Some(next[1]));
+        assert!(cast_queue.next_loop_ids.is_empty());
+        // The last loop's items are removed with the next fill.
+        assert_eq!(cast_queue.stale_ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_missing_items() {
+        let mut context = context(&["a", "b", "c", "d", "e"], None);
+        context.queue.jump_to(1);
+        // Loaded from the second entry: it and the next two.
+        let mut cast_queue = CastQueue {
+            item_ids: BTreeMap::from([(1, 1), (2, 2), (3, 3)]),
+            ..Default::default()
+        };
+        assert_eq!(cast_queue.missing(&context.queue), (4..4, 0..0));
+
+        context.queue.jump_to(2);
+        assert_eq!(cast_queue.missing(&context.queue), (4..5, 0..0));
+        cast_queue.item_ids.insert(4, 4);
+
+        // Near the end the next loop starts coming in.
+        context.queue.jump_to(4);
+        assert_eq!(cast_queue.missing(&context.queue), (5..5, 0..2));
+        cast_queue.next_loop_ids.insert(0, 5);
+        assert_eq!(cast_queue.missing(&context.queue), (5..5, 1..2));
+
+        context.queue.set_loop(false);
+        assert!(cast_queue.missing(&context.queue).1.is_empty());
+    }
+
+    #[test]
+    fn test_short_queue_wraps() {
+        let mut context = context(&["a", "b"], None);
+        let mut cast_queue = CastQueue::with_item_ids([(0, 1), (1, 2)]);
+        assert_eq!(cast_queue.due(&mut context.queue), (vec![0], 2..2, 0..1));
+        cast_queue.next_loop_ids.insert(0, 3);
+
+        context.queue.jump_to(1);
+        assert_eq!(cast_queue.due(&mut context.queue), (vec![1], 2..2, 1..2));
+        cast_queue.next_loop_ids.insert(1, 4);
+        assert_eq!(cast_queue.follow(&mut context, 3), Some(true));
+        assert_eq!(cast_queue.due(&mut context.queue), (vec![0], 2..2, 0..1));
+
+        context.queue.set_loop(false);
+        assert!(cast_queue.due(&mut context.queue).0.is_empty());
+    }
+
+    #[test]
+    fn test_next_loop_is_reshuffled() {
+        let shuffler = Shuffler::new(ShuffleMode::Uniform, Some(1));
+        let mut context = context(&["a", "b", "c", "d", "e"], Some(shuffler));
+        let order: Vec<usize> = context.queue.indexed().map(|(index, _)| index).collect();
+        let cast_queue = CastQueue::with_item_ids((0..5).zip(1..));
+        context.queue.jump_to(3);
+
+        let (indexes, current, next) = cast_queue.due(&mut context.queue);
+        let next_loop = context.queue.next_loop();
+        assert_ne!(next_loop, order);
+        assert_eq!((current, next), (5..5, 0..1));
+        assert_eq!(indexes, next_loop[..1]);
+    }
+
+    #[test]
+    fn test_receiver_repeat() {
+        assert_eq!(receiver_repeat(RepeatMode::All), RepeatMode::Off);
+        assert_eq!(receiver_repeat(RepeatMode::One), RepeatMode::One);
+        assert_eq!(receiver_repeat(RepeatMode::Off), RepeatMode::Off);
+    }
+}
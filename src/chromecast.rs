@@ -1,12 +1,18 @@
+use crate::messenger::{LoadRequest, Messenger};
+use crate::queue_engine::RepeatMode;
 use crate::settings::Settings;
 use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
-use rust_cast::channels::media::{Media, StreamType};
 use rust_cast::channels::receiver::CastDeviceApp;
 use rust_cast::CastDevice;
 use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
 use std::str::FromStr;
 use std::time::Duration;
 
+/// Items sent per QUEUE_LOAD or QUEUE_INSERT, keeping each message well
+/// under the 64 KiB limit of a cast message.
+const QUEUE_BATCH_SIZE: usize = 20;
+
 pub fn discover_devices() -> anyhow::Result<Vec<ServiceInfo>> {
     let mdns = ServiceDaemon::new()?;
     let receiver = mdns.browse("_googlecast._tcp.local.")?;
@@ -99,37 +105,108 @@ pub fn select_device(
     }
 }
 
+/// The address of the local interface that routes to the device, which is
+/// where the receiver reaches the media server. Connecting a UDP socket sends
+/// nothing; it only picks the route.
+pub fn local_ip_for(device_info: &ServiceInfo) -> anyhow::Result<IpAddr> {
+    let device_ip = device_info
+        .get_addresses()
+        .iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{} has no address", device_info.get_fullname()))?;
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect((*device_ip, device_info.get_port()))?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// A launched Default Media Receiver with both the `rust_cast` device and the
+/// raw messenger connected to its transport.
+pub struct CastSession<'a> {
+    pub device: CastDevice<'a>,
+    pub messenger: Messenger,
+    pub transport_id: String,
+    pub session_id: String,
+}
+
+impl CastSession<'_> {
+    /// Loads `request` into the running receiver app.
+    pub fn load(&self, request: &LoadRequest) -> anyhow::Result<()> {
+        self.messenger.load(&self.session_id, request)?;
+        Ok(())
+    }
+}
+
+/// Hands `items` (see [`LoadRequest::to_queue_item`]) to the receiver as its
+/// queue and starts playing item `start_index`. Returns the media session id
+/// and the receiver's item ids, in queue order.
+pub fn load_queue(
+    messenger: &Messenger,
+    session_id: &str,
+    items: &[serde_json::Value],
+    start_index: usize,
+    repeat_mode: RepeatMode,
+) -> anyhow::Result<(i32, Vec<i32>)> {
+    let first_batch = items.len().min(QUEUE_BATCH_SIZE.max(start_index + 1));
+    let status =
+        messenger.queue_load(session_id, &items[..first_batch], start_index, repeat_mode)?;
+    let media_session_id = status["status"][0]["mediaSessionId"]
+        .as_i64()
+        .ok_or_else(|| anyhow::anyhow!("The receiver did not start the queue"))?
+        as i32;
+    for batch in items[first_batch..].chunks(QUEUE_BATCH_SIZE) {
+        messenger.queue_insert(media_session_id, batch)?;
+    }
+    Ok((
+        media_session_id,
+        messenger.queue_item_ids(media_session_id)?,
+    ))
+}
+
+/// Appends `items` to the receiver's queue and returns their item ids.
+pub fn append_to_queue(
+    messenger: &Messenger,
+    media_session_id: i32,
+    items: &[serde_json::Value],
+) -> anyhow::Result<Vec<i32>> {
+    for batch in items.chunks(QUEUE_BATCH_SIZE) {
+        messenger.queue_insert(media_session_id, batch)?;
+    }
+    let item_ids = messenger.queue_item_ids(media_session_id)?;
+    Ok(item_ids[item_ids.len().saturating_sub(items.len())..].to_vec())
+}
+
 pub async fn cast(
     device_info: &ServiceInfo,
-    settings: Settings,
-) -> anyhow::Result<(CastDevice, String, String)> {
+    request: &LoadRequest,
+) -> anyhow::Result<CastSession<'static>> {
+    let session = launch(device_info).await?;
+    session.load(request)?;
+    Ok(session)
+}
+
+/// Connects to the device and launches the Default Media Receiver, with
+/// nothing loaded yet.
+pub async fn launch(device_info: &ServiceInfo) -> anyhow::Result<CastSession<'static>> {
     let ip = device_info
         .get_addresses()
         .iter()
         .next()
-        .unwrap()
+        .ok_or_else(|| anyhow::anyhow!("{} has no address", device_info.get_fullname()))?
         .to_string();
     let port = device_info.get_port();
 
     let device = CastDevice::connect_without_host_verification(ip.to_owned(), port)?;
+    device.connection.connect("receiver-0")?;
     let default_media_receiver_app = CastDeviceApp::from_str("CC1AD845").unwrap();
 
     let app = device.receiver.launch_app(&default_media_receiver_app)?;
-
-    let media = Media {
-        content_id: settings.media_path.as_ref().unwrap().to_string(),
-        content_type: settings
-            .media_type
-            .clone()
-            .unwrap_or_else(|| "video/mp4".to_string()),
-        stream_type: StreamType::Buffered,
-        duration: None,
-        metadata: None,
-    };
-
-    device
-        .media
-        .load(app.transport_id.as_str(), app.session_id.as_str(), &media)?;
-
-    Ok((device, app.transport_id, app.session_id))
+    device.connection.connect(app.transport_id.as_str())?;
+
+    let messenger = Messenger::connect(&ip, port, &app.transport_id)?;
+    Ok(CastSession {
+        device,
+        messenger,
+        transport_id: app.transport_id,
+        session_id: app.session_id,
+    })
 }
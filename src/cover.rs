@@ -0,0 +1,142 @@
+use crate::server::{Resource, Resources};
+use crate::tags;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use std::fs;
+use std::path::Path;
+
+/// Longest edge of the served cover; receivers show covers well below this.
+pub const MAX_COVER_SIZE: u32 = 600;
+const JPEG_QUALITY: u8 = 85;
+const SIDECAR_NAMES: [&str; 4] = ["cover.jpg", "folder.jpg", "cover.png", "folder.png"];
+
+/// Returns the raw cover image for `media`: the embedded front cover (or any
+/// embedded picture), falling back to a cover image in the same directory.
+pub fn find_cover(media: &Path) -> Option<Vec<u8>> {
+    embedded_cover(media).or_else(|| sidecar_cover(media))
+}
+
+fn embedded_cover(media: &Path) -> Option<Vec<u8>> {
+    let tags = tags::read_tags(media).ok()?;
+    tags.cover().map(|picture| picture.data.clone())
+}
+
+fn sidecar_cover(media: &Path) -> Option<Vec<u8>> {
+    let dir = media.parent()?;
+    let entries: Vec<_> = fs::read_dir(dir).ok()?.flatten().collect();
+    // Match names case-insensitively so Cover.JPG and Folder.jpg are found too.
+    SIDECAR_NAMES.iter().find_map(|name| {
+        entries
+            .iter()
+            .find(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .eq_ignore_ascii_case(name)
+            })
+            .and_then(|entry| fs::read(entry.path()).ok())
+    })
+}
+
+/// Decodes `data`, shrinks it to fit `max_size` and re-encodes it as JPEG.
+pub fn resize_to_jpeg(data: &[u8], max_size: u32) -> anyhow::Result<Vec<u8>> {
+    let mut image = image::load_from_memory(data)?;
+    if image.width() > max_size || image.height() > max_size {
+        image = image.resize(max_size, max_size, FilterType::Lanczos3);
+    }
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY).encode_image(&image.to_rgb8())?;
+    Ok(jpeg)
+}
+
+/// Path the cover of queue entry `index` is served under.
+pub fn cover_path(index: usize) -> String {
+    format!("/cover/{index}.jpg")
+}
+
+/// Finds, resizes and serves the cover for `media`, queue entry `index`.
+/// Returns the served path, or `None` when there is no usable cover.
+pub fn serve_cover(media: &Path, index: usize, resources: &Resources) -> Option<String> {
+    let data = find_cover(media)?;
+    match resize_to_jpeg(&data, MAX_COVER_SIZE) {
+        Ok(jpeg) => {
+            let path = cover_path(index);
+            resources
+                .write()
+                .unwrap()
+                .insert(path.clone(), Resource::new("image/jpeg", jpeg));
+            Some(path)
+        }
+        Err(e) => {
+            eprintln!("Error reading cover art for {}: {e}", media.display());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use id3::frame::{Picture, PictureType};
+    use id3::{Tag, TagLike, Version};
+    use image::{ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        RgbImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_resize_to_jpeg() {
+        let jpeg = resize_to_jpeg(&png(1200, 800), MAX_COVER_SIZE).unwrap();
+        let image = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap();
+        assert_eq!((image.width(), image.height()), (600, 400));
+
+        let small = resize_to_jpeg(&png(100, 100), MAX_COVER_SIZE).unwrap();
+        let image = image::load_from_memory(&small).unwrap();
+        assert_eq!((image.width(), image.height()), (100, 100));
+
+        assert!(resize_to_jpeg(b"not an image", MAX_COVER_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_find_cover_prefers_embedded_picture() {
+        let dir = tempfile::tempdir().unwrap();
+        let media = dir.path().join("track.mp3");
+        fs::write(&media, b"").unwrap();
+        fs::write(dir.path().join("Folder.JPG"), b"sidecar").unwrap();
+
+        let mut tag = Tag::new();
+        tag.add_frame(Picture {
+            mime_type: "image/png".to_string(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: b"embedded".to_vec(),
+        });
+        tag.write_to_path(&media, Version::Id3v24).unwrap();
+        assert_eq!(find_cover(&media).unwrap(), b"embedded");
+
+        let other = dir.path().join("other.mp3");
+        fs::write(&other, b"").unwrap();
+        assert_eq!(find_cover(&other).unwrap(), b"sidecar");
+    }
+
+    #[test]
+    fn test_serve_cover() {
+        let dir = tempfile::tempdir().unwrap();
+        let media = dir.path().join("track.mp3");
+        fs::write(&media, b"").unwrap();
+        let resources = Resources::default();
+        assert_eq!(serve_cover(&media, 0, &resources), None);
+
+        fs::write(dir.path().join("cover.png"), png(800, 800)).unwrap();
+        assert_eq!(serve_cover(&media, 2, &resources).unwrap(), "/cover/2.jpg");
+        let served = resources.read().unwrap()["/cover/2.jpg"].clone();
+        assert_eq!(served.content_type, "image/jpeg");
+    }
+}
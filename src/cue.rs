@@ -0,0 +1,225 @@
+/// A parsed CUE sheet. Sheet-level `TITLE` and `PERFORMER` describe the album.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub date: Option<String>,
+    pub genre: Option<String>,
+    pub files: Vec<CueFile>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CueFile {
+    pub path: String,
+    pub tracks: Vec<CueTrack>,
+}
+
+/// A track within a file. Times are offsets into the file in seconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// `INDEX 00`, the start of the pregap before the track.
+    pub pregap_secs: Option<f64>,
+    /// `INDEX 01`, where the track itself starts.
+    pub start_secs: Option<f64>,
+    /// Where the next track in the same file begins, including its pregap.
+    /// `None` for the last track, which plays to the end of the file.
+    pub end_secs: Option<f64>,
+}
+
+impl CueTrack {
+    pub fn duration_secs(&self) -> Option<f64> {
+        Some(self.end_secs? - self.start_secs?).filter(|duration| *duration > 0.0)
+    }
+}
+
+/// Converts a CUE `mm:ss:ff` index (75 frames per second) to seconds.
+pub fn parse_msf(index: &str) -> Option<f64> {
+    let mut parts = index.split(':').map(|part| part.parse::<u32>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || seconds >= 60 || frames >= 75 {
+        return None;
+    }
+    Some(f64::from(minutes * 60 + seconds) + f64::from(frames) / 75.0)
+}
+
+/// The value of a command: the text between quotes if quoted, otherwise the
+/// rest of the line.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    match value.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+        None => value,
+    }
+}
+
+/// `FILE "name" TYPE`; unquoted names run up to the file type.
+fn parse_file_name(value: &str) -> &str {
+    let value = value.trim();
+    if value.starts_with('"') {
+        unquote(value)
+    } else {
+        value
+            .rsplit_once(char::is_whitespace)
+            .map_or(value, |(name, _)| name.trim_end())
+    }
+}
+
+pub fn parse_cue(content: &str) -> anyhow::Result<CueSheet> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut sheet = CueSheet::default();
+
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let (command, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let value = value.trim();
+        let error = |message: &str| anyhow::anyhow!("line {}: {message}", line_number + 1);
+
+        match command.to_uppercase().as_str() {
+            "FILE" => {
+                // A track whose INDEX 01 only comes after the next FILE line
+                // (pregap at the end of the previous file) belongs to the new file.
+                let pending = sheet
+                    .files
+                    .last_mut()
+                    .filter(|file| file.tracks.last().is_some_and(|t| t.start_secs.is_none()))
+                    .and_then(|file| file.tracks.pop())
+                    .map(|track| CueTrack {
+                        pregap_secs: None,
+                        ..track
+                    });
+                sheet.files.push(CueFile {
+                    path: parse_file_name(value).to_string(),
+                    tracks: pending.into_iter().collect(),
+                });
+            }
+            "TRACK" => {
+                let file = sheet
+                    .files
+                    .last_mut()
+                    .ok_or_else(|| error("TRACK before FILE"))?;
+                let number = value
+                    .split_whitespace()
+                    .next()
+                    .and_then(|number| number.parse().ok())
+                    .ok_or_else(|| error("invalid TRACK number"))?;
+                file.tracks.push(CueTrack {
+                    number,
+                    ..Default::default()
+                });
+            }
+            "INDEX" => {
+                let mut parts = value.split_whitespace();
+                let (Some(number), Some(time)) = (parts.next(), parts.next()) else {
+                    return Err(error("invalid INDEX"));
+                };
+                let time = parse_msf(time).ok_or_else(|| error("invalid INDEX time"))?;
+                let Some(file) = sheet.files.last_mut() else {
+                    return Err(error("INDEX before FILE"));
+                };
+                let count = file.tracks.len();
+                let Some(track) = file.tracks.last_mut() else {
+                    return Err(error("INDEX before TRACK"));
+                };
+                match number.parse::<u32>() {
+                    Ok(0) => track.pregap_secs = Some(time),
+                    Ok(1) => track.start_secs = Some(time),
+                    _ => continue,
+                }
+                // The previous track in this file ends where this one's
+                // first index begins.
+                if count > 1 {
+                    let previous = &mut file.tracks[count - 2];
+                    previous.end_secs.get_or_insert(time);
+                }
+            }
+            "TITLE" | "PERFORMER" => {
+                let value = Some(unquote(value).to_string()).filter(|value| !value.is_empty());
+                let track = sheet
+                    .files
+                    .last_mut()
+                    .and_then(|file| file.tracks.last_mut());
+                match (command.to_uppercase().as_str(), track) {
+                    ("TITLE", Some(track)) => track.title = value,
+                    ("TITLE", None) => sheet.title = value,
+                    (_, Some(track)) => track.performer = value,
+                    (_, None) => sheet.performer = value,
+                }
+            }
+            "REM" => {
+                let (key, value) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+                let value = Some(unquote(value).to_string()).filter(|value| !value.is_empty());
+                match key.to_uppercase().as_str() {
+                    "DATE" => sheet.date = value,
+                    "GENRE" => sheet.genre = value,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(sheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUE: &str = include_str!("../tests/fixtures/playlists/live.cue");
+
+    #[test]
+    fn test_parse_msf() {
+        assert_eq!(parse_msf("00:00:00"), Some(0.0));
+        assert_eq!(parse_msf("03:25:15"), Some(205.0 + 15.0 / 75.0));
+        assert_eq!(parse_msf("120:00:00"), Some(7200.0));
+        assert_eq!(parse_msf("00:60:00"), None);
+        assert_eq!(parse_msf("00:00:75"), None);
+        assert_eq!(parse_msf("1:2"), None);
+    }
+
+    #[test]
+    fn test_parse_cue() {
+        let sheet = parse_cue(CUE).unwrap();
+        assert_eq!(sheet.title.as_deref(), Some("Live at the Hall"));
+        assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+        assert_eq!(sheet.date.as_deref(), Some("1999"));
+        assert_eq!(sheet.genre.as_deref(), Some("Rock"));
+        assert_eq!(sheet.files.len(), 2);
+
+        let first = &sheet.files[0];
+        assert_eq!(first.path, "Live Disc 1.flac");
+        let numbers: Vec<u32> = first.tracks.iter().map(|t| t.number).collect();
+        assert_eq!(numbers, [1, 2]);
+        assert_eq!(first.tracks[0].title.as_deref(), Some("Opening"));
+        assert_eq!(first.tracks[0].start_secs, Some(0.0));
+        // Track 2's pregap starts at 3:58.
+        assert_eq!(first.tracks[0].end_secs, Some(238.0));
+        assert_eq!(first.tracks[0].duration_secs(), Some(238.0));
+        assert_eq!(first.tracks[1].pregap_secs, Some(238.0));
+        assert_eq!(first.tracks[1].start_secs, Some(240.0));
+        assert_eq!(first.tracks[1].performer.as_deref(), Some("Guest Singer"));
+        // Track 2's own end is at track 3's pregap, which lies in this file.
+        assert_eq!(first.tracks[1].end_secs, Some(479.0));
+
+        let second = &sheet.files[1];
+        assert_eq!(second.path, "disc2.wav");
+        assert_eq!(second.tracks[0].number, 3);
+        assert_eq!(second.tracks[0].pregap_secs, None);
+        assert_eq!(second.tracks[0].start_secs, Some(0.0));
+        assert_eq!(second.tracks[0].title.as_deref(), Some("Encore"));
+        assert_eq!(second.tracks[0].end_secs, None);
+        assert_eq!(second.tracks[0].duration_secs(), None);
+    }
+
+    #[test]
+    fn test_parse_cue_errors() {
+        assert!(parse_cue("TRACK 01 AUDIO\n").is_err());
+        assert!(parse_cue("FILE \"a.wav\" WAVE\n  INDEX 01 00:00:00\n").is_err());
+        assert!(parse_cue("FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 00:99:00\n").is_err());
+        let sheet = parse_cue("FILE my file.wav WAVE\nTRACK 1 AUDIO\nTITLE Bare\n").unwrap();
+        assert_eq!(sheet.files[0].path, "my file.wav");
+        assert_eq!(sheet.files[0].tracks[0].title.as_deref(), Some("Bare"));
+    }
+}
@@ -0,0 +1,82 @@
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::header::{CONTENT_TYPE, LOCATION};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use url::Url;
+
+/// Redirects followed per request, e.g. Apache's `/music` -> `/music/`.
+const MAX_REDIRECTS: usize = 5;
+
+/// A response fetched over HTTP(S), after redirects.
+pub struct Page {
+    pub url: Url,
+    pub content_type: Option<String>,
+    /// `None` for media responses, whose bodies aren't downloaded.
+    pub body: Option<String>,
+}
+
+fn is_media(content_type: &str) -> bool {
+    ["audio/", "video/", "image/", "application/octet-stream"]
+        .iter()
+        .any(|prefix| content_type.starts_with(prefix))
+}
+
+pub struct HttpClient {
+    client: Client<HttpsConnector<HttpConnector>, Empty<Bytes>>,
+}
+
+impl HttpClient {
+    pub fn new() -> Self {
+        let connector = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        HttpClient {
+            client: Client::builder(TokioExecutor::new()).build(connector),
+        }
+    }
+
+    /// Fetches `url`, following redirects. Media responses aren't read, only
+    /// their type.
+    pub async fn get(&self, url: &Url) -> anyhow::Result<Page> {
+        let mut url = url.clone();
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self.client.get(url.as_str().parse()?).await?;
+            let status = response.status();
+            if status.is_redirection() {
+                let location = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .ok_or_else(|| anyhow::anyhow!("Redirect without a location from {url}"))?;
+                url = url.join(location)?;
+                continue;
+            }
+            if !status.is_success() {
+                return Err(anyhow::anyhow!("{url} returned {status}"));
+            }
+
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let body = if content_type.as_deref().is_some_and(is_media) {
+                None
+            } else {
+                let bytes = response.into_body().collect().await?.to_bytes();
+                Some(String::from_utf8_lossy(&bytes).to_string())
+            };
+            return Ok(Page {
+                url,
+                content_type,
+                body,
+            });
+        }
+        Err(anyhow::anyhow!("Too many redirects from {url}"))
+    }
+}
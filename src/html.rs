@@ -0,0 +1,136 @@
+use crate::metadata;
+use scraper::{Html, Selector};
+use std::path::Path;
+use url::Url;
+
+/// Open Graph properties that point at playable media.
+const OG_MEDIA_PROPERTIES: [&str; 6] = [
+    "og:audio",
+    "og:audio:url",
+    "og:audio:secure_url",
+    "og:video",
+    "og:video:url",
+    "og:video:secure_url",
+];
+
+/// Whether a link's path ends in a known audio or video extension.
+fn is_media_link(link: &str) -> bool {
+    let path = link.split(['?', '#']).next().unwrap_or_default();
+    metadata::content_type_for_path(Path::new(path)).is_some()
+}
+
+/// Collects media URLs from a page in document order: `<a>` links to media
+/// files, `<audio>`, `<video>` and `<source>` sources and Open Graph media
+/// tags. Links are resolved against the page's `<base>` element, itself
+/// resolved against `base_url`, and duplicates are dropped.
+pub fn find_media_links(content: &str, base_url: Option<&Url>) -> Vec<String> {
+    let document = Html::parse_document(content);
+    let base_selector = Selector::parse("base[href]").unwrap();
+    let selector =
+        Selector::parse("a[href], audio[src], video[src], source[src], meta[content]").unwrap();
+
+    let page_base = document
+        .select(&base_selector)
+        .next()
+        .and_then(|element| element.value().attr("href"))
+        .and_then(|href| match base_url {
+            Some(base_url) => base_url.join(href).ok(),
+            None => Url::parse(href).ok(),
+        });
+    let base = page_base.as_ref().or(base_url);
+
+    let mut links: Vec<String> = Vec::new();
+    for element in document.select(&selector) {
+        let element = element.value();
+        let link = match element.name() {
+            "a" => element.attr("href").filter(|href| is_media_link(href)),
+            "meta" => element
+                .attr("property")
+                .or_else(|| element.attr("name"))
+                .filter(|property| OG_MEDIA_PROPERTIES.contains(property))
+                .and_then(|_| element.attr("content")),
+            _ => element.attr("src"),
+        };
+        let Some(link) = link.map(str::trim).filter(|link| !link.is_empty()) else {
+            continue;
+        };
+        if link.starts_with('#') || link.starts_with("javascript:") || link.starts_with("data:") {
+            continue;
+        }
+
+        let resolved = match base {
+            Some(base) => match base.join(link) {
+                Ok(url) => url.to_string(),
+                Err(_) => continue,
+            },
+            None => link.to_string(),
+        };
+        if !links.contains(&resolved) {
+            links.push(resolved);
+        }
+    }
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><head>
+        <meta property="og:audio" content="/audio/feature.mp3">
+        <meta property="og:title" content="Not media">
+        <meta property="og:video:secure_url" content="https://cdn.example.com/clip.mp4">
+    </head><body>
+        <a href="songs/one.mp3">One</a>
+        <a href="songs/two.FLAC?download=1">Two</a>
+        <a href="about.html">About</a>
+        <a href="movies/film.mkv">Film</a>
+        <a href="songs/one.mp3">One again</a>
+        <a href="javascript:play('x.mp3')">Play</a>
+        <audio src="stream.ogg"></audio>
+        <video controls>
+            <source src="video/clip.webm" type="video/webm">
+            <source src="https://cdn.example.com/clip.mp4" type="video/mp4">
+        </video>
+    </body></html>"#;
+
+    #[test]
+    fn test_find_media_links_with_base_url() {
+        let base = Url::parse("http://example.com/music/index.html").unwrap();
+        assert_eq!(
+            find_media_links(PAGE, Some(&base)),
+            [
+                "http://example.com/audio/feature.mp3",
+                "https://cdn.example.com/clip.mp4",
+                "http://example.com/music/songs/one.mp3",
+                "http://example.com/music/songs/two.FLAC?download=1",
+                "http://example.com/music/movies/film.mkv",
+                "http://example.com/music/stream.ogg",
+                "http://example.com/music/video/clip.webm",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_media_links_with_base_element() {
+        let page = r#"<head><base href="/media/"></head>
+            <a href="a%20b.mp3">A</a><audio src="http://other.example.com/c.opus"></audio>"#;
+        let base = Url::parse("https://example.com/page").unwrap();
+        assert_eq!(
+            find_media_links(page, Some(&base)),
+            [
+                "https://example.com/media/a%20b.mp3",
+                "http://other.example.com/c.opus",
+            ]
+        );
+
+        let page = r#"<base href="http://host/dir/"><a href="x.wav">X</a>"#;
+        assert_eq!(find_media_links(page, None), ["http://host/dir/x.wav"]);
+    }
+
+    #[test]
+    fn test_find_media_links_unresolved() {
+        let page = r#"<a href="x.m4a">X</a><a href="x.m4a">X</a><video src="y.mp4"></video>"#;
+        assert_eq!(find_media_links(page, None), ["x.m4a", "y.mp4"]);
+    }
+}
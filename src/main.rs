@@ -1,14 +1,35 @@
+mod ab_loop;
+mod autoindex;
+mod cast_queue;
 mod chromecast;
 mod config;
+mod cover;
+mod cue;
+mod fetch;
+mod html;
+mod matroska;
+mod messenger;
+mod metadata;
+mod output;
+mod playback;
 mod player_controls;
+mod playlist;
+mod podcast;
+mod queue;
+mod queue_engine;
+mod resume;
+mod scanner;
 pub mod server;
 mod settings;
+mod shuffle;
+mod subtitle_style;
+mod subtitles;
+mod tags;
+mod timecode;
+mod torrent;
 mod utils;
 
 use clap::{Parser, Subcommand};
-use id3::{Tag, TagLike};
-use playlist_decoder::decode_playlist;
-use scraper::{Html, Selector};
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -17,6 +38,9 @@ use std::path::{Path, PathBuf};
 struct Args {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// Output format for the audio, playlist and html subcommands
+    #[arg(long, value_enum, default_value_t, global = true)]
+    format: output::OutputFormat,
     #[clap(flatten)]
     settings: settings::Settings,
 }
@@ -29,17 +53,115 @@ enum Commands {
         #[arg(short, long)]
         file: String,
     },
-    /// Handles playlist operations
+    /// Plays a playlist, or lists its entries
     Playlist {
         /// Path to the playlist file
         #[arg(short, long)]
         file: String,
+        /// Print the entries instead of casting them
+        #[arg(short, long)]
+        list: bool,
     },
-    /// Handles HTML file operations
+    /// Plays the media linked from an HTML page, or lists the links
     Html {
         /// Path to the HTML file
         #[arg(short, long)]
         file: String,
+        /// URL the page was saved from, for resolving relative links
+        /// (defaults to the file's own location)
+        #[arg(short, long)]
+        base_url: Option<String>,
+        /// Print the links instead of casting them
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Plays a local file
+    PlayFile {
+        /// Path to the media file
+        file: String,
+    },
+    /// Plays the media files in a directory and its subdirectories
+    PlayDir {
+        /// Path to the directory
+        dir: String,
+        /// How many subdirectory levels to descend into (no limit by default)
+        #[arg(long)]
+        depth: Option<usize>,
+        /// Follow symbolic links to files and directories
+        #[arg(long)]
+        follow_links: bool,
+        /// Include hidden files and directories
+        #[arg(long)]
+        hidden: bool,
+        /// Only queue files whose path within the directory matches this
+        /// glob (repeatable)
+        #[arg(long)]
+        include: Vec<String>,
+        /// Skip files whose path within the directory matches this glob
+        /// (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Kinds of media to queue (comma-separated)
+        #[arg(long, value_enum, value_delimiter = ',', default_values_t = [scanner::MediaKind::Audio, scanner::MediaKind::Video])]
+        kind: Vec<scanner::MediaKind>,
+        /// Order to play the files in
+        #[arg(long, value_enum, default_value_t)]
+        sort: scanner::SortOrder,
+        /// Choose which of the files to play
+        #[arg(long, conflicts_with = "list")]
+        pick: bool,
+        /// Print the files instead of casting them
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Plays several files or URLs as a queue
+    PlayMultiple {
+        /// Paths or URLs, in play order
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+    /// Plays a URL: a media file, a web page's media or a directory listing
+    PlayUrl {
+        /// The URL to play
+        url: String,
+        /// How many subdirectory levels of a directory listing to crawl
+        #[arg(long, default_value_t = 2)]
+        depth: usize,
+        /// File extensions to queue from a listing (comma-separated; defaults
+        /// to all known audio and video types)
+        #[arg(long)]
+        extensions: Option<String>,
+        /// Print the media URLs instead of casting them
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Plays the audio and video files of a torrent while they download
+    PlayTorrent {
+        /// Magnet link, or URL or path of a .torrent file
+        torrent: String,
+    },
+    /// Lists a podcast feed's episodes, or plays some of them
+    Podcast {
+        /// Feed URL or path to a feed file
+        feed: String,
+        /// Play this episode (numbered as in the listing)
+        #[arg(short, long, conflicts_with = "latest")]
+        episode: Option<usize>,
+        /// Play the N most recent episodes, newest first
+        #[arg(long)]
+        latest: Option<usize>,
+    },
+    /// Lists and extracts embedded text subtitle tracks
+    Subtitles {
+        /// Path to the video file
+        #[arg(short, long)]
+        file: String,
+        /// Tracks to extract to WebVTT (comma-separated track numbers, or "all")
+        #[arg(short, long)]
+        extract: Option<String>,
+        /// Directory to write extracted tracks to (defaults to the video's directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
     },
 }
 
@@ -55,213 +177,659 @@ async fn main() -> anyhow::Result<()> {
         println!("{settings:#?}");
         return Ok(());
     }
+    // Checked up front so a typo fails before device discovery.
+    if let Some(seek) = &settings.seek {
+        timecode::parse_time(seek)?;
+    }
+    segment_bounds(&settings)?;
 
     if let Some(command) = args.command {
         match command {
             Commands::Audio { file } => {
-                handle_audio_file(&file);
+                emit_report(handle_audio_file(&file), args.format)?;
+            }
+            Commands::Playlist { file, list } => {
+                if list || args.format != output::OutputFormat::Text {
+                    emit_report(handle_playlist_file(&file), args.format)?;
+                } else {
+                    play_playlist_file(&file, &settings).await?;
+                }
             }
-            Commands::Playlist { file } => {
-                handle_playlist_file(&file);
+            Commands::Html {
+                file,
+                base_url,
+                list,
+            } => {
+                let report = handle_html_file(&file, base_url.as_deref());
+                if list || args.format != output::OutputFormat::Text || report.is_err() {
+                    emit_report(report, args.format)?;
+                } else if let Ok(report) = report {
+                    play_html_links(report, &settings).await?;
+                }
+            }
+            Commands::PlayUrl {
+                url,
+                depth,
+                extensions,
+                list,
+            } => {
+                let report = handle_url(&url, depth, extensions.as_deref()).await;
+                if list || args.format != output::OutputFormat::Text || report.is_err() {
+                    emit_report(report, args.format)?;
+                } else if let Ok(report) = report {
+                    play_html_links(report, &settings).await?;
+                }
             }
-            Commands::Html { file } => {
-                handle_html_file(&file);
+            Commands::PlayFile { file } => play_file(&file, &settings).await?,
+            Commands::PlayDir {
+                dir,
+                depth,
+                follow_links,
+                hidden,
+                include,
+                exclude,
+                kind,
+                sort,
+                pick,
+                list,
+            } => {
+                let options = scanner::ScanOptions {
+                    max_depth: depth,
+                    follow_links,
+                    hidden,
+                    include,
+                    exclude,
+                    kinds: kind,
+                    sort,
+                };
+                let report = handle_dir(&dir, &options);
+                if list || args.format != output::OutputFormat::Text || report.is_err() {
+                    emit_report(report, args.format)?;
+                } else if let Ok(report) = report {
+                    play_dir(report, pick, &settings).await?;
+                }
+            }
+            Commands::PlayMultiple { files } => play_multiple(&files, &settings).await?,
+            Commands::PlayTorrent { torrent } => play_torrent(&torrent, &settings).await?,
+            Commands::Podcast {
+                feed,
+                episode,
+                latest,
+            } => {
+                let feed_result = load_feed(&feed).await;
+                match (feed_result, episode, latest) {
+                    (Ok(parsed), Some(_), _) | (Ok(parsed), _, Some(_))
+                        if args.format == output::OutputFormat::Text =>
+                    {
+                        play_episodes(&feed, parsed, episode, latest, &settings).await?;
+                    }
+                    (feed_result, _, _) => emit_report(
+                        feed_result.map(|parsed| podcast_report(&feed, parsed)),
+                        args.format,
+                    )?,
+                }
+            }
+            Commands::Subtitles {
+                file,
+                extract,
+                output_dir,
+            } => {
+                emit_report(
+                    handle_subtitles_file(&file, extract, output_dir, &settings),
+                    args.format,
+                )?;
             }
         }
     } else if let Some(media_path) = &settings.media_path {
-        let devices = chromecast::discover_devices()?;
-        let device_info = chromecast::select_device(&settings, devices)?;
-
-        let (device, transport_id, session_id) =
-            if media_path.starts_with("http://") || media_path.starts_with("https://") {
-                chromecast::cast(&device_info, settings.clone()).await?
-            } else {
-                let file_path = PathBuf::from(media_path);
-                if !file_path.exists() {
-                    eprintln!("Error: File not found: {media_path}");
-                    return Ok(());
-                }
-
-                let (tx, rx) = tokio::sync::oneshot::channel();
-                let (server_addr, server_handle) = server::start_server(file_path, rx).await?;
-                let media_url = format!("http://{server_addr}");
-                let mut settings_with_url = settings.clone();
-                settings_with_url.media_path = Some(media_url);
+        play_media_path(media_path, &settings).await?;
+    }
 
-                let (device, transport_id, session_id) =
-                    chromecast::cast(&device_info, settings_with_url).await?;
+    Ok(())
+}
 
-                if settings.exit {
-                    let _ = tx.send(());
-                    server_handle.await?;
-                }
-                (device, transport_id, session_id)
-            };
-        player_controls::handle_player_controls(device, transport_id, session_id).await?;
+/// Prints a subcommand result and exits with a non-zero status on error.
+fn emit_report<R: output::Report>(
+    result: Result<R, output::CommandError>,
+    format: output::OutputFormat,
+) -> anyhow::Result<()> {
+    output::emit(
+        &result,
+        format,
+        &mut std::io::stdout(),
+        &mut std::io::stderr(),
+    )?;
+    if result.is_err() {
+        std::process::exit(1);
     }
-
     Ok(())
 }
 
-fn handle_audio_file(file_path: &str) {
-    let path = Path::new(file_path);
+fn read_input(file_path: &str, kind: &str) -> Result<String, output::CommandError> {
+    use output::{CommandError, ErrorKind};
 
+    let path = Path::new(file_path);
     if !path.exists() {
-        eprintln!("Error: File not found at {file_path}");
-        return;
+        return Err(CommandError::new(
+            ErrorKind::NotFound,
+            format!("{kind} file not found at {file_path}"),
+        ));
     }
+    fs::read_to_string(path).map_err(|e| {
+        CommandError::new(
+            ErrorKind::Io,
+            format!(
+                "Error reading {} file {file_path}: {e}",
+                kind.to_lowercase()
+            ),
+        )
+    })
+}
 
-    match Tag::read_from_path(path) {
-        Ok(tag) => {
-            println!("--- Audio Metadata ---");
-            if let Some(title) = tag.title() {
-                println!("Title: {title}");
-            }
-            if let Some(artist) = tag.artist() {
-                println!("Artist: {artist}");
-            }
-            if let Some(album) = tag.album() {
-                println!("Album: {album}");
-            }
-            if let Some(year) = tag.year() {
-                println!("Year: {year}");
-            }
-            if let Some(genre) = tag.genre() {
-                println!("Genre: {genre}");
-            }
-            if let Some(track) = tag.track() {
-                println!("Track: {track}");
-            }
-            if let Some(total_tracks) = tag.total_tracks() {
-                println!("Total Tracks: {total_tracks}");
-            }
-            if let Some(disc) = tag.disc() {
-                println!("Disc: {disc}");
-            }
-            if let Some(total_discs) = tag.total_discs() {
-                println!("Total Discs: {total_discs}");
-            }
-            if let Some(comment) = tag.comments().next() {
-                println!("Comment: {}", comment.text);
-            }
+fn handle_audio_file(file_path: &str) -> Result<output::AudioReport, output::CommandError> {
+    use output::{CommandError, ErrorKind};
 
-            for picture in tag.pictures() {
-                println!(
-                    "Cover Art: MIME Type = {}, Size = {} bytes",
-                    picture.mime_type,
-                    picture.data.len()
-                );
-            }
-        }
-        Err(e) => {
-            eprintln!("Error reading tags from {file_path}: {e}");
-        }
+    let path = Path::new(file_path);
+    if !path.exists() {
+        return Err(CommandError::new(
+            ErrorKind::NotFound,
+            format!("File not found at {file_path}"),
+        ));
     }
+
+    let tags = tags::read_tags(path).map_err(|e| {
+        CommandError::new(
+            ErrorKind::Invalid,
+            format!("Error reading tags from {file_path}: {e}"),
+        )
+    })?;
+    let cover = cover::find_cover(path)
+        .and_then(|data| cover::resize_to_jpeg(&data, cover::MAX_COVER_SIZE).ok())
+        .map(|jpeg| output::CoverInfo {
+            content_type: "image/jpeg".to_string(),
+            size: jpeg.len(),
+        });
+    Ok(output::AudioReport::new(file_path, tags, cover))
 }
 
-fn handle_playlist_file(file_path: &str) {
-    let path = Path::new(file_path);
+fn handle_playlist_file(file_path: &str) -> Result<output::PlaylistReport, output::CommandError> {
+    use output::{CommandError, ErrorKind, PlaylistEntryReport};
 
-    if !path.exists() {
-        eprintln!("Error: Playlist file not found at {file_path}");
-        return;
-    }
+    let content = read_input(file_path, "Playlist")?;
+    let extension = Path::new(file_path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
 
-    let content = match fs::read_to_string(path) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error reading playlist file {file_path}: {e}");
-            return;
+    let (entries, album) = match playlist::PlaylistFormat::from_extension(&extension) {
+        Some(format) => (
+            playlist::parse_playlist(&content, format)
+                .map_err(|e| {
+                    CommandError::new(
+                        ErrorKind::Invalid,
+                        format!("Error parsing playlist {file_path}: {e}"),
+                    )
+                })?
+                .into_iter()
+                .map(|entry| {
+                    let segment = entry.segment();
+                    PlaylistEntryReport {
+                        path: entry.location,
+                        title: entry.title,
+                        artist: entry.artist,
+                        duration_secs: match segment {
+                            Some((_, length)) => length,
+                            None => entry.duration_secs,
+                        },
+                        image: entry.image,
+                        start_secs: segment.map(|(start, _)| start),
+                    }
+                })
+                .collect(),
+            None,
+        ),
+        None if extension == "cue" => {
+            let sheet = cue::parse_cue(&content).map_err(|e| {
+                CommandError::new(
+                    ErrorKind::Invalid,
+                    format!("Error parsing CUE sheet {file_path}: {e}"),
+                )
+            })?;
+            cue_report(sheet)
+        }
+        None => {
+            return Err(CommandError::new(
+                ErrorKind::UnsupportedFormat,
+                format!("Unsupported playlist format for file {file_path}"),
+            ));
         }
     };
 
-    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+    Ok(output::PlaylistReport {
+        path: file_path.to_string(),
+        format: extension,
+        album,
+        entries,
+    })
+}
 
-    match extension.to_lowercase().as_str() {
-        "m3u" | "m3u8" | "pls" | "xspf" => {
-            // playlist-decoder handles these
-            let playlist_entries = decode_playlist(&content);
-            println!("--- {} Playlist ---", extension.to_uppercase());
-            for entry in playlist_entries {
-                println!("Path: {entry}");
-            }
+/// Resolves a playlist's entries into a queue and casts it. Entries that
+/// can't be found are reported and skipped.
+async fn play_playlist_file(file_path: &str, settings: &settings::Settings) -> anyhow::Result<()> {
+    let report = match handle_playlist_file(file_path) {
+        Ok(report) => report,
+        Err(error) => {
+            return emit_report::<output::PlaylistReport>(Err(error), output::OutputFormat::Text)
+        }
+    };
+    let base_dir = Path::new(file_path).parent().unwrap_or(Path::new("."));
+
+    let album = report.album.and_then(|album| album.title);
+    let mut entries: Vec<queue::QueueEntry> = Vec::new();
+    for entry in report.entries {
+        match queue::resolve_entry(base_dir, &entry.path) {
+            Ok(source) => entries.push(queue::QueueEntry {
+                source,
+                title: entry.title,
+                artist: entry.artist,
+                duration_secs: entry.duration_secs,
+                image: entry.image,
+                album: album.clone().filter(|_| entry.start_secs.is_some()),
+                content_type: None,
+                start_secs: entry.start_secs,
+            }),
+            Err(e) => eprintln!("Skipping {}: {e}", entry.path),
         }
-        "cue" => {
-            handle_cue_file(&content);
+    }
+    play_entries(entries, file_path, settings).await
+}
+
+/// Casts the media found on an HTML page or behind a URL as a queue.
+async fn play_html_links(
+    report: output::LinksReport,
+    settings: &settings::Settings,
+) -> anyhow::Result<()> {
+    let base_dir = Path::new(&report.path).parent().unwrap_or(Path::new("."));
+    let mut entries = Vec::new();
+    for link in &report.links {
+        match queue::resolve_entry(base_dir, link) {
+            Ok(source) => entries.push(queue::QueueEntry::new(source)),
+            Err(e) => eprintln!("Skipping {link}: {e}"),
         }
-        _ => {
-            eprintln!("Error: Unsupported playlist format for file {file_path}");
+    }
+    play_entries(entries, &report.path, settings).await
+}
+
+/// Picks a device and casts `entries` as a queue.
+async fn play_entries(
+    mut entries: Vec<queue::QueueEntry>,
+    file_path: &str,
+    settings: &settings::Settings,
+) -> anyhow::Result<()> {
+    if entries.is_empty() {
+        return Err(anyhow::anyhow!("No playable entries in {file_path}"));
+    }
+
+    let devices = chromecast::discover_devices()?;
+    let device_info = chromecast::select_device(settings, devices)?;
+    let shuffler =
+        (settings.shuffle || settings.shuffle_mode.is_some() || settings.shuffle_seed.is_some())
+            .then(|| {
+                let shuffler = shuffle::Shuffler::new(
+                    settings.shuffle_mode.unwrap_or_default(),
+                    settings.shuffle_seed,
+                );
+                println!("Shuffle seed: {}", shuffler.seed());
+                shuffler
+            });
+    let (start, end) = segment_bounds(settings)?;
+    for entry in &mut entries {
+        entry.trim(start, end);
+    }
+    let queue = queue::Queue::new(entries, settings.loop_playback, shuffler);
+    playback::play_queue(settings, &device_info, queue).await
+}
+
+/// The `--start` and `--end` times, in seconds.
+fn segment_bounds(settings: &settings::Settings) -> anyhow::Result<(Option<f64>, Option<f64>)> {
+    let start = settings
+        .start
+        .as_deref()
+        .map(timecode::parse_secs)
+        .transpose()?;
+    let end = settings
+        .end
+        .as_deref()
+        .map(timecode::parse_secs)
+        .transpose()?;
+    if let (Some(start), Some(end)) = (start, end) {
+        if end <= start {
+            return Err(anyhow::anyhow!("--end must come after --start"));
         }
     }
+    Ok((start, end))
 }
 
-fn handle_cue_file(content: &str) {
-    println!("--- CUE Sheet ---");
-    let mut current_file = String::new();
-    let mut current_track_number = 0;
+/// Plays the positional media path as the matching play command would.
+async fn play_media_path(media_path: &str, settings: &settings::Settings) -> anyhow::Result<()> {
+    if media_path.starts_with("magnet:") || media_path.ends_with(".torrent") {
+        play_torrent(media_path, settings).await
+    } else if media_path.starts_with("http://") || media_path.starts_with("https://") {
+        let source = queue::MediaSource::Remote(media_path.to_string());
+        play_entries(vec![queue::QueueEntry::new(source)], media_path, settings).await
+    } else if Path::new(media_path).is_dir() {
+        let report = handle_dir(media_path, &scanner::ScanOptions::default())
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        play_dir(report, false, settings).await
+    } else {
+        play_file(media_path, settings).await
+    }
+}
 
-    for line in content.lines() {
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
+async fn play_file(file_path: &str, settings: &settings::Settings) -> anyhow::Result<()> {
+    let path = PathBuf::from(file_path);
+    if !path.is_file() {
+        return Err(anyhow::anyhow!("File not found: {file_path}"));
+    }
+    let entry = queue::QueueEntry::new(queue::MediaSource::Local(path));
+    play_entries(vec![entry], file_path, settings).await
+}
+
+/// Lists the media files under `dir_path` that pass the scan options.
+fn handle_dir(
+    dir_path: &str,
+    options: &scanner::ScanOptions,
+) -> Result<output::LinksReport, output::CommandError> {
+    use output::{CommandError, ErrorKind};
+
+    if !Path::new(dir_path).is_dir() {
+        return Err(CommandError::new(
+            ErrorKind::NotFound,
+            format!("Directory not found at {dir_path}"),
+        ));
+    }
+    let files = scanner::scan(Path::new(dir_path), options)
+        .map_err(|e| CommandError::new(ErrorKind::Invalid, e.to_string()))?;
+    Ok(output::LinksReport {
+        path: dir_path.to_string(),
+        links: files
+            .into_iter()
+            .map(|file| file.to_string_lossy().to_string())
+            .collect(),
+    })
+}
+
+/// Casts the scanned files as a queue, or the ones picked from them.
+async fn play_dir(
+    report: output::LinksReport,
+    pick: bool,
+    settings: &settings::Settings,
+) -> anyhow::Result<()> {
+    let mut files: Vec<PathBuf> = report.links.iter().map(PathBuf::from).collect();
+    if pick && !files.is_empty() {
+        files = scanner::pick_files(
+            &files,
+            Path::new(&report.path),
+            &mut std::io::stdin().lock(),
+            &mut std::io::stdout(),
+        )?;
+    }
+
+    let entries = files
+        .into_iter()
+        .map(|path| queue::QueueEntry {
+            content_type: scanner::content_type(&path).map(str::to_string),
+            ..queue::QueueEntry::new(queue::MediaSource::Local(path))
+        })
+        .collect();
+    play_entries(entries, &report.path, settings).await
+}
+
+/// Queues files and URLs in the order given. Missing files are reported and
+/// skipped.
+async fn play_multiple(files: &[String], settings: &settings::Settings) -> anyhow::Result<()> {
+    let mut entries = Vec::new();
+    for file in files {
+        match queue::resolve_entry(Path::new("."), file) {
+            Ok(source) => entries.push(queue::QueueEntry::new(source)),
+            Err(e) => eprintln!("Skipping {file}: {e}"),
         }
+    }
+    play_entries(entries, "the given files", settings).await
+}
 
-        if line.starts_with("FILE") {
-            if let Some(file_path_start) = line.find('"') {
-                if let Some(file_path_end) = line[file_path_start + 1..].find('"') {
-                    current_file =
-                        line[file_path_start + 1..file_path_start + 1 + file_path_end].to_string();
-                    println!("File: {current_file}");
-                }
-            }
-        } else if line.starts_with("TRACK") {
-            if let Some(track_num_str) = line.split_whitespace().nth(1) {
-                if let Ok(track_num) = track_num_str.parse::<u32>() {
-                    current_track_number = track_num;
-                    println!("  Track: {current_track_number}");
-                }
-            }
-        } else if line.starts_with("TITLE") {
-            if let Some(title_start) = line.find('"') {
-                if let Some(title_end) = line[title_start + 1..].find('"') {
-                    let title = line[title_start + 1..title_start + 1 + title_end].to_string();
-                    println!("    Title: {title}");
-                }
-            }
-        } else if line.starts_with("INDEX") {
-            if let Some(index_parts) = line.split_whitespace().nth(2) {
-                println!("    Index: {index_parts}");
-            }
+async fn play_torrent(torrent: &str, settings: &settings::Settings) -> anyhow::Result<()> {
+    println!("Fetching torrent metadata...");
+    let entries = torrent::media_entries(torrent).await?;
+    play_entries(entries, torrent, settings).await
+}
+
+/// Lists every track of a CUE sheet, with its offset into the file.
+fn cue_report(
+    sheet: cue::CueSheet,
+) -> (
+    Vec<output::PlaylistEntryReport>,
+    Option<output::AlbumReport>,
+) {
+    let mut entries = Vec::new();
+    for file in &sheet.files {
+        for track in &file.tracks {
+            entries.push(output::PlaylistEntryReport {
+                path: file.path.clone(),
+                title: track.title.clone(),
+                artist: track.performer.clone().or_else(|| sheet.performer.clone()),
+                duration_secs: track.duration_secs(),
+                image: None,
+                start_secs: Some(track.start_secs.unwrap_or_default()),
+            });
         }
     }
+    let album = output::AlbumReport {
+        title: sheet.title,
+        performer: sheet.performer,
+        date: sheet.date,
+        genre: sheet.genre,
+    };
+    (entries, Some(album))
 }
 
-fn handle_html_file(file_path: &str) {
-    let path = Path::new(file_path);
+fn handle_html_file(
+    file_path: &str,
+    base_url: Option<&str>,
+) -> Result<output::LinksReport, output::CommandError> {
+    use output::{CommandError, ErrorKind};
 
-    if !path.exists() {
-        eprintln!("Error: HTML file not found at {file_path}");
-        return;
+    let content = read_input(file_path, "HTML")?;
+    // Without a base URL, relative links point next to the file itself.
+    let base_url = match base_url {
+        Some(base_url) => url::Url::parse(base_url).map_err(|e| {
+            CommandError::new(
+                ErrorKind::Invalid,
+                format!("Invalid base URL {base_url}: {e}"),
+            )
+        })?,
+        None => std::path::absolute(file_path)
+            .ok()
+            .and_then(|path| url::Url::from_file_path(path).ok())
+            .ok_or_else(|| {
+                CommandError::new(ErrorKind::Invalid, format!("Invalid path {file_path}"))
+            })?,
+    };
+
+    Ok(output::LinksReport {
+        path: file_path.to_string(),
+        links: html::find_media_links(&content, Some(&base_url)),
+    })
+}
+
+/// Finds the media behind a URL, crawling directory listings.
+async fn handle_url(
+    url: &str,
+    depth: usize,
+    extensions: Option<&str>,
+) -> Result<output::LinksReport, output::CommandError> {
+    use output::{CommandError, ErrorKind};
+
+    let parsed = url::Url::parse(url)
+        .map_err(|e| CommandError::new(ErrorKind::Invalid, format!("Invalid URL {url}: {e}")))?;
+    let crawler = autoindex::Crawler::new(autoindex::ExtensionFilter::new(extensions), depth);
+    let links = crawler
+        .media_urls(&parsed)
+        .await
+        .map_err(|e| CommandError::new(ErrorKind::Io, format!("Error fetching {url}: {e}")))?;
+    Ok(output::LinksReport {
+        path: url.to_string(),
+        links,
+    })
+}
+
+async fn load_feed(source: &str) -> Result<podcast::Feed, output::CommandError> {
+    use output::{CommandError, ErrorKind};
+
+    let content = if source.starts_with("http://") || source.starts_with("https://") {
+        let url = url::Url::parse(source).map_err(|e| {
+            CommandError::new(ErrorKind::Invalid, format!("Invalid URL {source}: {e}"))
+        })?;
+        let page = fetch::HttpClient::new().get(&url).await.map_err(|e| {
+            CommandError::new(ErrorKind::Io, format!("Error fetching {source}: {e}"))
+        })?;
+        page.body.ok_or_else(|| {
+            CommandError::new(
+                ErrorKind::UnsupportedFormat,
+                format!("{source} is not a feed"),
+            )
+        })?
+    } else {
+        read_input(source, "Feed")?
+    };
+    podcast::parse_feed(&content).map_err(|e| {
+        CommandError::new(
+            ErrorKind::UnsupportedFormat,
+            format!("Error parsing feed {source}: {e}"),
+        )
+    })
+}
+
+fn podcast_report(source: &str, feed: podcast::Feed) -> output::PodcastReport {
+    output::PodcastReport {
+        source: source.to_string(),
+        title: feed.title,
+        author: feed.author,
+        image: feed.image,
+        episodes: feed
+            .episodes
+            .into_iter()
+            .enumerate()
+            .map(|(index, episode)| output::EpisodeReport {
+                number: index + 1,
+                title: episode.title,
+                published: episode.published,
+                duration_secs: episode.duration_secs,
+                url: episode.url,
+                image: episode.image,
+            })
+            .collect(),
     }
+}
 
-    let content = match fs::read_to_string(path) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error reading HTML file {file_path}: {e}");
-            return;
+/// Casts the chosen episode, or the latest ones, with the episode title and
+/// artwork (falling back to the feed's) as metadata.
+async fn play_episodes(
+    source: &str,
+    feed: podcast::Feed,
+    episode: Option<usize>,
+    latest: Option<usize>,
+    settings: &settings::Settings,
+) -> anyhow::Result<()> {
+    let episodes = match (episode, latest) {
+        (Some(number), _) => {
+            let episode = number
+                .checked_sub(1)
+                .and_then(|index| feed.episodes.get(index))
+                .ok_or_else(|| {
+                    anyhow::anyhow!("No episode {number}; the feed has {}", feed.episodes.len())
+                })?;
+            vec![episode]
         }
+        (None, Some(count)) => feed.latest(count),
+        (None, None) => feed.episodes.iter().collect(),
     };
 
-    let document = Html::parse_document(&content);
-    let selector = Selector::parse(
-        "a[href$='.mp3'], a[href$='.flac'], a[href$='.ogg'], a[href$='.wav'], a[href$='.m4a']",
-    )
-    .unwrap();
+    let entries = episodes
+        .into_iter()
+        .map(|episode| queue::QueueEntry {
+            title: episode.title.clone(),
+            artist: feed.author.clone().or_else(|| feed.title.clone()),
+            album: feed.title.clone(),
+            duration_secs: episode.duration_secs,
+            image: episode.image.clone().or_else(|| feed.image.clone()),
+            content_type: episode.content_type.clone(),
+            ..queue::QueueEntry::new(queue::MediaSource::Remote(episode.url.clone()))
+        })
+        .collect();
+    play_entries(entries, source, settings).await
+}
+
+fn handle_subtitles_file(
+    file_path: &str,
+    extract: Option<String>,
+    output_dir: Option<String>,
+    settings: &settings::Settings,
+) -> Result<output::SubtitlesReport, output::CommandError> {
+    use output::{CommandError, ErrorKind};
+
+    let path = Path::new(file_path);
+
+    if !path.exists() {
+        return Err(CommandError::new(
+            ErrorKind::NotFound,
+            format!("Video file not found at {file_path}"),
+        ));
+    }
+
+    let extractor = settings.subtitle_extractor.as_deref();
+    let tracks = subtitles::list_embedded_tracks(path, extractor).map_err(|e| {
+        CommandError::new(
+            ErrorKind::Io,
+            format!("Error reading subtitle tracks from {file_path}: {e}"),
+        )
+    })?;
+
+    let mut report = output::SubtitlesReport::new(file_path, &tracks);
+    let Some(selection) = extract else {
+        return Ok(report);
+    };
+    let selected = subtitles::select_tracks(&selection, &tracks)
+        .map_err(|e| CommandError::new(ErrorKind::Invalid, e.to_string()))?;
+    let extracted =
+        subtitles::extract_embedded_tracks(path, &selected, extractor).map_err(|e| {
+            CommandError::new(
+                ErrorKind::Io,
+                format!("Error extracting subtitles from {file_path}: {e}"),
+            )
+        })?;
 
-    println!("--- Audio Links in HTML ---");
-    for element in document.select(&selector) {
-        if let Some(href) = element.value().attr("href") {
-            println!("Link: {href}");
+    let output_dir = output_dir
+        .map(PathBuf::from)
+        .or_else(|| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    for (track, cues) in selected.iter().zip(extracted) {
+        let mut file_name = format!("{stem}.{}", track.number);
+        if let Some(language) = &track.language {
+            file_name.push_str(&format!(".{language}"));
         }
+        file_name.push_str(".vtt");
+        let output_path = output_dir.join(file_name);
+        fs::write(&output_path, subtitles::to_webvtt(&cues, 0)).map_err(|e| {
+            CommandError::new(
+                ErrorKind::Io,
+                format!("Error writing {}: {e}", output_path.display()),
+            )
+        })?;
+        report
+            .extracted
+            .push(output_path.to_string_lossy().into_owned());
     }
+    Ok(report)
 }
@@ -0,0 +1,585 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+const EBML_HEADER: u64 = 0x1A45_DFA3;
+const SEGMENT: u64 = 0x1853_8067;
+const SEEK_HEAD: u64 = 0x114D_9B74;
+const INFO: u64 = 0x1549_A966;
+const TIMECODE_SCALE: u64 = 0x2A_D7B1;
+const TRACKS: u64 = 0x1654_AE6B;
+const TRACK_ENTRY: u64 = 0xAE;
+const TRACK_NUMBER: u64 = 0xD7;
+const TRACK_TYPE: u64 = 0x83;
+const CODEC_ID: u64 = 0x86;
+const LANGUAGE: u64 = 0x22_B59C;
+const NAME: u64 = 0x53_6E;
+const FLAG_DEFAULT: u64 = 0x88;
+const FLAG_FORCED: u64 = 0x55AA;
+const CLUSTER: u64 = 0x1F43_B675;
+const TIMECODE: u64 = 0xE7;
+const SIMPLE_BLOCK: u64 = 0xA3;
+const BLOCK_GROUP: u64 = 0xA0;
+const BLOCK: u64 = 0xA1;
+const BLOCK_DURATION: u64 = 0x9B;
+const CUES: u64 = 0x1C53_BB6B;
+const ATTACHMENTS: u64 = 0x1941_A469;
+const CHAPTERS: u64 = 0x1043_A770;
+const TAGS: u64 = 0x1254_C367;
+
+const TRACK_TYPE_SUBTITLE: u64 = 0x11;
+const DEFAULT_TIMECODE_SCALE: u64 = 1_000_000;
+const MAX_STRING_SIZE: u64 = 1 << 20;
+
+/// A subtitle track declared in the `Tracks` element.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatroskaTrack {
+    pub number: u64,
+    pub codec_id: String,
+    pub language: Option<String>,
+    pub name: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+}
+
+impl MatroskaTrack {
+    pub fn is_text(&self) -> bool {
+        self.codec_id.starts_with("S_TEXT/")
+    }
+}
+
+/// The payload of a single subtitle block with its absolute timing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextBlock {
+    pub track: u64,
+    pub start_ms: i64,
+    pub duration_ms: Option<i64>,
+    pub data: Vec<u8>,
+}
+
+struct Header {
+    id: u64,
+    size: Option<u64>,
+    data_start: u64,
+}
+
+impl Header {
+    fn end(&self) -> Option<u64> {
+        self.size.map(|size| self.data_start + size)
+    }
+}
+
+pub fn is_matroska<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut magic = [0u8; 4];
+    let is_matroska = match reader.read_exact(&mut magic) {
+        Ok(()) => u32::from_be_bytes(magic) as u64 == EBML_HEADER,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => false,
+        Err(e) => return Err(e),
+    };
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(is_matroska)
+}
+
+/// Lists the subtitle tracks of a Matroska/WebM file.
+pub fn read_subtitle_tracks<R: Read + Seek>(reader: &mut R) -> io::Result<Vec<MatroskaTrack>> {
+    let mut parser = Parser::new(reader, None)?;
+    parser.run()?;
+    Ok(parser.tracks)
+}
+
+/// Collects every block belonging to `tracks`, in file order.
+pub fn read_text_blocks<R: Read + Seek>(
+    reader: &mut R,
+    tracks: &[u64],
+) -> io::Result<Vec<TextBlock>> {
+    let mut parser = Parser::new(reader, Some(tracks))?;
+    parser.run()?;
+    Ok(parser.blocks)
+}
+
+struct Parser<'r, 't, R> {
+    reader: &'r mut R,
+    wanted: Option<&'t [u64]>,
+    timecode_scale: u64,
+    tracks: Vec<MatroskaTrack>,
+    blocks: Vec<TextBlock>,
+}
+
+impl<'r, 't, R: Read + Seek> Parser<'r, 't, R> {
+    fn new(reader: &'r mut R, wanted: Option<&'t [u64]>) -> io::Result<Self> {
+        reader.seek(SeekFrom::Start(0))?;
+        Ok(Parser {
+            reader,
+            wanted,
+            timecode_scale: DEFAULT_TIMECODE_SCALE,
+            tracks: Vec::new(),
+            blocks: Vec::new(),
+        })
+    }
+
+    fn run(&mut self) -> io::Result<()> {
+        match self.read_header()? {
+            Some(header) if header.id == EBML_HEADER => self.skip(&header)?,
+            _ => return Err(invalid_data("not a Matroska file")),
+        }
+
+        while let Some(header) = self.read_header()? {
+            if header.id == SEGMENT {
+                return self.read_segment(header.end());
+            }
+            self.skip(&header)?;
+        }
+        Err(invalid_data("no Segment element"))
+    }
+
+    fn read_segment(&mut self, end: Option<u64>) -> io::Result<()> {
+        while end.is_none_or(|end| self.position() < end) {
+            let Some(header) = self.read_header()? else {
+                break;
+            };
+            match header.id {
+                INFO => self.read_info(&header)?,
+                TRACKS => {
+                    self.read_tracks(&header)?;
+                    if self.wanted.is_none() {
+                        return Ok(());
+                    }
+                }
+                CLUSTER if self.wanted.is_some() => self.read_cluster(&header)?,
+                _ => self.skip(&header)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn read_info(&mut self, info: &Header) -> io::Result<()> {
+        let end = self.known_end(info)?;
+        while self.position() < end {
+            let Some(header) = self.read_header()? else {
+                break;
+            };
+            match header.id {
+                TIMECODE_SCALE => self.timecode_scale = self.read_uint(&header)?,
+                _ => self.skip(&header)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn read_tracks(&mut self, tracks: &Header) -> io::Result<()> {
+        let end = self.known_end(tracks)?;
+        while self.position() < end {
+            let Some(header) = self.read_header()? else {
+                break;
+            };
+            if header.id == TRACK_ENTRY {
+                if let Some(track) = self.read_track_entry(&header)? {
+                    self.tracks.push(track);
+                }
+            } else {
+                self.skip(&header)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_track_entry(&mut self, entry: &Header) -> io::Result<Option<MatroskaTrack>> {
+        let end = self.known_end(entry)?;
+        let mut track = MatroskaTrack {
+            number: 0,
+            codec_id: String::new(),
+            language: None,
+            name: None,
+            default: true,
+            forced: false,
+        };
+        let mut track_type = 0;
+
+        while self.position() < end {
+            let Some(header) = self.read_header()? else {
+                break;
+            };
+            match header.id {
+                TRACK_NUMBER => track.number = self.read_uint(&header)?,
+                TRACK_TYPE => track_type = self.read_uint(&header)?,
+                CODEC_ID => track.codec_id = self.read_string(&header)?,
+                LANGUAGE => {
+                    track.language = Some(self.read_string(&header)?).filter(|l| l != "und")
+                }
+                NAME => track.name = Some(self.read_string(&header)?),
+                FLAG_DEFAULT => track.default = self.read_uint(&header)? != 0,
+                FLAG_FORCED => track.forced = self.read_uint(&header)? != 0,
+                _ => self.skip(&header)?,
+            }
+        }
+
+        Ok((track_type == TRACK_TYPE_SUBTITLE).then_some(track))
+    }
+
+    fn read_cluster(&mut self, cluster: &Header) -> io::Result<()> {
+        let end = cluster.end();
+        let mut cluster_timecode = 0;
+
+        while end.is_none_or(|end| self.position() < end) {
+            let start = self.position();
+            let Some(header) = self.read_header()? else {
+                break;
+            };
+            match header.id {
+                TIMECODE => cluster_timecode = self.read_uint(&header)?,
+                SIMPLE_BLOCK => {
+                    if let Some(block) = self.read_block(&header, cluster_timecode)? {
+                        self.blocks.push(block);
+                    }
+                }
+                BLOCK_GROUP => self.read_block_group(&header, cluster_timecode)?,
+                id if end.is_none() && is_top_level(id) => {
+                    // An unknown-sized cluster ends where the next top-level element starts.
+                    self.skip_to(start)?;
+                    break;
+                }
+                _ => self.skip(&header)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn read_block_group(&mut self, group: &Header, cluster_timecode: u64) -> io::Result<()> {
+        let end = group.end();
+        let mut block = None;
+        let mut duration = None;
+
+        while end.is_none_or(|end| self.position() < end) {
+            let start = self.position();
+            let Some(header) = self.read_header()? else {
+                break;
+            };
+            match header.id {
+                BLOCK => block = self.read_block(&header, cluster_timecode)?,
+                BLOCK_DURATION => duration = Some(self.read_uint(&header)?),
+                id if end.is_none() && ends_block_group(id) => {
+                    // Like a cluster, an unknown-sized group ends where the next
+                    // element that can't be inside it starts.
+                    self.skip_to(start)?;
+                    break;
+                }
+                _ => self.skip(&header)?,
+            }
+        }
+
+        if let Some(mut block) = block {
+            if let Some(duration) = duration {
+                block.duration_ms = Some(self.to_ms(duration as i64));
+            }
+            self.blocks.push(block);
+        }
+        Ok(())
+    }
+
+    fn read_block(
+        &mut self,
+        header: &Header,
+        cluster_timecode: u64,
+    ) -> io::Result<Option<TextBlock>> {
+        let end = self.known_end(header)?;
+        let (track, _) = self.read_vint()?;
+        if !self.wanted.is_some_and(|wanted| wanted.contains(&track)) {
+            self.skip_to(end)?;
+            return Ok(None);
+        }
+
+        let mut fixed = [0u8; 3];
+        self.reader.read_exact(&mut fixed)?;
+        let relative_timecode = i16::from_be_bytes([fixed[0], fixed[1]]);
+        let lacing = fixed[2] & 0x06;
+        if lacing != 0 {
+            // Subtitle tracks are never laced in practice; skip rather than misparse.
+            self.skip_to(end)?;
+            return Ok(None);
+        }
+
+        let remaining = end.saturating_sub(self.position());
+        if remaining > MAX_STRING_SIZE {
+            return Err(invalid_data("subtitle block too large"));
+        }
+        let mut data = vec![0u8; remaining as usize];
+        self.reader.read_exact(&mut data)?;
+
+        Ok(Some(TextBlock {
+            track,
+            start_ms: self.to_ms(cluster_timecode as i64 + i64::from(relative_timecode)),
+            duration_ms: None,
+            data,
+        }))
+    }
+
+    fn to_ms(&self, timecode: i64) -> i64 {
+        (timecode as i128 * self.timecode_scale as i128 / 1_000_000) as i64
+    }
+
+    fn position(&mut self) -> u64 {
+        self.reader.stream_position().unwrap_or(u64::MAX)
+    }
+
+    fn read_header(&mut self) -> io::Result<Option<Header>> {
+        let mut first = [0u8; 1];
+        match self.reader.read_exact(&mut first) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let id_length = first[0].leading_zeros() as usize + 1;
+        if id_length > 4 {
+            return Err(invalid_data("invalid element id"));
+        }
+        let mut id = u64::from(first[0]);
+        for _ in 1..id_length {
+            id = (id << 8) | u64::from(self.read_byte()?);
+        }
+
+        let (size, unknown) = self.read_vint()?;
+        Ok(Some(Header {
+            id,
+            size: (!unknown).then_some(size),
+            data_start: self.position(),
+        }))
+    }
+
+    /// Reads a variable-length integer, returning its value without the length
+    /// marker and whether all value bits were set (the "unknown size" marker).
+    fn read_vint(&mut self) -> io::Result<(u64, bool)> {
+        let first = self.read_byte()?;
+        let length = first.leading_zeros() as usize + 1;
+        if length > 8 {
+            return Err(invalid_data("invalid variable-length integer"));
+        }
+        let mask = if length == 8 { 0 } else { 0xFFu8 >> length };
+        let mut value = u64::from(first & mask);
+        for _ in 1..length {
+            value = (value << 8) | u64::from(self.read_byte()?);
+        }
+        let all_ones = value == (1u64 << (7 * length)) - 1;
+        Ok((value, all_ones))
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        let mut byte = [0u8; 1];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn read_uint(&mut self, header: &Header) -> io::Result<u64> {
+        let size = header.size.unwrap_or(0);
+        if size > 8 {
+            return Err(invalid_data("integer element too large"));
+        }
+        let mut value = 0u64;
+        for _ in 0..size {
+            value = (value << 8) | u64::from(self.read_byte()?);
+        }
+        Ok(value)
+    }
+
+    fn read_string(&mut self, header: &Header) -> io::Result<String> {
+        let size = header.size.unwrap_or(0);
+        if size > MAX_STRING_SIZE {
+            return Err(invalid_data("string element too large"));
+        }
+        let mut data = vec![0u8; size as usize];
+        self.reader.read_exact(&mut data)?;
+        let text = String::from_utf8_lossy(&data);
+        Ok(text.trim_end_matches('\0').to_string())
+    }
+
+    fn known_end(&self, header: &Header) -> io::Result<u64> {
+        header
+            .end()
+            .ok_or_else(|| invalid_data("unexpected unknown-sized element"))
+    }
+
+    fn skip(&mut self, header: &Header) -> io::Result<()> {
+        let end = self.known_end(header)?;
+        self.skip_to(end)
+    }
+
+    /// Moves to `position` relative to where the reader is, which keeps what
+    /// a `BufReader` has buffered when `position` is within it.
+    fn skip_to(&mut self, position: u64) -> io::Result<()> {
+        let offset = position as i64 - self.position() as i64;
+        self.reader.seek_relative(offset)
+    }
+}
+
+fn is_top_level(id: u64) -> bool {
+    matches!(
+        id,
+        SEEK_HEAD | INFO | TRACKS | CLUSTER | CUES | ATTACHMENTS | CHAPTERS | TAGS
+    )
+}
+
+/// Whether an element ends an unknown-sized BlockGroup: one that belongs to
+/// the cluster or the segment rather than the group.
+fn ends_block_group(id: u64) -> bool {
+    matches!(id, TIMECODE | SIMPLE_BLOCK | BLOCK_GROUP) || is_top_level(id)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn element(id: u64, data: &[u8]) -> Vec<u8> {
+        let mut out: Vec<u8> = id
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&b| b == 0)
+            .collect();
+        out.push(0x01);
+        out.extend_from_slice(&(data.len() as u64).to_be_bytes()[1..]);
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn unknown_size_element(id: u64, data: &[u8]) -> Vec<u8> {
+        let mut out: Vec<u8> = id
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&b| b == 0)
+            .collect();
+        out.extend_from_slice(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn track_entry(number: u8, track_type: u8, codec: &str, language: &str) -> Vec<u8> {
+        let mut data = element(TRACK_NUMBER, &[number]);
+        data.extend(element(TRACK_TYPE, &[track_type]));
+        data.extend(element(CODEC_ID, codec.as_bytes()));
+        data.extend(element(LANGUAGE, language.as_bytes()));
+        element(TRACK_ENTRY, &data)
+    }
+
+    fn simple_block(track: u8, timecode: i16, text: &str) -> Vec<u8> {
+        let mut data = vec![0x80 | track];
+        data.extend_from_slice(&timecode.to_be_bytes());
+        data.push(0x80);
+        data.extend_from_slice(text.as_bytes());
+        element(SIMPLE_BLOCK, &data)
+    }
+
+    fn block_group(
+        track: u8,
+        timecode: i16,
+        duration: u8,
+        text: &str,
+        unknown_size: bool,
+    ) -> Vec<u8> {
+        let mut block = vec![0x80 | track];
+        block.extend_from_slice(&timecode.to_be_bytes());
+        block.push(0x00);
+        block.extend_from_slice(text.as_bytes());
+        let mut data = element(BLOCK, &block);
+        data.extend(element(BLOCK_DURATION, &[duration]));
+        if unknown_size {
+            unknown_size_element(BLOCK_GROUP, &data)
+        } else {
+            element(BLOCK_GROUP, &data)
+        }
+    }
+
+    fn sample_file(unknown_sizes: bool) -> Vec<u8> {
+        let mut tracks = track_entry(1, 0x01, "V_MPEG4/ISO/AVC", "und");
+        tracks.extend(track_entry(
+            2,
+            TRACK_TYPE_SUBTITLE as u8,
+            "S_TEXT/UTF8",
+            "eng",
+        ));
+        tracks.extend(track_entry(
+            3,
+            TRACK_TYPE_SUBTITLE as u8,
+            "S_HDMV/PGS",
+            "jpn",
+        ));
+
+        let mut cluster = element(TIMECODE, &[0x03, 0xE8]);
+        cluster.extend(simple_block(1, 0, "video frame"));
+        cluster.extend(block_group(2, 500, 200, "Hello", unknown_sizes));
+        cluster.extend(simple_block(2, 1500, "World"));
+
+        let mut segment = element(INFO, &element(TIMECODE_SCALE, &[0x0F, 0x42, 0x40]));
+        segment.extend(element(TRACKS, &tracks));
+        if unknown_sizes {
+            segment.extend(unknown_size_element(CLUSTER, &cluster));
+            segment.extend(element(CUES, &[]));
+        } else {
+            segment.extend(element(CLUSTER, &cluster));
+        }
+
+        let mut file = element(EBML_HEADER, &element(0x4282, b"matroska"));
+        if unknown_sizes {
+            file.extend(unknown_size_element(SEGMENT, &segment));
+        } else {
+            file.extend(element(SEGMENT, &segment));
+        }
+        file
+    }
+
+    #[test]
+    fn test_is_matroska() {
+        assert!(is_matroska(&mut Cursor::new(sample_file(false))).unwrap());
+        assert!(!is_matroska(&mut Cursor::new(b"ftypisom".to_vec())).unwrap());
+        assert!(!is_matroska(&mut Cursor::new(Vec::new())).unwrap());
+    }
+
+    #[test]
+    fn test_read_subtitle_tracks() {
+        let tracks = read_subtitle_tracks(&mut Cursor::new(sample_file(false))).unwrap();
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].number, 2);
+        assert_eq!(tracks[0].codec_id, "S_TEXT/UTF8");
+        assert_eq!(tracks[0].language.as_deref(), Some("eng"));
+        assert!(tracks[0].is_text());
+        assert!(!tracks[1].is_text());
+    }
+
+    #[test]
+    fn test_read_text_blocks() {
+        let blocks = read_text_blocks(&mut Cursor::new(sample_file(false)), &[2]).unwrap();
+
+        assert_eq!(
+            blocks,
+            vec![
+                TextBlock {
+                    track: 2,
+                    start_ms: 1500,
+                    duration_ms: Some(200),
+                    data: b"Hello".to_vec(),
+                },
+                TextBlock {
+                    track: 2,
+                    start_ms: 2500,
+                    duration_ms: None,
+                    data: b"World".to_vec(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_text_blocks_with_unknown_sizes() {
+        let blocks = read_text_blocks(&mut Cursor::new(sample_file(true)), &[2]).unwrap();
+        let sized = read_text_blocks(&mut Cursor::new(sample_file(false)), &[2]).unwrap();
+        assert_eq!(blocks, sized);
+    }
+
+    #[test]
+    fn test_rejects_non_matroska() {
+        assert!(read_subtitle_tracks(&mut Cursor::new(b"not a video".to_vec())).is_err());
+    }
+}
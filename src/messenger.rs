@@ -0,0 +1,577 @@
+use crate::queue_engine::RepeatMode;
+use crate::subtitle_style::TextTrackStyle;
+use rust_cast::channels::connection::ConnectionChannel;
+use rust_cast::channels::media::{Image, Metadata, StreamType};
+use rust_cast::message_manager::{CastMessage, CastMessagePayload, MessageManager};
+use rust_cast::NoCertificateVerification;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+use serde_json::{json, Value};
+use std::io;
+use std::net::TcpStream;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SENDER_ID: &str = "sender-castnow";
+const MEDIA_NAMESPACE: &str = "urn:x-cast:com.google.cast.media";
+const HEARTBEAT_NAMESPACE: &str = "urn:x-cast:com.google.cast.tp.heartbeat";
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long [`Messenger::answer_heartbeats`] waits for a message that may not
+/// have been sent.
+const HEARTBEAT_READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+type Stream = StreamOwned<ClientConnection, TcpStream>;
+
+/// A text track attached to a LOAD request.
+#[derive(Clone, Debug)]
+pub struct TextTrack {
+    pub id: u32,
+    pub url: String,
+    pub name: Option<String>,
+    pub language: Option<String>,
+}
+
+/// Everything needed to build a media LOAD request.
+#[derive(Clone, Debug)]
+pub struct LoadRequest {
+    pub content_id: String,
+    pub content_type: String,
+    pub stream_type: StreamType,
+    pub tracks: Vec<TextTrack>,
+    pub active_track_ids: Vec<u32>,
+    pub text_track_style: Option<TextTrackStyle>,
+    pub metadata: Option<Metadata>,
+    pub current_time: f64,
+    pub autoplay: bool,
+}
+
+impl LoadRequest {
+    pub fn new(content_id: impl Into<String>, content_type: impl Into<String>) -> Self {
+        LoadRequest {
+            content_id: content_id.into(),
+            content_type: content_type.into(),
+            stream_type: StreamType::Buffered,
+            tracks: Vec::new(),
+            active_track_ids: Vec::new(),
+            text_track_style: None,
+            metadata: None,
+            current_time: 0.0,
+            autoplay: true,
+        }
+    }
+
+    fn media_json(&self) -> Value {
+        let mut media = json!({
+            "contentId": self.content_id,
+            "contentType": self.content_type,
+            "streamType": self.stream_type.to_string(),
+        });
+        if !self.tracks.is_empty() {
+            media["tracks"] = Value::Array(self.tracks.iter().map(track_json).collect());
+        }
+        if let Some(style) = self
+            .text_track_style
+            .as_ref()
+            .filter(|style| !style.is_empty())
+        {
+            media["textTrackStyle"] = style.to_json();
+        }
+        if let Some(metadata) = &self.metadata {
+            media["metadata"] = metadata_json(metadata);
+        }
+        media
+    }
+
+    pub fn to_json(&self, session_id: &str) -> Value {
+        let mut payload = json!({
+            "type": "LOAD",
+            "sessionId": session_id,
+            "media": self.media_json(),
+            "currentTime": self.current_time,
+            "autoplay": self.autoplay,
+        });
+        if !self.active_track_ids.is_empty() {
+            payload["activeTrackIds"] = json!(self.active_track_ids);
+        }
+        payload
+    }
+
+    /// The request as an item of a QUEUE_LOAD or QUEUE_INSERT. The receiver
+    /// starts buffering it `preload_time` seconds before the item ahead of
+    /// it ends.
+    pub fn to_queue_item(&self, preload_time: f64) -> Value {
+        let mut item = json!({
+            "media": self.media_json(),
+            "autoplay": self.autoplay,
+            "startTime": self.current_time,
+            "preloadTime": preload_time,
+        });
+        if !self.active_track_ids.is_empty() {
+            item["activeTrackIds"] = json!(self.active_track_ids);
+        }
+        item
+    }
+}
+
+/// The receiver's name for a repeat mode.
+fn repeat_mode_json(mode: RepeatMode) -> &'static str {
+    match mode {
+        RepeatMode::Off => "REPEAT_OFF",
+        RepeatMode::One => "REPEAT_SINGLE",
+        RepeatMode::All => "REPEAT_ALL",
+    }
+}
+
+fn queue_load_json(
+    session_id: &str,
+    items: &[Value],
+    start_index: usize,
+    repeat_mode: RepeatMode,
+) -> Value {
+    json!({
+        "type": "QUEUE_LOAD",
+        "sessionId": session_id,
+        "items": items,
+        "startIndex": start_index,
+        "repeatMode": repeat_mode_json(repeat_mode),
+    })
+}
+
+fn track_json(track: &TextTrack) -> Value {
+    let mut value = json!({
+        "trackId": track.id,
+        "type": "TEXT",
+        "subtype": "SUBTITLES",
+        "trackContentId": track.url,
+        "trackContentType": "text/vtt",
+    });
+    if let Some(name) = &track.name {
+        value["name"] = json!(name);
+    }
+    if let Some(language) = &track.language {
+        value["language"] = json!(language);
+    }
+    value
+}
+
+/// Encodes cast metadata the way the receiver expects, leaving out unset fields.
+fn metadata_json(metadata: &Metadata) -> Value {
+    let (metadata_type, fields, images): (u32, Value, &[Image]) = match metadata {
+        Metadata::Generic(x) => (
+            0,
+            json!({
+                "title": x.title,
+                "subtitle": x.subtitle,
+                "releaseDate": x.release_date,
+            }),
+            &x.images,
+        ),
+        Metadata::Movie(x) => (
+            1,
+            json!({
+                "title": x.title,
+                "subtitle": x.subtitle,
+                "studio": x.studio,
+                "releaseDate": x.release_date,
+            }),
+            &x.images,
+        ),
+        Metadata::TvShow(x) => (
+            2,
+            json!({
+                "seriesTitle": x.series_title,
+                "title": x.episode_title,
+                "season": x.season,
+                "episode": x.episode,
+                "originalAirDate": x.original_air_date,
+            }),
+            &x.images,
+        ),
+        Metadata::MusicTrack(x) => (
+            3,
+            json!({
+                "albumName": x.album_name,
+                "title": x.title,
+                "albumArtist": x.album_artist,
+                "artist": x.artist,
+                "composer": x.composer,
+                "trackNumber": x.track_number,
+                "discNumber": x.disc_number,
+                "releaseDate": x.release_date,
+            }),
+            &x.images,
+        ),
+        Metadata::Photo(x) => (
+            4,
+            json!({
+                "title": x.title,
+                "artist": x.artist,
+                "location": x.location,
+                "latitude": x.latitude_longitude.map(|l| l.0),
+                "longitude": x.latitude_longitude.map(|l| l.1),
+                "width": x.dimensions.map(|d| d.0),
+                "height": x.dimensions.map(|d| d.1),
+                "creationDateTime": x.creation_date_time,
+            }),
+            &[],
+        ),
+    };
+
+    let mut value: serde_json::Map<String, Value> = fields
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(_, field)| !field.is_null())
+        .map(|(key, field)| (key.clone(), field.clone()))
+        .collect();
+    value.insert("metadataType".to_string(), json!(metadata_type));
+    if !images.is_empty() {
+        value.insert(
+            "images".to_string(),
+            Value::Array(images.iter().map(image_json).collect()),
+        );
+    }
+    Value::Object(value)
+}
+
+fn image_json(image: &Image) -> Value {
+    let mut value = json!({ "url": image.url });
+    if let Some((width, height)) = image.dimensions {
+        value["width"] = json!(width);
+        value["height"] = json!(height);
+    }
+    value
+}
+
+/// Sends raw media-namespace messages that `rust_cast` has no API for
+/// (text tracks, styles, queue editing) over a second connection to the device.
+pub struct Messenger {
+    message_manager: Rc<MessageManager<Stream>>,
+    /// The connection's socket, to change how long reads wait.
+    socket: TcpStream,
+    transport_id: String,
+}
+
+impl Messenger {
+    pub fn connect(host: &str, port: u16, transport_id: &str) -> anyhow::Result<Self> {
+        let config = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoCertificateVerification {}))
+            .with_no_client_auth();
+        let tcp_stream = TcpStream::connect((host, port))?;
+        tcp_stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+        let socket = tcp_stream.try_clone()?;
+        let stream = StreamOwned::new(
+            ClientConnection::new(Arc::new(config), ServerName::try_from(host)?.to_owned())?,
+            tcp_stream,
+        );
+
+        let message_manager = Rc::new(MessageManager::new(stream));
+        ConnectionChannel::new(SENDER_ID, Rc::clone(&message_manager)).connect(transport_id)?;
+
+        Ok(Messenger {
+            message_manager,
+            socket,
+            transport_id: transport_id.to_string(),
+        })
+    }
+
+    pub fn load(&self, session_id: &str, request: &LoadRequest) -> anyhow::Result<Value> {
+        self.request(request.to_json(session_id))
+    }
+
+    /// Replaces the active tracks; an empty list turns all text tracks off.
+    pub fn set_active_tracks(
+        &self,
+        media_session_id: i32,
+        track_ids: &[u32],
+    ) -> anyhow::Result<Value> {
+        self.request(json!({
+            "type": "EDIT_TRACKS_INFO",
+            "mediaSessionId": media_session_id,
+            "activeTrackIds": track_ids,
+        }))
+    }
+
+    pub fn set_text_track_style(
+        &self,
+        media_session_id: i32,
+        style: &TextTrackStyle,
+    ) -> anyhow::Result<Value> {
+        self.request(json!({
+            "type": "EDIT_TRACKS_INFO",
+            "mediaSessionId": media_session_id,
+            "textTrackStyle": style.to_json(),
+        }))
+    }
+
+    /// Replaces whatever is playing with a queue of `items` (see
+    /// [`LoadRequest::to_queue_item`]), starting at `start_index`.
+    pub fn queue_load(
+        &self,
+        session_id: &str,
+        items: &[Value],
+        start_index: usize,
+        repeat_mode: RepeatMode,
+    ) -> anyhow::Result<Value> {
+        self.request(queue_load_json(session_id, items, start_index, repeat_mode))
+    }
+
+    /// Appends `items` to the end of the queue.
+    pub fn queue_insert(&self, media_session_id: i32, items: &[Value]) -> anyhow::Result<Value> {
+        self.request(json!({
+            "type": "QUEUE_INSERT",
+            "mediaSessionId": media_session_id,
+            "items": items,
+        }))
+    }
+
+    /// Moves `jump` items forward (or back, when negative) in the queue.
+    pub fn queue_jump(&self, media_session_id: i32, jump: i32) -> anyhow::Result<Value> {
+        self.request(json!({
+            "type": "QUEUE_UPDATE",
+            "mediaSessionId": media_session_id,
+            "jump": jump,
+        }))
+    }
+
+    /// Switches to the queue item `item_id`.
+    pub fn queue_jump_to_item(&self, media_session_id: i32, item_id: i32) -> anyhow::Result<Value> {
+        self.request(json!({
+            "type": "QUEUE_UPDATE",
+            "mediaSessionId": media_session_id,
+            "currentItemId": item_id,
+        }))
+    }
+
+    pub fn queue_set_repeat(
+        &self,
+        media_session_id: i32,
+        repeat_mode: RepeatMode,
+    ) -> anyhow::Result<Value> {
+        self.request(json!({
+            "type": "QUEUE_UPDATE",
+            "mediaSessionId": media_session_id,
+            "repeatMode": repeat_mode_json(repeat_mode),
+        }))
+    }
+
+    pub fn queue_remove(&self, media_session_id: i32, item_ids: &[i32]) -> anyhow::Result<Value> {
+        self.request(json!({
+            "type": "QUEUE_REMOVE",
+            "mediaSessionId": media_session_id,
+            "itemIds": item_ids,
+        }))
+    }
+
+    /// The ids the receiver gave the queue's items, in queue order.
+    pub fn queue_item_ids(&self, media_session_id: i32) -> anyhow::Result<Vec<i32>> {
+        let response = self.request(json!({
+            "type": "QUEUE_GET_ITEM_IDS",
+            "mediaSessionId": media_session_id,
+        }))?;
+        Ok(response["itemIds"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|id| id.as_i64())
+            .map(|id| id as i32)
+            .collect())
+    }
+
+    /// Sends `payload` with a fresh request id and waits for the matching
+    /// media status (or queue item ids), answering heartbeats while waiting.
+    pub fn request(&self, mut payload: Value) -> anyhow::Result<Value> {
+        let request_id = self.message_manager.generate_request_id().get();
+        payload["requestId"] = json!(request_id);
+        self.send(MEDIA_NAMESPACE, &payload)?;
+
+        loop {
+            let message = self.message_manager.receive()?;
+            let CastMessagePayload::String(raw) = &message.payload else {
+                continue;
+            };
+            let response: Value = serde_json::from_str(raw)?;
+            let message_type = response["type"].as_str().unwrap_or_default();
+
+            if self.answer_ping(&message.namespace, message_type)? {
+                continue;
+            }
+            if message.namespace != MEDIA_NAMESPACE
+                || response["requestId"].as_u64() != Some(u64::from(request_id))
+            {
+                continue;
+            }
+
+            return match message_type {
+                "MEDIA_STATUS" | "QUEUE_ITEM_IDS" => Ok(response),
+                _ => Err(anyhow::anyhow!(
+                    "{} failed: {} ({})",
+                    payload["type"].as_str().unwrap_or_default(),
+                    message_type,
+                    response["reason"].as_str().unwrap_or("UNKNOWN")
+                )),
+            };
+        }
+    }
+
+    /// Answers the heartbeat PINGs the receiver sent since the last request,
+    /// which it closes the connection over if they go unanswered while
+    /// nothing else is sent on it. Called as the player polls the receiver.
+    /// Other messages waiting are statuses nobody asked for and are dropped.
+    pub fn answer_heartbeats(&self) -> anyhow::Result<()> {
+        self.socket.set_read_timeout(Some(HEARTBEAT_READ_TIMEOUT))?;
+        let answered = self.answer_waiting_pings();
+        self.socket.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+        answered
+    }
+
+    fn answer_waiting_pings(&self) -> anyhow::Result<()> {
+        loop {
+            // The receiver writes each message in one TLS record, so a read
+            // that times out has taken nothing of the next one.
+            let message = match self.message_manager.receive() {
+                Ok(message) => message,
+                Err(rust_cast::errors::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(());
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let CastMessagePayload::String(raw) = &message.payload else {
+                continue;
+            };
+            let payload: Value = serde_json::from_str(raw)?;
+            self.answer_ping(
+                &message.namespace,
+                payload["type"].as_str().unwrap_or_default(),
+            )?;
+        }
+    }
+
+    /// Sends a PONG if the message is a heartbeat PING, and says whether it
+    /// was.
+    fn answer_ping(&self, namespace: &str, message_type: &str) -> anyhow::Result<bool> {
+        let ping = namespace == HEARTBEAT_NAMESPACE && message_type == "PING";
+        if ping {
+            self.send(HEARTBEAT_NAMESPACE, &json!({ "type": "PONG" }))?;
+        }
+        Ok(ping)
+    }
+
+    fn send(&self, namespace: &str, payload: &Value) -> anyhow::Result<()> {
+        let destination = if namespace == HEARTBEAT_NAMESPACE {
+            "receiver-0"
+        } else {
+            self.transport_id.as_str()
+        };
+        self.message_manager.send(CastMessage {
+            namespace: namespace.to_string(),
+            source: SENDER_ID.to_string(),
+            destination: destination.to_string(),
+            payload: CastMessagePayload::String(payload.to_string()),
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_cast::channels::media::MusicTrackMediaMetadata;
+
+    #[test]
+    fn test_load_request_without_tracks() {
+        let request = LoadRequest::new("http://host/", "video/mp4");
+        let json = request.to_json("session");
+
+        assert_eq!(json["type"], "LOAD");
+        assert_eq!(json["sessionId"], "session");
+        assert_eq!(json["media"]["contentId"], "http://host/");
+        assert_eq!(json["media"]["streamType"], "BUFFERED");
+        assert!(json["media"].get("tracks").is_none());
+        assert!(json.get("activeTrackIds").is_none());
+        assert!(json["media"].get("textTrackStyle").is_none());
+        assert!(json["media"].get("metadata").is_none());
+    }
+
+    #[test]
+    fn test_load_request_with_metadata() {
+        let mut request = LoadRequest::new("http://host/", "audio/mpeg");
+        request.metadata = Some(Metadata::MusicTrack(MusicTrackMediaMetadata {
+            title: Some("Song".to_string()),
+            artist: Some("Artist".to_string()),
+            track_number: Some(2),
+            images: vec![Image::new("http://host/cover.jpg".to_string())],
+            ..Default::default()
+        }));
+        let json = request.to_json("session");
+
+        let metadata = &json["media"]["metadata"];
+        assert_eq!(metadata["metadataType"], 3);
+        assert_eq!(metadata["title"], "Song");
+        assert_eq!(metadata["artist"], "Artist");
+        assert_eq!(metadata["trackNumber"], 2);
+        assert!(metadata.get("albumName").is_none());
+        assert_eq!(
+            metadata["images"],
+            json!([{ "url": "http://host/cover.jpg" }])
+        );
+    }
+
+    #[test]
+    fn test_load_request_with_text_tracks() {
+        let mut request = LoadRequest::new("http://host/", "video/x-matroska");
+        request.tracks.push(TextTrack {
+            id: 1,
+            url: "http://host/subtitles/0/1.vtt".to_string(),
+            name: Some("English".to_string()),
+            language: Some("en".to_string()),
+        });
+        request.active_track_ids = vec![1];
+        request.text_track_style = Some(TextTrackStyle {
+            font_scale: Some(1.2),
+            ..Default::default()
+        });
+        let json = request.to_json("session");
+
+        let track = &json["media"]["tracks"][0];
+        assert_eq!(track["trackId"], 1);
+        assert_eq!(track["type"], "TEXT");
+        assert_eq!(track["trackContentType"], "text/vtt");
+        assert_eq!(track["language"], "en");
+        assert_eq!(json["activeTrackIds"], json!([1]));
+        assert_eq!(json["media"]["textTrackStyle"]["fontScale"], json!(1.2f32));
+    }
+
+    #[test]
+    fn test_queue_load() {
+        let mut request = LoadRequest::new("http://host/media/0.flac", "audio/flac");
+        request.current_time = 30.0;
+        request.active_track_ids = vec![1];
+        let item = request.to_queue_item(20.0);
+        assert_eq!(item["media"]["contentId"], "http://host/media/0.flac");
+        assert_eq!(item["startTime"], json!(30.0));
+        assert_eq!(item["preloadTime"], json!(20.0));
+        assert_eq!(item["autoplay"], true);
+        assert_eq!(item["activeTrackIds"], json!([1]));
+        assert!(LoadRequest::new("http://host/", "video/mp4")
+            .to_queue_item(20.0)
+            .get("activeTrackIds")
+            .is_none());
+
+        let json = queue_load_json("session", &[item.clone(), item], 1, RepeatMode::One);
+        assert_eq!(json["type"], "QUEUE_LOAD");
+        assert_eq!(json["sessionId"], "session");
+        assert_eq!(json["items"].as_array().unwrap().len(), 2);
+        assert_eq!(json["startIndex"], 1);
+        assert_eq!(json["repeatMode"], "REPEAT_SINGLE");
+        assert_eq!(repeat_mode_json(RepeatMode::All), "REPEAT_ALL");
+        assert_eq!(repeat_mode_json(RepeatMode::Off), "REPEAT_OFF");
+    }
+}
@@ -0,0 +1,219 @@
+use crate::tags;
+use rust_cast::channels::media::{
+    GenericMediaMetadata, Image, Metadata, MovieMediaMetadata, MusicTrackMediaMetadata,
+};
+use std::path::Path;
+
+/// Guesses the MIME type of a local media file from its extension.
+pub fn content_type_for_path(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let content_type = match extension.as_str() {
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "m4a" | "aac" => "audio/mp4",
+        "wav" => "audio/wav",
+        "mp4" | "m4v" => "video/mp4",
+        "mkv" => "video/x-matroska",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+/// Details known from outside the file, e.g. from a playlist entry. Tags in
+/// the file take precedence; hints take precedence over the file name.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hints<'a> {
+    pub title: Option<&'a str>,
+    pub artist: Option<&'a str>,
+}
+
+/// Builds the cast metadata for a local file from its tags and file name.
+/// Audio files get `MusicTrack` metadata; videos get `Movie` metadata when the
+/// file name carries a year (e.g. `Film (1999).mkv`) and `Generic` otherwise.
+pub fn build_metadata(
+    path: &Path,
+    content_type: &str,
+    hints: Hints<'_>,
+    images: Vec<String>,
+) -> Metadata {
+    let images: Vec<Image> = images.into_iter().map(Image::new).collect();
+    let (file_name, year) = parse_file_name(path);
+    let name = hints.title.map(str::to_string).unwrap_or(file_name);
+    let artist = hints.artist.map(str::to_string);
+
+    if content_type.starts_with("audio/") {
+        let tags = tags::read_tags(path).unwrap_or_default();
+        return Metadata::MusicTrack(MusicTrackMediaMetadata {
+            title: tags.title.or(Some(name)),
+            artist: tags.artist.or(artist),
+            album_name: tags.album,
+            album_artist: tags.album_artist,
+            composer: None,
+            track_number: tags.track,
+            disc_number: tags.disc,
+            images,
+            release_date: tags.date.or(year),
+        });
+    }
+
+    match year {
+        Some(year) => Metadata::Movie(MovieMediaMetadata {
+            title: Some(name),
+            subtitle: artist,
+            studio: None,
+            images,
+            release_date: Some(year),
+        }),
+        None => Metadata::Generic(GenericMediaMetadata {
+            title: Some(name),
+            subtitle: artist,
+            images,
+            release_date: None,
+        }),
+    }
+}
+
+/// Metadata carrying only images, used when `--no-metadata` is set.
+pub fn images_only(images: Vec<String>) -> Option<Metadata> {
+    if images.is_empty() {
+        return None;
+    }
+    Some(Metadata::Generic(GenericMediaMetadata {
+        title: None,
+        subtitle: None,
+        images: images.into_iter().map(Image::new).collect(),
+        release_date: None,
+    }))
+}
+
+/// Splits a file name into a readable title and an optional year, turning
+/// `The_Film.(1999).mkv` into `("The Film", Some("1999"))`.
+fn parse_file_name(path: &Path) -> (String, Option<String>) {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let cleaned = stem.replace(['_', '.'], " ");
+
+    let year = cleaned
+        .split(|c: char| !c.is_ascii_digit())
+        .find(|part| part.len() == 4 && (part.starts_with("19") || part.starts_with("20")))
+        .map(str::to_string);
+    let title = match &year {
+        Some(year) => cleaned[..cleaned.find(year.as_str()).unwrap_or(cleaned.len())]
+            .trim_end_matches(|c: char| c.is_whitespace() || c == '(' || c == '[')
+            .to_string(),
+        None => cleaned.trim().to_string(),
+    };
+    let title = if title.is_empty() { stem } else { title };
+    (title, year)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use id3::{Tag, TagLike, Version};
+
+    #[test]
+    fn test_content_type_for_path() {
+        assert_eq!(
+            content_type_for_path(Path::new("a.MP3")),
+            Some("audio/mpeg")
+        );
+        assert_eq!(
+            content_type_for_path(Path::new("a.mkv")),
+            Some("video/x-matroska")
+        );
+        assert_eq!(content_type_for_path(Path::new("a.txt")), None);
+        assert_eq!(content_type_for_path(Path::new("noext")), None);
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(
+            parse_file_name(Path::new("/films/The_Film.(1999).mkv")),
+            ("The Film".to_string(), Some("1999".to_string()))
+        );
+        assert_eq!(
+            parse_file_name(Path::new("holiday clip.mp4")),
+            ("holiday clip".to_string(), None)
+        );
+        assert_eq!(
+            parse_file_name(Path::new("2001.mp4")),
+            ("2001".to_string(), Some("2001".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_build_metadata_for_video() {
+        let images = vec!["http://host/cover.jpg".to_string()];
+        match build_metadata(
+            Path::new("Film (2010).mkv"),
+            "video/x-matroska",
+            Hints::default(),
+            images,
+        ) {
+            Metadata::Movie(movie) => {
+                assert_eq!(movie.title.as_deref(), Some("Film"));
+                assert_eq!(movie.release_date.as_deref(), Some("2010"));
+                assert_eq!(movie.images[0].url, "http://host/cover.jpg");
+            }
+            other => panic!("unexpected metadata: {other:?}"),
+        }
+        assert!(matches!(
+            build_metadata(
+                Path::new("clip.mp4"),
+                "video/mp4",
+                Hints::default(),
+                Vec::new()
+            ),
+            Metadata::Generic(_)
+        ));
+        let hints = Hints {
+            title: Some("Clip"),
+            artist: Some("Someone"),
+        };
+        match build_metadata(Path::new("clip.mp4"), "video/mp4", hints, Vec::new()) {
+            Metadata::Generic(generic) => {
+                assert_eq!(generic.title.as_deref(), Some("Clip"));
+                assert_eq!(generic.subtitle.as_deref(), Some("Someone"));
+            }
+            other => panic!("unexpected metadata: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_build_metadata_for_audio() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("01_track.mp3");
+        std::fs::write(&path, b"").unwrap();
+        let mut tag = Tag::new();
+        tag.set_title("Song");
+        tag.set_artist("Artist");
+        tag.set_album("Album");
+        tag.set_track(3);
+        tag.set_disc(1);
+        tag.set_year(2004);
+        tag.write_to_path(&path, Version::Id3v24).unwrap();
+
+        let hints = Hints {
+            title: Some("Playlist Title"),
+            artist: Some("Playlist Artist"),
+        };
+        match build_metadata(&path, "audio/mpeg", hints, Vec::new()) {
+            Metadata::MusicTrack(track) => {
+                assert_eq!(track.title.as_deref(), Some("Song"));
+                assert_eq!(track.artist.as_deref(), Some("Artist"));
+                assert_eq!(track.album_name.as_deref(), Some("Album"));
+                assert_eq!(track.track_number, Some(3));
+                assert_eq!(track.disc_number, Some(1));
+                assert_eq!(track.release_date.as_deref(), Some("2004"));
+            }
+            other => panic!("unexpected metadata: {other:?}"),
+        }
+    }
+}
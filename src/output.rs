@@ -0,0 +1,543 @@
+use crate::subtitles::EmbeddedTrack;
+use crate::tags::AudioTags;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+
+/// How subcommands print their results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Yaml,
+}
+
+/// A subcommand result with a stable serialised schema and a human-readable
+/// text form.
+pub trait Report: Serialize {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()>;
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PictureInfo {
+    pub mime_type: String,
+    pub size: usize,
+    pub front_cover: bool,
+}
+
+/// The cover as it would be served to the receiver.
+#[derive(Clone, Debug, Serialize)]
+pub struct CoverInfo {
+    pub content_type: String,
+    pub size: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioReport {
+    pub path: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub date: Option<String>,
+    pub genre: Option<String>,
+    pub track: Option<u32>,
+    pub total_tracks: Option<u32>,
+    pub disc: Option<u32>,
+    pub total_discs: Option<u32>,
+    pub comment: Option<String>,
+    pub pictures: Vec<PictureInfo>,
+    pub cover: Option<CoverInfo>,
+}
+
+impl AudioReport {
+    pub fn new(path: &str, tags: AudioTags, cover: Option<CoverInfo>) -> Self {
+        AudioReport {
+            path: path.to_string(),
+            pictures: tags
+                .pictures
+                .iter()
+                .map(|picture| PictureInfo {
+                    mime_type: picture.mime_type.clone(),
+                    size: picture.data.len(),
+                    front_cover: picture.front_cover,
+                })
+                .collect(),
+            title: tags.title,
+            artist: tags.artist,
+            album: tags.album,
+            album_artist: tags.album_artist,
+            date: tags.date,
+            genre: tags.genre,
+            track: tags.track,
+            total_tracks: tags.total_tracks,
+            disc: tags.disc,
+            total_discs: tags.total_discs,
+            comment: tags.comment,
+            cover,
+        }
+    }
+}
+
+impl Report for AudioReport {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "--- Audio Metadata ---")?;
+        let fields = [
+            ("Title", self.title.clone()),
+            ("Artist", self.artist.clone()),
+            ("Album", self.album.clone()),
+            ("Album Artist", self.album_artist.clone()),
+            ("Date", self.date.clone()),
+            ("Genre", self.genre.clone()),
+            ("Track", self.track.map(|n| n.to_string())),
+            ("Total Tracks", self.total_tracks.map(|n| n.to_string())),
+            ("Disc", self.disc.map(|n| n.to_string())),
+            ("Total Discs", self.total_discs.map(|n| n.to_string())),
+            ("Comment", self.comment.clone()),
+        ];
+        for (label, value) in fields {
+            if let Some(value) = value {
+                writeln!(out, "{label}: {value}")?;
+            }
+        }
+        for picture in &self.pictures {
+            writeln!(
+                out,
+                "Cover Art: MIME Type = {}, Size = {} bytes",
+                picture.mime_type, picture.size
+            )?;
+        }
+        if let Some(cover) = &self.cover {
+            writeln!(
+                out,
+                "Served Cover: {}, Size = {} bytes",
+                cover.content_type, cover.size
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlaylistEntryReport {
+    pub path: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration_secs: Option<f64>,
+    pub image: Option<String>,
+    /// Offset into `path` where the entry starts (CUE sheet tracks and
+    /// playlist segments).
+    pub start_secs: Option<f64>,
+}
+
+/// Album-level details from a CUE sheet.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AlbumReport {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub date: Option<String>,
+    pub genre: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlaylistReport {
+    pub path: String,
+    pub format: String,
+    pub album: Option<AlbumReport>,
+    pub entries: Vec<PlaylistEntryReport>,
+}
+
+impl Report for PlaylistReport {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "--- {} Playlist ---", self.format.to_uppercase())?;
+        if let Some(album) = &self.album {
+            let fields = [
+                ("Album", &album.title),
+                ("Performer", &album.performer),
+                ("Date", &album.date),
+                ("Genre", &album.genre),
+            ];
+            for (label, value) in fields {
+                if let Some(value) = value {
+                    writeln!(out, "{label}: {value}")?;
+                }
+            }
+        }
+        for entry in &self.entries {
+            let mut line = format!("Path: {}", entry.path);
+            if let Some(title) = &entry.title {
+                line.push_str(&format!(", Title = {title}"));
+            }
+            if let Some(artist) = &entry.artist {
+                line.push_str(&format!(", Artist = {artist}"));
+            }
+            if let Some(duration) = entry.duration_secs {
+                line.push_str(&format!(", Duration = {duration}s"));
+            }
+            if let Some(start) = entry.start_secs {
+                line.push_str(&format!(", Start = {start}s"));
+            }
+            writeln!(out, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LinksReport {
+    pub path: String,
+    pub links: Vec<String>,
+}
+
+impl Report for LinksReport {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "--- Media Links in {} ---", self.path)?;
+        for link in &self.links {
+            writeln!(out, "Link: {link}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EpisodeReport {
+    /// 1-based position in the feed, as accepted by `--episode`.
+    pub number: usize,
+    pub title: Option<String>,
+    pub published: Option<String>,
+    pub duration_secs: Option<f64>,
+    pub url: String,
+    pub image: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PodcastReport {
+    pub source: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub image: Option<String>,
+    pub episodes: Vec<EpisodeReport>,
+}
+
+impl Report for PodcastReport {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        let title = self.title.as_deref().unwrap_or(&self.source);
+        match &self.author {
+            Some(author) => writeln!(out, "--- {title} by {author} ---")?,
+            None => writeln!(out, "--- {title} ---")?,
+        }
+        for episode in &self.episodes {
+            let mut line = format!("{:>3}.", episode.number);
+            if let Some(published) = &episode.published {
+                line.push_str(&format!(" {published}"));
+            }
+            line.push_str(&format!(
+                " {}",
+                episode.title.as_deref().unwrap_or(&episode.url)
+            ));
+            if let Some(duration) = episode.duration_secs {
+                let seconds = duration.round() as u64;
+                line.push_str(&format!(
+                    " ({}:{:02}:{:02})",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60
+                ));
+            }
+            writeln!(out, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SubtitleTrackReport {
+    /// The number `--extract` selects the track by.
+    pub number: u64,
+    pub codec: String,
+    pub language: Option<String>,
+    pub name: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SubtitlesReport {
+    pub path: String,
+    pub tracks: Vec<SubtitleTrackReport>,
+    /// WebVTT files written for `--extract`.
+    pub extracted: Vec<String>,
+}
+
+impl SubtitlesReport {
+    pub fn new(path: &str, tracks: &[EmbeddedTrack]) -> Self {
+        SubtitlesReport {
+            path: path.to_string(),
+            tracks: tracks
+                .iter()
+                .map(|track| SubtitleTrackReport {
+                    number: track.number,
+                    codec: track.codec.clone(),
+                    language: track.language.clone(),
+                    name: track.name.clone(),
+                    default: track.default,
+                    forced: track.forced,
+                })
+                .collect(),
+            extracted: Vec::new(),
+        }
+    }
+}
+
+impl Report for SubtitlesReport {
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "--- Embedded Text Tracks ---")?;
+        for track in &self.tracks {
+            let mut line = format!("Track {}: {}", track.number, track.codec);
+            if let Some(language) = &track.language {
+                line.push_str(&format!(", Language = {language}"));
+            }
+            if let Some(name) = &track.name {
+                line.push_str(&format!(", Name = {name}"));
+            }
+            if track.default {
+                line.push_str(" (default)");
+            }
+            if track.forced {
+                line.push_str(" (forced)");
+            }
+            writeln!(out, "{line}")?;
+        }
+        for path in &self.extracted {
+            writeln!(out, "Extracted: {path}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    NotFound,
+    Io,
+    UnsupportedFormat,
+    Invalid,
+}
+
+/// A subcommand failure, printed as `{"error": {"kind": ..., "message": ...}}`
+/// in the structured formats.
+#[derive(Clone, Debug, Serialize)]
+pub struct CommandError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl CommandError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        CommandError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorReport<'a> {
+    error: &'a CommandError,
+}
+
+fn write_structured(
+    value: &impl Serialize,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, value)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Yaml => serde_yaml::to_writer(out, value)?,
+        OutputFormat::Text => unreachable!("text output is written by the report"),
+    }
+    Ok(())
+}
+
+/// Writes a subcommand's result. Errors go to stderr in text mode and to
+/// stdout as an error object otherwise; either way the process should then
+/// exit with a non-zero status.
+pub fn emit<R: Report>(
+    result: &Result<R, CommandError>,
+    format: OutputFormat,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> anyhow::Result<()> {
+    match (result, format) {
+        (Ok(report), OutputFormat::Text) => report.write_text(out)?,
+        (Ok(report), _) => write_structured(report, format, out)?,
+        (Err(error), OutputFormat::Text) => writeln!(err, "Error: {error}")?,
+        (Err(error), _) => write_structured(&ErrorReport { error }, format, out)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render<R: Report>(
+        result: Result<R, CommandError>,
+        format: OutputFormat,
+    ) -> (String, String) {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        emit(&result, format, &mut out, &mut err).unwrap();
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    fn links() -> LinksReport {
+        LinksReport {
+            path: "page.html".to_string(),
+            links: vec!["a.mp3".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_emit_json() {
+        let (out, _) = render(Ok(links()), OutputFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value["path"], "page.html");
+        assert_eq!(value["links"][0], "a.mp3");
+    }
+
+    #[test]
+    fn test_emit_yaml() {
+        let report = PlaylistReport {
+            path: "list.m3u".to_string(),
+            format: "m3u".to_string(),
+            album: None,
+            entries: vec![PlaylistEntryReport {
+                path: "a.mp3".to_string(),
+                title: Some("A".to_string()),
+                artist: None,
+                duration_secs: Some(61.0),
+                image: None,
+                start_secs: None,
+            }],
+        };
+        let (out, _) = render(Ok(report), OutputFormat::Yaml);
+        let value: serde_yaml::Value = serde_yaml::from_str(&out).unwrap();
+        assert_eq!(value["entries"][0]["title"].as_str(), Some("A"));
+        assert_eq!(value["entries"][0]["duration_secs"].as_f64(), Some(61.0));
+        assert!(value["entries"][0]["start_secs"].is_null());
+        assert!(value["album"].is_null());
+    }
+
+    #[test]
+    fn test_playlist_text_with_album() {
+        let report = PlaylistReport {
+            path: "album.cue".to_string(),
+            format: "cue".to_string(),
+            album: Some(AlbumReport {
+                title: Some("Live".to_string()),
+                genre: Some("Rock".to_string()),
+                ..Default::default()
+            }),
+            entries: vec![PlaylistEntryReport {
+                path: "album.flac".to_string(),
+                title: Some("Opening".to_string()),
+                artist: None,
+                duration_secs: Some(238.0),
+                image: None,
+                start_secs: Some(0.0),
+            }],
+        };
+        let (out, _) = render(Ok(report), OutputFormat::Text);
+        assert_eq!(
+            out,
+            "--- CUE Playlist ---\nAlbum: Live\nGenre: Rock\n\
+             Path: album.flac, Title = Opening, Duration = 238s, Start = 0s\n"
+        );
+    }
+
+    #[test]
+    fn test_emit_text() {
+        let (out, err) = render(Ok(links()), OutputFormat::Text);
+        assert_eq!(out, "--- Media Links in page.html ---\nLink: a.mp3\n");
+        assert!(err.is_empty());
+    }
+
+    #[test]
+    fn test_podcast_text() {
+        let report = PodcastReport {
+            source: "feed.xml".to_string(),
+            title: Some("Cast".to_string()),
+            author: Some("Ferris".to_string()),
+            image: None,
+            episodes: vec![EpisodeReport {
+                number: 1,
+                title: Some("Pilot".to_string()),
+                published: Some("2024-03-05".to_string()),
+                duration_secs: Some(3723.0),
+                url: "https://example.com/1.mp3".to_string(),
+                image: None,
+            }],
+        };
+        let (out, _) = render(Ok(report), OutputFormat::Text);
+        assert_eq!(
+            out,
+            "--- Cast by Ferris ---\n  1. 2024-03-05 Pilot (1:02:03)\n"
+        );
+    }
+
+    #[test]
+    fn test_subtitles_report() {
+        let track = EmbeddedTrack {
+            number: 3,
+            codec: "S_TEXT/UTF8".to_string(),
+            language: Some("eng".to_string()),
+            name: None,
+            default: true,
+            forced: false,
+        };
+        let mut report = SubtitlesReport::new("film.mkv", &[track]);
+        report.extracted.push("film.3.eng.vtt".to_string());
+
+        let (out, _) = render(Ok(report.clone()), OutputFormat::Text);
+        assert_eq!(
+            out,
+            "--- Embedded Text Tracks ---\n\
+             Track 3: S_TEXT/UTF8, Language = eng (default)\n\
+             Extracted: film.3.eng.vtt\n"
+        );
+        let (out, _) = render(Ok(report), OutputFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value["tracks"][0]["number"], 3);
+        assert_eq!(value["tracks"][0]["language"], "eng");
+        assert_eq!(value["extracted"][0], "film.3.eng.vtt");
+    }
+
+    #[test]
+    fn test_emit_errors() {
+        let error = || CommandError::new(ErrorKind::NotFound, "File not found at x.mp3");
+
+        let (out, err) = render::<LinksReport>(Err(error()), OutputFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(value["error"]["kind"], "not_found");
+        assert_eq!(value["error"]["message"], "File not found at x.mp3");
+        assert!(err.is_empty());
+
+        let (out, err) = render::<LinksReport>(Err(error()), OutputFormat::Text);
+        assert!(out.is_empty());
+        assert_eq!(err, "Error: File not found at x.mp3\n");
+    }
+}
@@ -0,0 +1,300 @@
+use crate::cast_queue::CastQueue;
+use crate::chromecast;
+use crate::cover;
+use crate::messenger::LoadRequest;
+use crate::metadata;
+use crate::player_controls::{self, PlayerContext};
+use crate::queue::{MediaSource, Queue, QueueEntry};
+use crate::queue_engine::RepeatMode;
+use crate::resume::ResumeState;
+use crate::server::{self, Resource};
+use crate::settings::Settings;
+use crate::subtitle_style::TextTrackStyle;
+use crate::subtitles::{self, SubtitleTrack};
+use crate::timecode;
+use mdns_sd::ServiceInfo;
+use rust_cast::channels::media::Metadata;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// Builds LOAD requests for queue entries, serving local files, subtitles and
+/// cover art from the media server at `base_url`.
+pub struct MediaLoader {
+    settings: Settings,
+    base_url: String,
+}
+
+impl MediaLoader {
+    pub fn new(settings: Settings, base_url: impl Into<String>) -> Self {
+        MediaLoader {
+            settings,
+            base_url: base_url.into(),
+        }
+    }
+
+    /// Returns the request for `entry` together with the subtitle tracks it
+    /// carries. `index`, the entry's index in the queue's original order,
+    /// keeps the served URLs of queue entries apart.
+    pub fn request_for(
+        &self,
+        entry: &QueueEntry,
+        index: usize,
+        context: &PlayerContext,
+    ) -> anyhow::Result<(LoadRequest, Vec<SubtitleTrack>)> {
+        let settings = &self.settings;
+        let path = match &entry.source {
+            MediaSource::Local(path) => path.as_path(),
+            // Remote URLs get a title from the last path segment.
+            MediaSource::Remote(url) => Path::new(url.split(['?', '#']).next().unwrap_or(url)),
+            MediaSource::Stream { name, .. } => Path::new(name),
+        };
+        let content_type = settings
+            .media_type
+            .clone()
+            .or_else(|| entry.content_type.clone())
+            .or_else(|| metadata::content_type_for_path(path).map(str::to_string))
+            .unwrap_or_else(|| "video/mp4".to_string());
+
+        let (media_url, tracks, images) = match &entry.source {
+            MediaSource::Remote(url) => (
+                url.clone(),
+                Vec::new(),
+                entry.image.iter().cloned().collect(),
+            ),
+            MediaSource::Local(path) => {
+                let media_path = format!("/media/{index}{}", extension_of(path));
+                context.resources.write().unwrap().insert(
+                    media_path.clone(),
+                    Resource::file(content_type.clone(), path),
+                );
+
+                let tracks = subtitles::load_subtitle_tracks(path, settings, index == 0)?;
+                subtitles::serve_tracks(&tracks, index, &context.resources, 0);
+                let cover_path = if settings.no_cover {
+                    None
+                } else {
+                    cover::serve_cover(path, index, &context.resources)
+                };
+                let images = cover_path
+                    .map(|cover_path| format!("{}{cover_path}", self.base_url))
+                    .or_else(|| entry.image.clone())
+                    .into_iter()
+                    .collect();
+                (format!("{}{media_path}", self.base_url), tracks, images)
+            }
+            MediaSource::Stream { source, .. } => {
+                let media_path = format!("/media/{index}{}", extension_of(path));
+                context.resources.write().unwrap().insert(
+                    media_path.clone(),
+                    Resource::stream(content_type.clone(), source.clone()),
+                );
+                let images = entry.image.iter().cloned().collect();
+                (format!("{}{media_path}", self.base_url), Vec::new(), images)
+            }
+        };
+
+        let mut request = LoadRequest::new(media_url, content_type);
+        request.metadata = if settings.no_metadata {
+            metadata::images_only(images)
+        } else {
+            Some(metadata::build_metadata(
+                path,
+                &request.content_type,
+                metadata::Hints {
+                    title: entry.title.as_deref(),
+                    artist: entry.artist.as_deref(),
+                },
+                images,
+            ))
+        };
+        if entry.start_secs.is_some() {
+            // A CUE track's own details beat the tags of the file it's cut from.
+            if let Some(Metadata::MusicTrack(music)) = &mut request.metadata {
+                music.title = entry.title.clone().or(music.title.take());
+                music.artist = entry.artist.clone().or(music.artist.take());
+                music.album_name = entry.album.clone().or(music.album_name.take());
+                music.track_number = None;
+            }
+        }
+        request.current_time = entry.start_secs.unwrap_or_default();
+        request.tracks = tracks
+            .iter()
+            .map(|track| track.text_track(&self.base_url, index))
+            .collect();
+        request.active_track_ids = request
+            .tracks
+            .first()
+            .map(|track| track.id)
+            .into_iter()
+            .collect();
+        request.text_track_style = Some(context.subtitle_style.clone());
+        Ok((request, tracks))
+    }
+}
+
+/// The path's extension with its dot, kept on served URLs so receivers that
+/// sniff the URL see the right type.
+fn extension_of(path: &Path) -> String {
+    path.extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default()
+}
+
+/// Prints the "Playing n/m" line for the current queue entry.
+pub fn announce(queue: &Queue) {
+    if let Some(entry) = queue.current() {
+        if queue.len() > 1 {
+            println!(
+                "Playing {}/{}: {}",
+                queue.position() + 1,
+                queue.len(),
+                entry.label()
+            );
+        } else {
+            println!("Playing {}", entry.label());
+        }
+    }
+}
+
+/// Casts `queue` in order and hands over to the player controls, which
+/// advance through the rest of it. With `--native-queue` the receiver runs
+/// the queue instead.
+pub async fn play_queue(
+    settings: &Settings,
+    device_info: &ServiceInfo,
+    queue: Queue,
+) -> anyhow::Result<()> {
+    let Some(entry) = queue.current().cloned() else {
+        return Err(anyhow::anyhow!("Nothing to play"));
+    };
+
+    let mut context = PlayerContext {
+        subtitle_style: TextTrackStyle::from_settings(settings)?,
+        queue,
+        save_playlist: settings.save_playlist.as_ref().map(PathBuf::from),
+        absolute_paths: settings.absolute_paths,
+        resume: ResumeState::load(
+            settings.resume_file.as_deref().map(Path::new),
+            settings.resume,
+        ),
+        ..Default::default()
+    };
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let (server_addr, server_handle) =
+        server::start_resource_server(context.resources.clone(), rx).await?;
+    let local_ip = chromecast::local_ip_for(device_info)?;
+    context.loader = Some(MediaLoader::new(
+        settings.clone(),
+        format!("http://{}", SocketAddr::new(local_ip, server_addr.port())),
+    ));
+
+    // The receiver can't play part of a file and stop at its end.
+    let shares_files = context.queue.iter().any(|entry| entry.start_secs.is_some());
+    if settings.native_queue && shares_files {
+        eprintln!(
+            "Tracks or segments within a file can't be queued on the receiver; loading them one by one."
+        );
+    }
+    let native_queue = settings.native_queue && !shares_files;
+    let start_time = match settings
+        .seek
+        .as_deref()
+        .map(timecode::parse_time)
+        .transpose()?
+    {
+        // --seek wins over a remembered position.
+        Some(seek) => {
+            let start_time = seek
+                .resolve(entry.duration_secs)
+                .map(|time| entry.start_secs.unwrap_or_default() + time);
+            if start_time.is_none() {
+                // A percentage of a duration the receiver has yet to report.
+                context.pending_seek = Some(seek);
+            }
+            start_time
+        }
+        None => context
+            .resume
+            .as_ref()
+            .and_then(|resume| resume.offer(&entry)),
+    };
+    announce(&context.queue);
+    let (session, cast_queue) = if native_queue {
+        let session = chromecast::launch(device_info).await?;
+        let repeat_mode = if settings.loop_playback {
+            RepeatMode::All
+        } else {
+            RepeatMode::Off
+        };
+        let cast_queue = CastQueue::load(
+            &session.messenger,
+            &session.session_id,
+            &mut context,
+            repeat_mode,
+            start_time,
+        )?;
+        (session, Some(cast_queue))
+    } else {
+        let loader = context.loader.as_ref().unwrap();
+        let index = context.queue.current_index().unwrap_or_default();
+        let (mut request, tracks) = loader.request_for(&entry, index, &context)?;
+        if let Some(start_time) = start_time {
+            request.current_time = start_time;
+        }
+        let session = chromecast::cast(device_info, &request).await?;
+        context.subtitle_tracks = tracks;
+        context.active_subtitle = request.active_track_ids.first().copied();
+        (session, None)
+    };
+
+    if settings.exit {
+        let _ = tx.send(());
+        server_handle.await?;
+    }
+    player_controls::handle_player_controls(session, context, cast_queue).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+
+    #[test]
+    fn test_subtitles_go_with_the_first_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let srt = dir.path().join("first.srt");
+        fs::write(&srt, "1\n00:00:01,000 --> 00:00:02,000\nHello\n").unwrap();
+        let entries: Vec<QueueEntry> = ["first.mp4", "second.mp3"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                fs::write(&path, b"media").unwrap();
+                QueueEntry::new(MediaSource::Local(path))
+            })
+            .collect();
+        let context = PlayerContext {
+            queue: Queue::new(entries.clone(), false, None),
+            ..Default::default()
+        };
+        let loader = |args: &[&str]| {
+            let settings = Settings::parse_from(["gemini_castnow"].iter().chain(args));
+            MediaLoader::new(settings, "http://192.168.1.2:8000")
+        };
+
+        let with_srt = loader(&["--subtitles", srt.to_str().unwrap()]);
+        let (request, tracks) = with_srt.request_for(&entries[0], 0, &context).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(request.tracks.len(), 1);
+        let (request, tracks) = with_srt.request_for(&entries[1], 1, &context).unwrap();
+        assert!(tracks.is_empty());
+        assert!(request.tracks.is_empty());
+
+        // Neither is Matroska: only the first entry, which the tracks were
+        // asked for, fails.
+        let with_tracks = loader(&["--subtitle-tracks", "1"]);
+        assert!(with_tracks.request_for(&entries[0], 0, &context).is_err());
+        let (_, tracks) = with_tracks.request_for(&entries[1], 1, &context).unwrap();
+        assert!(tracks.is_empty());
+    }
+}
@@ -3,217 +3,1042 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode},
 };
 use futures::StreamExt;
+use rust_cast::channels::media::{PlayerState, StatusEntry};
+use rust_cast::channels::receiver::Volume;
 use rust_cast::CastDevice;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
+use crate::ab_loop::AbLoop;
+use crate::cast_queue::CastQueue;
+use crate::chromecast::CastSession;
+use crate::messenger::Messenger;
+use crate::playback::{self, MediaLoader};
+use crate::playlist;
+use crate::queue::{MediaSource, Queue};
+use crate::queue_engine::{Action, QueueEngine, QueueEvent, ReceiverState, RepeatMode};
+use crate::resume::ResumeState;
+use crate::server::Resources;
+use crate::subtitle_style::TextTrackStyle;
+use crate::subtitles::{self, SubtitleTrack};
+use crate::timecode::{self, TimeSpec};
+
+/// Subtitle timing step for the offset keys, in milliseconds.
+const SUBTITLE_OFFSET_STEP_MS: i64 = 100;
+/// Subtitle font scale step for the size keys.
+const SUBTITLE_SCALE_STEP: f32 = 0.1;
+/// Where the `w` key saves the queue when `--save-playlist` isn't given.
+const DEFAULT_QUEUE_PLAYLIST: &str = "queue.m3u8";
+/// How often the receiver is polled to notice the end of the current item.
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How close to B playback has to be when the loop timer fires to go back
+/// to A, since reported times lag a little.
+const LOOP_END_SLACK_SECS: f64 = 0.5;
+/// How many entries the `u` key lists.
+const UPCOMING_COUNT: usize = 5;
+/// Volume step for the up and down keys.
+const VOLUME_STEP: f32 = 0.05;
+/// Seek step for the left and right keys, in seconds.
+const SEEK_STEP_SECS: f32 = 10.0;
+/// Shown when a subtitle key is pressed without any tracks.
+const NO_TEXT_TRACKS: &str = "No text tracks found.";
+
+/// State the key handlers need beyond the cast session itself.
+#[derive(Default)]
+pub struct PlayerContext {
+    pub subtitle_tracks: Vec<SubtitleTrack>,
+    pub active_subtitle: Option<u32>,
+    pub subtitle_offset_ms: i64,
+    pub subtitle_style: TextTrackStyle,
+    pub resources: Resources,
+    pub queue: Queue,
+    pub loader: Option<MediaLoader>,
+    /// `--save-playlist`: where the queue is saved on exit.
+    pub save_playlist: Option<PathBuf>,
+    pub absolute_paths: bool,
+    /// Remembered playback positions, unless there is nowhere to keep them.
+    pub resume: Option<ResumeState>,
+    /// `--seek` to a percentage, done once the receiver reports the duration.
+    pub pending_seek: Option<TimeSpec>,
+    /// The A-B repeat range set with the `a` and `b` keys.
+    pub ab_loop: Option<AbLoop>,
+    /// Set while the receiver is sent back to the item an A-B loop is in.
+    pub returning_to_loop: bool,
+}
+
+impl PlayerContext {
+    /// Saves the queue to `--save-playlist`, or `queue.m3u8` for the `w` key.
+    fn save_queue(&self) {
+        let path = self
+            .save_playlist
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_QUEUE_PLAYLIST));
+        match playlist::save_queue(&self.queue, &path, self.absolute_paths) {
+            Ok(()) => println!("Saved the queue to {}", path.display()),
+            Err(e) => eprintln!("Error saving the queue: {e}"),
+        }
+    }
+
+    /// Remembers how far into the current entry the receiver is.
+    fn record_position(&mut self, status: Option<&StatusEntry>, state: ReceiverState) {
+        let (Some(resume), Some(entry)) = (&mut self.resume, self.queue.current()) else {
+            return;
+        };
+        match state {
+            ReceiverState::Active(Some(time)) => {
+                let duration = status
+                    .and_then(|status| status.media.as_ref())
+                    .and_then(|media| media.duration)
+                    .map(f64::from);
+                resume.update(entry, time, duration);
+            }
+            ReceiverState::Finished => resume.finish(entry),
+            _ => {}
+        }
+        resume.save_if_due();
+    }
+}
+
+enum PlayerEvent {
+    Key(KeyCode),
+    Tick,
+    /// Playback should have reached the B point of the A-B loop.
+    LoopEnd,
+    /// A line typed at a prompt.
+    Entered(PromptKind, String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PromptKind {
+    /// `j`: an item number to jump to.
+    Jump,
+    /// `g`: a time to go to.
+    Seek,
+}
+
+enum PromptInput {
+    Typing,
+    Cancelled,
+    Entered(String),
+}
+
+/// A line typed after a prompt key, read key by key from the event loop
+/// since the terminal is in raw mode.
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+impl Prompt {
+    fn new(kind: PromptKind, message: &str) -> Self {
+        print!("{message}");
+        let _ = io::stdout().flush();
+        Prompt {
+            kind,
+            input: String::new(),
+        }
+    }
+
+    /// Takes one key: Enter finishes the line and Esc cancels it.
+    fn key(&mut self, key_code: KeyCode) -> PromptInput {
+        match key_code {
+            KeyCode::Enter => {
+                println!();
+                return PromptInput::Entered(std::mem::take(&mut self.input));
+            }
+            KeyCode::Esc => {
+                println!();
+                return PromptInput::Cancelled;
+            }
+            KeyCode::Backspace if self.input.pop().is_some() => print!("\x08 \x08"),
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                print!("{c}");
+            }
+            _ => {}
+        }
+        let _ = io::stdout().flush();
+        PromptInput::Typing
+    }
+}
+
+/// The receiver's status for the current media, if it has any.
+fn media_status(device: &CastDevice, transport_id: &str) -> Option<StatusEntry> {
+    let status = device.media.get_status(transport_id, None).ok()?;
+    status.entries.into_iter().next()
+}
+
+/// Where `time` into the current entry is in the media, counted from the
+/// entry's start for tracks within a longer file. `None` when `time` is a
+/// percentage and the duration isn't known yet.
+fn seek_target(queue: &Queue, status: &StatusEntry, time: TimeSpec) -> Option<f64> {
+    let (start, duration) = item_span(queue, status);
+    Some(start + time.resolve(duration)?)
+}
+
+/// Seeks to `time` into the current entry (see [`seek_target`]). Returns
+/// false when the duration it needs isn't known yet.
+fn go_to(
+    device: &CastDevice,
+    transport_id: &str,
+    queue: &Queue,
+    status: &StatusEntry,
+    time: TimeSpec,
+) -> bool {
+    let Some(time) = seek_target(queue, status, time) else {
+        return false;
+    };
+    let _ = device.media.seek(
+        transport_id,
+        status.media_session_id,
+        Some(time as f32),
+        None,
+    );
+    true
+}
+
+/// Where the current entry starts in the media and how long it runs, if
+/// that's known.
+fn item_span(queue: &Queue, status: &StatusEntry) -> (f64, Option<f64>) {
+    let media_duration = status
+        .media
+        .as_ref()
+        .and_then(|media| media.duration)
+        .map(f64::from);
+    let entry = queue.current();
+    match entry.and_then(|entry| entry.start_secs) {
+        Some(start) => (
+            start,
+            entry
+                .and_then(|entry| entry.duration_secs)
+                .or(media_duration.map(|duration| duration - start)),
+        ),
+        None => (
+            0.0,
+            media_duration.or(entry.and_then(|entry| entry.duration_secs)),
+        ),
+    }
+}
+
+/// Prints the A-B loop over the current entry's timeline.
+fn show_loop(ab_loop: &AbLoop, queue: &Queue, status: &StatusEntry) {
+    let (start, duration) = item_span(queue, status);
+    let span = duration.map(|duration| (start, start + duration));
+    let time = status.current_time.map(f64::from);
+    println!("{}", ab_loop.timeline(time, span));
+}
+
+/// Goes back to A and shows the loop.
+fn loop_back(
+    device: &CastDevice,
+    transport_id: &str,
+    ab_loop: &AbLoop,
+    queue: &Queue,
+    status: &StatusEntry,
+) {
+    let _ = device.media.seek(
+        transport_id,
+        status.media_session_id,
+        Some(ab_loop.a() as f32),
+        None,
+    );
+    show_loop(ab_loop, queue, status);
+}
+
+/// What one status poll calls for on the receiver, worked out by
+/// [`plan_tick`].
+#[derive(Debug, Default, PartialEq)]
+struct TickPlan {
+    /// Playback passed B: seek back to A, which is all this poll does.
+    loop_back: bool,
+    /// B comes before the next poll, this many seconds from now.
+    loop_timer: Option<f64>,
+    /// Seek to this time in the media, for a `--seek` that had to wait for
+    /// the duration.
+    seek: Option<f64>,
+    /// Send the receiver back to the queue item an A-B loop is in.
+    return_to_item: Option<i32>,
+    /// Append the coming items to the receiver's queue.
+    fill: bool,
+    action: Option<Action>,
+}
+
+/// Takes one status poll: keeps an A-B loop going, follows the receiver
+/// through the queue and works out what to play next.
+fn plan_tick(
+    context: &mut PlayerContext,
+    engine: &mut QueueEngine,
+    cast_queue: Option<&mut CastQueue>,
+    status: Option<&StatusEntry>,
+) -> TickPlan {
+    let state = ReceiverState::from_status(status);
+    let index = context.queue.current_index();
+    let mut plan = TickPlan::default();
+    // A loop ends with the entry it was set in.
+    if context
+        .ab_loop
+        .is_some_and(|ab_loop| !ab_loop.is_for(index))
+    {
+        context.ab_loop = None;
+    }
+    let time = match state {
+        ReceiverState::Active(time) => time,
+        _ => None,
+    };
+    if let (Some(ab_loop), Some(time)) = (context.ab_loop, time) {
+        if ab_loop.wrap(index, time).is_some() {
+            plan.loop_back = true;
+            return plan;
+        }
+        let playing = status.is_some_and(|status| status.player_state == PlayerState::Playing);
+        plan.loop_timer = ab_loop
+            .until_b(index, time)
+            .filter(|&left| playing && left < STATUS_POLL_INTERVAL.as_secs_f64());
+    }
+    if let (Some(time), Some(status), ReceiverState::Active(_)) =
+        (context.pending_seek, status, state)
+    {
+        plan.seek = seek_target(&context.queue, status, time);
+        if plan.seek.is_some() {
+            context.pending_seek = None;
+        }
+    }
+    // A complete loop keeps the queue on its entry.
+    let held = context.ab_loop.filter(|ab_loop| ab_loop.holds(index));
+    plan.action = match (cast_queue, held) {
+        (Some(cast_queue), Some(_)) => {
+            let Some(status) = status else {
+                return plan;
+            };
+            let loop_item = cast_queue.item_id(context.queue.position());
+            let item_id = status.current_item_id.map(i32::from);
+            // The receiver went on past B: send it back.
+            plan.return_to_item = loop_item.filter(|&loop_item| {
+                item_id != Some(loop_item) || state == ReceiverState::Finished
+            });
+            if plan.return_to_item.is_some() {
+                context.returning_to_loop = true;
+                return plan;
+            }
+            if context.returning_to_loop && time.is_some() {
+                context.returning_to_loop = false;
+                plan.loop_back = true;
+            }
+            context.record_position(Some(status), state);
+            None
+        }
+        (Some(cast_queue), None) => {
+            let moved = status
+                .and_then(|status| status.current_item_id)
+                .and_then(|item_id| cast_queue.follow(context, i32::from(item_id)));
+            plan.fill = status.is_some();
+            context.record_position(status, state);
+            engine.on_receiver_queue(state, moved)
+        }
+        (None, Some(ab_loop)) => {
+            context.record_position(status, state);
+            match state {
+                ReceiverState::Finished | ReceiverState::NoMedia
+                    if context.pending_seek.is_none() =>
+                {
+                    // Played on past B to the end: start over at A.
+                    let start = context
+                        .queue
+                        .current()
+                        .and_then(|entry| entry.start_secs)
+                        .unwrap_or_default();
+                    context.pending_seek = Some(TimeSpec::Secs(ab_loop.a() - start));
+                    Some(Action::Play { playing: None })
+                }
+                _ => None,
+            }
+        }
+        (None, None) => {
+            context.record_position(status, state);
+            engine.on_status(&mut context.queue, state)
+        }
+    };
+    plan
+}
+
+/// Whether playback at `time` has reached B when the loop timer fires.
+/// Reported times lag a little, so just short of B counts.
+fn loop_end_due(context: &PlayerContext, time: Option<f64>) -> bool {
+    let index = context.queue.current_index();
+    context
+        .ab_loop
+        .zip(time)
+        .and_then(|(ab_loop, time)| ab_loop.until_b(index, time))
+        .is_some_and(|left| left < LOOP_END_SLACK_SECS)
+}
+
+/// What's left to do for a key once `on_key` has updated the player's own
+/// state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    TogglePause,
+    ToggleMute,
+    Volume(f32),
+    SeekBy(f32),
+    Stop,
+    /// Switch to the given subtitle track, or turn subtitles off.
+    SetSubtitles(Option<u32>),
+    /// Make the receiver fetch the re-timed subtitles.
+    ReloadSubtitles,
+    UpdateStyle,
+    ShowLoop,
+    Previous,
+    Next,
+    CycleRepeat,
+    ToggleStopAfterCurrent,
+    Prompt(PromptKind),
+    ShowUpcoming,
+    SaveQueue,
+    Quit,
+    Say(&'static str),
+}
+
+/// Maps a key to a command, updating the loop points, subtitle offset and
+/// style on the way. `time` is where the receiver is, for the loop keys.
+fn on_key(context: &mut PlayerContext, key_code: KeyCode, time: Option<f64>) -> Option<Command> {
+    let command = match key_code {
+        KeyCode::Char(' ') => Command::TogglePause,
+        KeyCode::Char('m') => Command::ToggleMute,
+        KeyCode::Char('t' | '[' | ']') if context.subtitle_tracks.is_empty() => {
+            Command::Say(NO_TEXT_TRACKS)
+        }
+        KeyCode::Char('t') => {
+            // Cycle subtitle tracks, including off
+            Command::SetSubtitles(subtitles::next_track(
+                &context.subtitle_tracks,
+                context.active_subtitle,
+            ))
+        }
+        KeyCode::Char(c @ ('[' | ']')) => {
+            // Shift subtitle timing
+            if c == '[' {
+                context.subtitle_offset_ms -= SUBTITLE_OFFSET_STEP_MS;
+            } else {
+                context.subtitle_offset_ms += SUBTITLE_OFFSET_STEP_MS;
+            }
+            subtitles::serve_tracks(
+                &context.subtitle_tracks,
+                context.queue.current_index().unwrap_or_default(),
+                &context.resources,
+                context.subtitle_offset_ms,
+            );
+            Command::ReloadSubtitles
+        }
+        KeyCode::Char(c @ ('+' | '-' | 'c' | 'k' | 'e' | 'f')) => {
+            // Subtitle style: size, text colour, background, edge, font
+            let style = &mut context.subtitle_style;
+            match c {
+                '+' => style.scale_by(SUBTITLE_SCALE_STEP),
+                '-' => style.scale_by(-SUBTITLE_SCALE_STEP),
+                'c' => style.cycle_foreground(),
+                'k' => style.cycle_background(),
+                'e' => style.cycle_edge_type(),
+                _ => style.cycle_font_family(),
+            }
+            Command::UpdateStyle
+        }
+        KeyCode::Up => Command::Volume(VOLUME_STEP),
+        KeyCode::Down => Command::Volume(-VOLUME_STEP),
+        KeyCode::Left => Command::SeekBy(-SEEK_STEP_SECS),
+        KeyCode::Right => Command::SeekBy(SEEK_STEP_SECS),
+        KeyCode::Char('s') => Command::Stop,
+        KeyCode::Char(c @ ('a' | 'b')) => {
+            // Set the A or B point of a repeat loop
+            let (Some(index), Some(time)) = (context.queue.current_index(), time) else {
+                return None;
+            };
+            let set = if c == 'a' {
+                context.ab_loop = Some(AbLoop::set_a(context.ab_loop, index, time));
+                true
+            } else {
+                context
+                    .ab_loop
+                    .as_mut()
+                    .is_some_and(|ab_loop| ab_loop.set_b(index, time))
+            };
+            if set {
+                Command::ShowLoop
+            } else {
+                Command::Say("Set A first with a; B has to come after it.")
+            }
+        }
+        KeyCode::Char('l') if context.ab_loop.take().is_some() => Command::Say("Loop cleared"),
+        KeyCode::Char('p') => Command::Previous,
+        KeyCode::Char('n') => Command::Next,
+        KeyCode::Char('r') => Command::CycleRepeat,
+        KeyCode::Char('x') => Command::ToggleStopAfterCurrent,
+        KeyCode::Char('j') => Command::Prompt(PromptKind::Jump),
+        KeyCode::Char('g') => Command::Prompt(PromptKind::Seek),
+        KeyCode::Char('u') => Command::ShowUpcoming,
+        KeyCode::Char('w') => Command::SaveQueue,
+        KeyCode::Char('q') => Command::Quit,
+        _ => return None,
+    };
+    Some(command)
+}
+
+/// Carries out a command that only talks to the receiver or the terminal.
+/// The queue commands are left to the event loop.
+fn run_command(
+    device: &CastDevice,
+    transport_id: &str,
+    messenger: &Messenger,
+    context: &mut PlayerContext,
+    status: Option<&StatusEntry>,
+    command: Command,
+) {
+    let media_session_id = status.map(|status| status.media_session_id);
+    match command {
+        Command::TogglePause => {
+            println!("Toggling play/pause...");
+            if let Some(status) = status {
+                let _ = if status.player_state == PlayerState::Playing {
+                    device.media.pause(transport_id, status.media_session_id)
+                } else {
+                    device.media.play(transport_id, status.media_session_id)
+                };
+            }
+        }
+        Command::ToggleMute => {
+            println!("Toggling mute...");
+            if let Ok(receiver_status) = device.receiver.get_status() {
+                let current_volume = &receiver_status.volume;
+                let _ = device.receiver.set_volume(Volume {
+                    level: current_volume.level,
+                    muted: Some(!current_volume.muted.unwrap_or(false)),
+                });
+            }
+        }
+        Command::Volume(step) => {
+            println!(
+                "{}",
+                if step > 0.0 {
+                    "Volume up..."
+                } else {
+                    "Down..."
+                }
+            );
+            if let Ok(receiver_status) = device.receiver.get_status() {
+                let current_volume = &receiver_status.volume;
+                let new_level = (current_volume.level.unwrap_or(0.0) + step).clamp(0.0, 1.0);
+                let _ = device.receiver.set_volume(Volume {
+                    level: Some(new_level),
+                    muted: current_volume.muted,
+                });
+            }
+        }
+        Command::SeekBy(step) => {
+            if step < 0.0 {
+                println!("Seeking backward...");
+            } else {
+                println!("Seeking forward...");
+            }
+            if let Some(status) = status {
+                let current_time = status.current_time.unwrap_or(0.0);
+                // Forward stops at the end, or where it is if that isn't known
+                let duration = status
+                    .media
+                    .as_ref()
+                    .and_then(|media| media.duration)
+                    .unwrap_or(current_time);
+                let new_time = if step < 0.0 {
+                    (current_time + step).max(0.0)
+                } else {
+                    (current_time + step).min(duration)
+                };
+                let _ =
+                    device
+                        .media
+                        .seek(transport_id, status.media_session_id, Some(new_time), None);
+            }
+        }
+        Command::Stop => {
+            println!("Stopping playback...");
+            if let Some(media_session_id) = media_session_id {
+                let _ = device.media.stop(transport_id, media_session_id);
+            }
+        }
+        Command::SetSubtitles(next) => {
+            let Some(media_session_id) = media_session_id else {
+                return;
+            };
+            let active_track_ids: Vec<u32> = next.into_iter().collect();
+            if let Err(e) = messenger.set_active_tracks(media_session_id, &active_track_ids) {
+                eprintln!("Error switching subtitles: {e}");
+                return;
+            }
+            context.active_subtitle = next;
+            match context
+                .subtitle_tracks
+                .iter()
+                .find(|track| Some(track.id) == next)
+            {
+                Some(track) => {
+                    println!("Subtitles: {}", track.name.as_deref().unwrap_or("untitled"))
+                }
+                None => println!("Subtitles off"),
+            }
+        }
+        Command::ReloadSubtitles => {
+            println!("Subtitle offset: {} ms", context.subtitle_offset_ms);
+            // Toggling the track off and on makes the receiver fetch the regenerated file.
+            if let (Some(track_id), Some(media_session_id)) =
+                (context.active_subtitle, media_session_id)
+            {
+                if let Err(e) = messenger
+                    .set_active_tracks(media_session_id, &[])
+                    .and_then(|_| messenger.set_active_tracks(media_session_id, &[track_id]))
+                {
+                    eprintln!("Error reloading subtitles: {e}");
+                }
+            }
+        }
+        Command::UpdateStyle => {
+            let style = &context.subtitle_style;
+            println!("Subtitle style: {:?}", style.to_json());
+            if let Some(media_session_id) = media_session_id {
+                if let Err(e) = messenger.set_text_track_style(media_session_id, style) {
+                    eprintln!("Error updating subtitle style: {e}");
+                }
+            }
+        }
+        Command::ShowLoop => {
+            if let (Some(ab_loop), Some(status)) = (&context.ab_loop, status) {
+                show_loop(ab_loop, &context.queue, status);
+            }
+        }
+        Command::ShowUpcoming => show_upcoming(&context.queue),
+        Command::SaveQueue => context.save_queue(),
+        Command::Say(message) => println!("{message}"),
+        Command::Previous
+        | Command::Next
+        | Command::CycleRepeat
+        | Command::ToggleStopAfterCurrent
+        | Command::Prompt(_)
+        | Command::Quit => {}
+    }
+}
+
+/// Lists the next few entries of the queue.
+fn show_upcoming(queue: &Queue) {
+    let mut upcoming = queue.upcoming(UPCOMING_COUNT).peekable();
+    if upcoming.peek().is_none() {
+        println!("Nothing after this item.");
+        return;
+    }
+    println!("Up next:");
+    for (position, entry) in upcoming {
+        println!("  {}. {}", position + 1, entry.label());
+    }
+}
+
+/// Prints what the queue engine reports.
+fn render(event: QueueEvent, queue: &Queue) {
+    match event {
+        QueueEvent::NowPlaying => playback::announce(queue),
+        QueueEvent::Restarted => {
+            if let Some(entry) = queue.current() {
+                println!("Back to the start of {}", entry.label());
+            }
+        }
+        QueueEvent::Looped => println!("Back to the start of the queue."),
+        QueueEvent::EndOfQueue if queue.len() > 1 => println!("End of queue."),
+        QueueEvent::EndOfQueue => {}
+        QueueEvent::StoppedAfterCurrent => println!("Stopped after the current item."),
+        QueueEvent::RepeatChanged(mode) => match mode {
+            RepeatMode::Off => println!("Repeat off"),
+            RepeatMode::One => println!("Repeat one"),
+            RepeatMode::All => println!("Repeat all"),
+        },
+        QueueEvent::StopAfterCurrentChanged(true) => println!("Stopping after the current item"),
+        QueueEvent::StopAfterCurrentChanged(false) => println!("Playing on after the current item"),
+    }
+}
+
+/// Loads the queue's current entry on the receiver.
+fn load_current(
+    messenger: &Messenger,
+    session_id: &str,
+    context: &mut PlayerContext,
+) -> anyhow::Result<()> {
+    let (Some(loader), Some(index), Some(entry)) = (
+        &context.loader,
+        context.queue.current_index(),
+        context.queue.current(),
+    ) else {
+        return Ok(());
+    };
+    let (mut request, tracks) = loader.request_for(entry, index, context)?;
+    if let Some(time) = context
+        .resume
+        .as_ref()
+        .and_then(|resume| resume.resume_at(entry))
+    {
+        request.current_time = time;
+    }
+    messenger.load(session_id, &request)?;
+    context.subtitle_tracks = tracks;
+    context.active_subtitle = request.active_track_ids.first().copied();
+    context.subtitle_offset_ms = 0;
+    Ok(())
+}
+
+/// Switches the receiver to the queue's current entry. Another track of the
+/// media that is `playing` is reached by seeking; anything else is loaded.
+fn play_current(
+    device: &CastDevice,
+    transport_id: &str,
+    messenger: &Messenger,
+    session_id: &str,
+    context: &mut PlayerContext,
+    playing: Option<&MediaSource>,
+) -> anyhow::Result<()> {
+    let seek_to = context
+        .queue
+        .current()
+        .filter(|entry| Some(&entry.source) == playing)
+        .and_then(|entry| entry.start_secs);
+    if let Some(start) = seek_to {
+        let status = device.media.get_status(transport_id, None)?;
+        if let Some(media_status) = status.entries.first() {
+            device.media.seek(
+                transport_id,
+                media_status.media_session_id,
+                Some(start as f32),
+                None,
+            )?;
+            return Ok(());
+        }
+    }
+    load_current(messenger, session_id, context)
+}
+
+/// Runs the key and status loop. With a `cast_queue` the receiver moves
+/// through the queue itself, and `n`/`p` jump between its items.
 pub async fn handle_player_controls(
-    device: CastDevice<'_>,
-    transport_id: String,
-    _session_id: String,
+    session: CastSession<'_>,
+    mut context: PlayerContext,
+    mut cast_queue: Option<CastQueue>,
 ) -> Result<(), anyhow::Error> {
+    let CastSession {
+        device,
+        messenger,
+        transport_id,
+        session_id,
+    } = session;
+
     enable_raw_mode()?;
     let mut reader = event::EventStream::new();
     let (tx, mut rx) = mpsc::unbounded_channel();
 
+    let tick_tx = tx.clone();
+    let loop_tx = tx.clone();
     tokio::spawn(async move {
         loop {
             let event = reader.next().await;
             if let Some(Ok(Event::Key(key_event))) = event {
                 if key_event.kind == KeyEventKind::Press {
-                    tx.send(key_event.code).unwrap();
+                    tx.send(PlayerEvent::Key(key_event.code)).unwrap();
                 }
             }
         }
     });
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STATUS_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if tick_tx.send(PlayerEvent::Tick).is_err() {
+                break;
+            }
+        }
+    });
 
-    while let Some(key_code) = rx.recv().await {
-        match key_code {
-            KeyCode::Char(' ') => {
-                // Play/Pause toggle
-                println!("Toggling play/pause...");
-                if let Ok(status) = device.media.get_status(&transport_id, None) {
-                    if let Some(media_status) = status.entries.first() {
-                        if media_status.player_state
-                            == rust_cast::channels::media::PlayerState::Playing
-                        {
-                            let _ = device
-                                .media
-                                .pause(&transport_id, media_status.media_session_id);
+    let mut engine = QueueEngine::default();
+    let mut prompt: Option<Prompt> = None;
+    // Sends `LoopEnd` when B is due between two polls.
+    let mut loop_timer: Option<tokio::task::JoinHandle<()>> = None;
+    while let Some(event) = rx.recv().await {
+        // While a prompt is open, keys go to it.
+        let event = match (event, prompt.take()) {
+            (PlayerEvent::Key(key_code), Some(mut open)) => match open.key(key_code) {
+                PromptInput::Typing => {
+                    prompt = Some(open);
+                    continue;
+                }
+                PromptInput::Cancelled => continue,
+                PromptInput::Entered(input) => PlayerEvent::Entered(open.kind, input),
+            },
+            (event, open) => {
+                prompt = open;
+                event
+            }
+        };
+        let action = match event {
+            PlayerEvent::Key(key_code) => {
+                let status = media_status(&device, &transport_id);
+                let time = status
+                    .as_ref()
+                    .and_then(|status| status.current_time)
+                    .map(f64::from);
+                match on_key(&mut context, key_code, time) {
+                    Some(command @ (Command::Previous | Command::Next)) if cast_queue.is_some() => {
+                        // Previous/next item in the receiver's queue
+                        let next = command == Command::Next;
+                        let restart = if next {
+                            None
+                        } else {
+                            engine.restart(&context.queue, time)
+                        };
+                        let queue = &context.queue;
+                        let at_end = if next {
+                            queue.position() + 1 == queue.len()
+                        } else {
+                            queue.position() == 0
+                        };
+                        if restart.is_some() {
+                            restart
+                        } else if at_end && !queue.loops() {
+                            println!("No more items in the queue.");
+                            None
                         } else {
-                            let _ = device
-                                .media
-                                .play(&transport_id, media_status.media_session_id);
+                            // Past the last item is the next loop's first, which is
+                            // already on the receiver.
+                            let (position, len) = (queue.position(), queue.len());
+                            let target = if next {
+                                (position + 1 < len).then_some(position + 1)
+                            } else {
+                                Some((position + len - 1) % len)
+                            };
+                            if let (Some(status), Some(cast_queue)) = (&status, &mut cast_queue) {
+                                let media_session_id = status.media_session_id;
+                                let switched = match target {
+                                    Some(target) => cast_queue.jump_to(
+                                        &messenger,
+                                        &session_id,
+                                        media_session_id,
+                                        &context,
+                                        target,
+                                        engine.repeat_mode(&context.queue),
+                                    ),
+                                    None => messenger.queue_jump(media_session_id, 1).map(drop),
+                                };
+                                if let Err(e) = switched {
+                                    eprintln!("Error switching items: {e}");
+                                }
+                            }
+                            None
                         }
                     }
-                }
-            }
-            KeyCode::Char('m') => {
-                // Mute toggle
-                println!("Toggling mute...");
-                if let Ok(receiver_status) = device.receiver.get_status() {
-                    let current_volume = &receiver_status.volume;
-                    let _ = device
-                        .receiver
-                        .set_volume(rust_cast::channels::receiver::Volume {
-                            level: current_volume.level,
-                            muted: Some(!current_volume.muted.unwrap_or(false)), // unwrap_or(false) を追加
-                        });
-                }
-            }
-            // KeyCode::Char('t') => {
-            //     // Subtitle toggle
-            //     println!("Toggling subtitles...");
-            //     if let Ok(status) = device.media.get_status(&transport_id, None) {
-            //         if let Some(media_status) = status.entries.first() {
-            //             let mut active_track_ids = media_status.active_track_ids.clone().unwrap_or_default();
-            //             let text_track_id = media_status.tracks.as_ref().and_then(|tracks| {
-            //                 tracks.iter().find(|track| track.track_type == rust_cast::channels::media::TrackType::Text)
-            //                     .map(|track| track.track_id)
-            //             });
-            //
-            //             if let Some(track_id) = text_track_id {
-            //                 if active_track_ids.contains(&track_id) {
-            //                     // Subtitle is active, deactivate it
-            //                     active_track_ids.retain(|&id| id != track_id);
-            //                     println!("Subtitles off");
-            //                 } else {
-            //                     // Subtitle is inactive, activate it
-            //                     active_track_ids.push(track_id);
-            //                     println!("Subtitles on");
-            //                 }
-            //                 let _ = device.media.set_active_media_tracks(
-            //                     &transport_id,
-            //                     media_status.media_session_id,
-            //                     &active_track_ids,
-            //                 );
-            //             } else {
-            //                 println!("No text tracks found.");
-            //             }
-            //         }
-            //     }
-            // }
-            KeyCode::Up => {
-                // Volume up
-                println!("Volume up...");
-                if let Ok(receiver_status) = device.receiver.get_status() {
-                    let current_volume = &receiver_status.volume;
-                    let new_level = (current_volume.level.unwrap_or(0.0) + 0.05).min(1.0);
-                    let _ = device
-                        .receiver
-                        .set_volume(rust_cast::channels::receiver::Volume {
-                            level: Some(new_level),
-                            muted: current_volume.muted,
-                        });
-                }
-            }
-            KeyCode::Down => {
-                // Volume down
-                println!("Down...");
-                if let Ok(receiver_status) = device.receiver.get_status() {
-                    let current_volume = &receiver_status.volume;
-                    let new_level = (current_volume.level.unwrap_or(0.0) - 0.05).max(0.0);
-                    let _ = device
-                        .receiver
-                        .set_volume(rust_cast::channels::receiver::Volume {
-                            level: Some(new_level),
-                            muted: current_volume.muted,
-                        });
-                }
-            }
-            KeyCode::Left => {
-                // Seek backward
-                println!("Seeking backward...");
-                if let Ok(status) = device.media.get_status(&transport_id, None) {
-                    if let Some(media_status) = status.entries.first() {
-                        let current_time = media_status.current_time.unwrap_or(0.0);
-                        let new_time = (current_time - 10.0).max(0.0); // Seek back 10 seconds
-                        let _ = device.media.seek(
+                    Some(command @ (Command::Previous | Command::Next)) => {
+                        // Previous/next item in the queue; previous starts the
+                        // current item over once it is a few seconds in
+                        let action = if command == Command::Next {
+                            engine.skip(&mut context.queue, true)
+                        } else {
+                            engine.previous(&mut context.queue, time)
+                        };
+                        if action.is_none() {
+                            println!("No more items in the queue.");
+                        }
+                        action
+                    }
+                    Some(Command::CycleRepeat) => {
+                        // Cycle repeat: off, all, one
+                        let mode = engine.cycle_repeat(&mut context.queue);
+                        if let (Some(cast_queue), Some(status)) = (&mut cast_queue, &status) {
+                            if let Err(e) =
+                                cast_queue.set_repeat(&messenger, status.media_session_id, mode)
+                            {
+                                eprintln!("Error updating repeat: {e}");
+                            }
+                        }
+                        None
+                    }
+                    Some(Command::ToggleStopAfterCurrent) => {
+                        engine.toggle_stop_after_current();
+                        None
+                    }
+                    Some(Command::Prompt(PromptKind::Jump)) => {
+                        prompt = Some(Prompt::new(
+                            PromptKind::Jump,
+                            &format!("Jump to item (1-{}): ", context.queue.len()),
+                        ));
+                        None
+                    }
+                    Some(Command::Prompt(PromptKind::Seek)) => {
+                        prompt = Some(Prompt::new(PromptKind::Seek, "Go to time: "));
+                        None
+                    }
+                    Some(Command::Quit) => {
+                        println!("Quit (q)");
+                        if context.save_playlist.is_some() {
+                            context.save_queue();
+                        }
+                        if let Some(resume) = &mut context.resume {
+                            if let Err(e) = resume.save() {
+                                eprintln!("Error saving playback positions: {e}");
+                            }
+                        }
+                        break;
+                    }
+                    Some(command) => {
+                        run_command(
+                            &device,
                             &transport_id,
-                            media_status.media_session_id,
-                            Some(new_time),
-                            None,
+                            &messenger,
+                            &mut context,
+                            status.as_ref(),
+                            command,
                         );
+                        None
                     }
+                    None => None,
                 }
             }
-            KeyCode::Right => {
-                // Seek forward
-                println!("Seeking forward...");
-                if let Ok(status) = device.media.get_status(&transport_id, None) {
-                    if let Some(media_status) = status.entries.first() {
-                        let current_time = media_status.current_time.unwrap_or(0.0);
-                        let media_duration = media_status
-                            .media
-                            .as_ref()
-                            .and_then(|m| m.duration)
-                            .unwrap_or(current_time); // Use current_time if duration is not available
-                        let new_time = (current_time + 10.0).min(media_duration); // Cap at media_duration
-                        let _ = device.media.seek(
-                            &transport_id,
-                            media_status.media_session_id,
-                            Some(new_time),
-                            None,
-                        );
+            PlayerEvent::Entered(PromptKind::Jump, ref input) => {
+                let position = input
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|number| (1..=context.queue.len()).contains(number))
+                    .map(|number| number - 1);
+                match (position, &mut cast_queue) {
+                    (None, _) => {
+                        println!("No item {}", input.trim());
+                        None
+                    }
+                    (Some(position), Some(cast_queue)) => {
+                        if let Some(media_status) = media_status(&device, &transport_id) {
+                            if let Err(e) = cast_queue.jump_to(
+                                &messenger,
+                                &session_id,
+                                media_status.media_session_id,
+                                &context,
+                                position,
+                                engine.repeat_mode(&context.queue),
+                            ) {
+                                eprintln!("Error switching items: {e}");
+                            }
+                        }
+                        None
+                    }
+                    (Some(position), None) => engine.jump(&mut context.queue, position),
+                }
+            }
+            PlayerEvent::Entered(PromptKind::Seek, ref input) => {
+                match timecode::parse_time(input) {
+                    Err(e) => println!("{e}"),
+                    Ok(time) => {
+                        if let Some(status) = media_status(&device, &transport_id) {
+                            if !go_to(&device, &transport_id, &context.queue, &status, time) {
+                                println!("The duration isn't known yet.");
+                            }
+                        }
+                    }
+                }
+                None
+            }
+            PlayerEvent::LoopEnd => {
+                if let Some(status) = media_status(&device, &transport_id) {
+                    let time = status.current_time.map(f64::from);
+                    if let (true, Some(ab_loop)) = (loop_end_due(&context, time), &context.ab_loop)
+                    {
+                        loop_back(&device, &transport_id, ab_loop, &context.queue, &status);
+                    }
+                }
+                None
+            }
+            PlayerEvent::Tick => {
+                // Nothing else keeps the messenger's connection alive between
+                // key presses.
+                let _ = messenger.answer_heartbeats();
+                if context.loader.is_none() {
+                    continue;
+                }
+                let Ok(status) = device.media.get_status(&transport_id, None) else {
+                    continue;
+                };
+                let entry = status.entries.first();
+                let plan = plan_tick(&mut context, &mut engine, cast_queue.as_mut(), entry);
+                if let (true, Some(status), Some(ab_loop)) =
+                    (plan.loop_back, entry, &context.ab_loop)
+                {
+                    loop_back(&device, &transport_id, ab_loop, &context.queue, status);
+                    continue;
+                }
+                if let Some(left) = plan.loop_timer {
+                    if let Some(timer) = loop_timer.take() {
+                        timer.abort();
+                    }
+                    let loop_tx = loop_tx.clone();
+                    loop_timer = Some(tokio::spawn(async move {
+                        tokio::time::sleep(Duration::from_secs_f64(left)).await;
+                        let _ = loop_tx.send(PlayerEvent::LoopEnd);
+                    }));
+                }
+                if let (Some(time), Some(status)) = (plan.seek, entry) {
+                    let _ = device.media.seek(
+                        &transport_id,
+                        status.media_session_id,
+                        Some(time as f32),
+                        None,
+                    );
+                }
+                if let (Some(item_id), Some(status)) = (plan.return_to_item, entry) {
+                    if let Err(e) = messenger.queue_jump_to_item(status.media_session_id, item_id) {
+                        eprintln!("Error switching items: {e}");
+                    }
+                }
+                if let (true, Some(cast_queue), Some(status)) = (plan.fill, &mut cast_queue, entry)
+                {
+                    if let Err(e) =
+                        cast_queue.fill(&messenger, status.media_session_id, &mut context)
+                    {
+                        eprintln!("Error queueing the next items: {e}");
                     }
                 }
+                plan.action
+            }
+        };
+        for queue_event in engine.take_events() {
+            render(queue_event, &context.queue);
+        }
+        match action {
+            Some(Action::Play { playing }) => {
+                if let Err(e) = play_current(
+                    &device,
+                    &transport_id,
+                    &messenger,
+                    &session_id,
+                    &mut context,
+                    playing.as_ref(),
+                ) {
+                    eprintln!("Error loading item: {e}");
+                }
             }
-            // KeyCode::Char('p') => {
-            //     // Previous item in playlist
-            //     println!("Previous item in playlist...");
-            //     if let Ok(status) = device.media.get_status(&transport_id, None) {
-            //         if let Some(media_status) = status.entries.first() {
-            //             if let Some(current_item_id) = media_status.current_item_id {
-            //                 if let Some(items) = &media_status.items {
-            //                     if let Some(current_index) = items.iter().position(|item| item.item_id == current_item_id) {
-            //                         if current_index > 0 {
-            //                             let previous_item_id = items[current_index - 1].item_id;
-            //                             let _ = device.media.queue_update(
-            //                                 &transport_id,
-            //                                 media_status.media_session_id,
-            //                                 Some(previous_item_id),
-            //                                 None,
-            //                                 None,
-            //                                 None,
-            //                             );
-            //                         } else {
-            //                             println!("Already at the beginning of the playlist.");
-            //                         }
-            //                     }
-            //                 }
-            //             }
-            //         }
-            //     }
-            // }
-            // KeyCode::Char('n') => {
-            //     // Next item in playlist
-            //     println!("Next item in playlist...");
-            //     if let Ok(status) = device.media.get_status(&transport_id, None) {
-            //         if let Some(media_status) = status.entries.first() {
-            //             if let Some(current_item_id) = media_status.current_item_id {
-            //                 if let Some(items) = &media_status.items {
-            //                     if let Some(current_index) = items.iter().position(|item| item.item_id == current_item_id) {
-            //                         if current_index < items.len() - 1 {
-            //                             let next_item_id = items[current_index + 1].item_id;
-            //                             let _ = device.media.queue_update(
-            //                                 &transport_id,
-            //                                 media_status.media_session_id,
-            //                                 Some(next_item_id),
-            //                                 None,
-            //                                 None,
-            //                                 None,
-            //                             );
-            //                         } else {
-            //                             println!("Already at the end of the playlist.");
-            //                         }
-            //                     }
-            //                 }
-            //             }
-            //         }
-            //     }
-            // }
-            KeyCode::Char('s') => {
-                // Stop playback
-                println!("Stopping playback...");
+            Some(Action::Restart) => {
+                if let Some(media_status) = media_status(&device, &transport_id) {
+                    let start = context
+                        .queue
+                        .current()
+                        .and_then(|entry| entry.start_secs)
+                        .unwrap_or_default();
+                    let _ = device.media.seek(
+                        &transport_id,
+                        media_status.media_session_id,
+                        Some(start as f32),
+                        None,
+                    );
+                }
+            }
+            Some(Action::Stop) => {
                 if let Ok(status) = device.media.get_status(&transport_id, None) {
                     if let Some(media_status) = status.entries.first() {
                         let _ = device
@@ -222,15 +1047,301 @@ pub async fn handle_player_controls(
                     }
                 }
             }
-            KeyCode::Char('q') => {
-                // Quit
-                println!("Quit (q)");
-                break;
-            }
-            _ => {}
+            None => {}
         }
     }
 
     disable_raw_mode()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::QueueEntry;
+    use rust_cast::channels::media::{IdleReason, Media, StreamType};
+
+    fn context(entries: Vec<QueueEntry>) -> PlayerContext {
+        PlayerContext {
+            queue: Queue::new(entries, false, None),
+            ..Default::default()
+        }
+    }
+
+    fn remote(name: &str) -> QueueEntry {
+        QueueEntry::new(MediaSource::Remote(name.to_string()))
+    }
+
+    /// A receiver playing 300 s of media at `time`.
+    fn playing(time: f64) -> StatusEntry {
+        StatusEntry {
+            media_session_id: 1,
+            media: Some(Media {
+                content_id: "a".to_string(),
+                stream_type: StreamType::Buffered,
+                content_type: "video/mp4".to_string(),
+                metadata: None,
+                duration: Some(300.0),
+            }),
+            playback_rate: 1.0,
+            player_state: PlayerState::Playing,
+            current_item_id: None,
+            loading_item_id: None,
+            preloaded_item_id: None,
+            supported_media_commands: 0,
+            idle_reason: None,
+            extended_status: None,
+            current_time: Some(time as f32),
+        }
+    }
+
+    fn finished() -> StatusEntry {
+        StatusEntry {
+            player_state: PlayerState::Idle,
+            idle_reason: Some(IdleReason::Finished),
+            current_time: None,
+            ..playing(0.0)
+        }
+    }
+
+    fn ab_loop(index: usize, a: f64, b: f64) -> Option<AbLoop> {
+        let mut ab_loop = AbLoop::set_a(None, index, a);
+        assert!(ab_loop.set_b(index, b));
+        Some(ab_loop)
+    }
+
+    #[test]
+    fn test_passing_b_seeks_to_a() {
+        let mut context = context(vec![remote("a"), remote("b")]);
+        context.ab_loop = ab_loop(0, 60.0, 90.0);
+        let mut engine = QueueEngine::default();
+
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&playing(70.0)));
+        assert_eq!(plan, TickPlan::default());
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&playing(91.0)));
+        assert!(plan.loop_back);
+        assert_eq!(plan.action, None);
+
+        // B coming before the next poll is timed instead.
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&playing(89.5)));
+        assert!(!plan.loop_back);
+        assert_eq!(plan.loop_timer, Some(0.5));
+        let paused = StatusEntry {
+            player_state: PlayerState::Paused,
+            ..playing(89.5)
+        };
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&paused));
+        assert_eq!(plan.loop_timer, None);
+
+        assert!(loop_end_due(&context, Some(89.8)));
+        assert!(!loop_end_due(&context, Some(80.0)));
+        assert!(!loop_end_due(&context, None));
+    }
+
+    #[test]
+    fn test_loop_holds_the_queue() {
+        let mut trimmed = remote("a");
+        trimmed.trim(Some(30.0), Some(120.0));
+        let mut context = context(vec![trimmed, remote("b")]);
+        context.ab_loop = ab_loop(0, 60.0, 100.0);
+        let mut engine = QueueEngine::default();
+        plan_tick(&mut context, &mut engine, None, Some(&playing(65.0)));
+
+        // Playback ran to the end anyway: it starts over at A, not on the
+        // next entry.
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&finished()));
+        assert_eq!(plan.action, Some(Action::Play { playing: None }));
+        assert_eq!(context.queue.position(), 0);
+        assert_eq!(context.pending_seek, Some(TimeSpec::Secs(30.0)));
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&playing(30.0)));
+        assert_eq!(plan.seek, Some(60.0));
+        assert_eq!(context.pending_seek, None);
+
+        // Without B nothing holds it.
+        context.ab_loop = Some(AbLoop::set_a(None, 0, 60.0));
+        plan_tick(&mut context, &mut engine, None, Some(&playing(110.0)));
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&finished()));
+        assert!(matches!(plan.action, Some(Action::Play { .. })));
+        assert_eq!(context.queue.position(), 1);
+        // And the loop went with its entry.
+        plan_tick(&mut context, &mut engine, None, Some(&playing(0.0)));
+        assert_eq!(context.ab_loop, None);
+    }
+
+    #[test]
+    fn test_loop_brings_the_receiver_queue_back() {
+        let mut context = context(vec![remote("a"), remote("b")]);
+        context.ab_loop = ab_loop(0, 60.0, 90.0);
+        let mut cast_queue = CastQueue::with_item_ids([(0, 7), (1, 8)]);
+        let mut engine = QueueEngine::default();
+        let on_item = |item_id: u16, time: f64| StatusEntry {
+            current_item_id: Some(item_id),
+            ..playing(time)
+        };
+
+        let plan = plan_tick(
+            &mut context,
+            &mut engine,
+            Some(&mut cast_queue),
+            Some(&on_item(8, 0.5)),
+        );
+        assert_eq!(plan.return_to_item, Some(7));
+        assert_eq!(context.queue.position(), 0);
+        let plan = plan_tick(
+            &mut context,
+            &mut engine,
+            Some(&mut cast_queue),
+            Some(&on_item(7, 0.0)),
+        );
+        assert!(plan.loop_back);
+        let plan = plan_tick(
+            &mut context,
+            &mut engine,
+            Some(&mut cast_queue),
+            Some(&on_item(7, 61.0)),
+        );
+        assert_eq!(plan, TickPlan::default());
+
+        // Without a loop the queue follows the receiver.
+        context.ab_loop = None;
+        let plan = plan_tick(
+            &mut context,
+            &mut engine,
+            Some(&mut cast_queue),
+            Some(&on_item(8, 0.5)),
+        );
+        assert!(plan.fill);
+        assert_eq!(context.queue.position(), 1);
+    }
+
+    #[test]
+    fn test_seek_past_end_ends_the_segment() {
+        // `--end 90`, then a seek well past it.
+        let mut segment = remote("a");
+        segment.trim(None, Some(90.0));
+        let mut context = context(vec![segment, remote("b")]);
+        let mut engine = QueueEngine::default();
+
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&playing(60.0)));
+        assert_eq!(plan.action, None);
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&playing(200.0)));
+        assert!(matches!(plan.action, Some(Action::Play { .. })));
+        assert_eq!(context.queue.position(), 1);
+    }
+
+    #[test]
+    fn test_pending_seek_waits_for_the_duration() {
+        let mut context = context(vec![remote("a")]);
+        context.pending_seek = Some(TimeSpec::Percent(50.0));
+        let mut engine = QueueEngine::default();
+        let unknown = StatusEntry {
+            media: None,
+            ..playing(0.0)
+        };
+
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&unknown));
+        assert_eq!(plan.seek, None);
+        let plan = plan_tick(&mut context, &mut engine, None, Some(&playing(0.0)));
+        assert_eq!(plan.seek, Some(150.0));
+        assert_eq!(context.pending_seek, None);
+    }
+
+    fn track(id: u32) -> SubtitleTrack {
+        SubtitleTrack {
+            id,
+            name: None,
+            language: None,
+            cues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_loop_keys() {
+        let mut context = context(vec![remote("a")]);
+        assert_eq!(on_key(&mut context, KeyCode::Char('a'), None), None);
+        assert_eq!(
+            on_key(&mut context, KeyCode::Char('b'), Some(90.0)),
+            Some(Command::Say("Set A first with a; B has to come after it."))
+        );
+
+        assert_eq!(
+            on_key(&mut context, KeyCode::Char('a'), Some(60.0)),
+            Some(Command::ShowLoop)
+        );
+        assert_eq!(
+            on_key(&mut context, KeyCode::Char('b'), Some(50.0)),
+            Some(Command::Say("Set A first with a; B has to come after it."))
+        );
+        assert_eq!(
+            on_key(&mut context, KeyCode::Char('b'), Some(90.0)),
+            Some(Command::ShowLoop)
+        );
+        assert_eq!(context.ab_loop, ab_loop(0, 60.0, 90.0));
+
+        assert_eq!(
+            on_key(&mut context, KeyCode::Char('l'), None),
+            Some(Command::Say("Loop cleared"))
+        );
+        assert_eq!(context.ab_loop, None);
+        assert_eq!(on_key(&mut context, KeyCode::Char('l'), None), None);
+    }
+
+    #[test]
+    fn test_subtitle_keys() {
+        let mut context = context(vec![remote("a")]);
+        for key in ['t', '[', ']'] {
+            assert_eq!(
+                on_key(&mut context, KeyCode::Char(key), None),
+                Some(Command::Say(NO_TEXT_TRACKS))
+            );
+        }
+        assert_eq!(context.subtitle_offset_ms, 0);
+
+        context.subtitle_tracks = vec![track(1), track(2)];
+        assert_eq!(
+            on_key(&mut context, KeyCode::Char('t'), None),
+            Some(Command::SetSubtitles(Some(1)))
+        );
+        context.active_subtitle = Some(2);
+        assert_eq!(
+            on_key(&mut context, KeyCode::Char('t'), None),
+            Some(Command::SetSubtitles(None))
+        );
+
+        assert_eq!(
+            on_key(&mut context, KeyCode::Char('['), None),
+            Some(Command::ReloadSubtitles)
+        );
+        assert_eq!(context.subtitle_offset_ms, -SUBTITLE_OFFSET_STEP_MS);
+        assert!(context
+            .resources
+            .read()
+            .unwrap()
+            .contains_key(&track(1).path(0)));
+
+        assert_eq!(
+            on_key(&mut context, KeyCode::Char('+'), None),
+            Some(Command::UpdateStyle)
+        );
+        assert_ne!(context.subtitle_style, TextTrackStyle::default());
+    }
+
+    #[test]
+    fn test_queue_and_receiver_keys() {
+        let mut context = context(vec![remote("a")]);
+        let keys = [
+            (KeyCode::Char(' '), Some(Command::TogglePause)),
+            (KeyCode::Up, Some(Command::Volume(VOLUME_STEP))),
+            (KeyCode::Left, Some(Command::SeekBy(-SEEK_STEP_SECS))),
+            (KeyCode::Char('p'), Some(Command::Previous)),
+            (KeyCode::Char('n'), Some(Command::Next)),
+            (KeyCode::Char('r'), Some(Command::CycleRepeat)),
+            (KeyCode::Char('j'), Some(Command::Prompt(PromptKind::Jump))),
+            (KeyCode::Char('q'), Some(Command::Quit)),
+            (KeyCode::Char('z'), None),
+        ];
+        for (key, command) in keys {
+            assert_eq!(on_key(&mut context, key, Some(10.0)), command, "{key:?}");
+        }
+    }
+}
@@ -0,0 +1,687 @@
+use crate::queue::{MediaSource, Queue};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Component, Path, PathBuf};
+
+/// Characters escaped when a relative path is written as an XSPF location.
+const URI_PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// A playlist entry with whatever metadata the playlist carries.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlaylistEntry {
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration_secs: Option<f64>,
+    pub image: Option<String>,
+    /// Where to start and stop playing within the media, as VLC's
+    /// `start-time` and `stop-time` options give them.
+    pub start_secs: Option<f64>,
+    pub end_secs: Option<f64>,
+}
+
+impl PlaylistEntry {
+    /// The part of the media the entry plays, as a start and a length, if
+    /// it doesn't play all of it.
+    pub fn segment(&self) -> Option<(f64, Option<f64>)> {
+        if self.start_secs.is_none() && self.end_secs.is_none() {
+            return None;
+        }
+        let start = self.start_secs.unwrap_or_default();
+        let length = self
+            .end_secs
+            .map(|end| end - start)
+            .filter(|length| *length > 0.0);
+        Some((start, length))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "m3u" | "m3u8" => Some(PlaylistFormat::M3u),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse_playlist(content: &str, format: PlaylistFormat) -> anyhow::Result<Vec<PlaylistEntry>> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    match format {
+        PlaylistFormat::M3u => Ok(parse_m3u(content)),
+        PlaylistFormat::Pls => Ok(parse_pls(content)),
+        PlaylistFormat::Xspf => parse_xspf(content),
+    }
+}
+
+/// Splits on `\n`, `\r\n` and lone `\r` line endings.
+fn lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+}
+
+/// Durations of zero or less mean "unknown" in M3U and PLS.
+fn parse_duration(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|duration| *duration > 0.0)
+}
+
+/// Offsets into the media are seconds, from zero.
+fn parse_offset(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+}
+
+/// Applies a VLC `start-time=<secs>` or `stop-time=<secs>` option.
+fn apply_vlc_option(entry: &mut PlaylistEntry, option: &str) {
+    match option.trim().split_once('=') {
+        Some(("start-time", value)) => entry.start_secs = parse_offset(value),
+        Some(("stop-time", value)) => entry.end_secs = parse_offset(value),
+        _ => {}
+    }
+}
+
+/// Parses plain and extended M3U. `#EXTINF:<duration> [attributes],<title>`
+/// and `#EXTVLCOPT:start-time=` / `stop-time=` apply to the next entry; other
+/// comments and directives are skipped.
+pub fn parse_m3u(content: &str) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut pending = PlaylistEntry::default();
+
+    for line in lines(content) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            pending = PlaylistEntry {
+                start_secs: pending.start_secs,
+                end_secs: pending.end_secs,
+                ..parse_extinf(info)
+            };
+        } else if let Some(option) = line.strip_prefix("#EXTVLCOPT:") {
+            apply_vlc_option(&mut pending, option);
+        } else if !line.starts_with('#') {
+            entries.push(PlaylistEntry {
+                location: line.to_string(),
+                ..std::mem::take(&mut pending)
+            });
+        }
+    }
+    entries
+}
+
+fn parse_extinf(info: &str) -> PlaylistEntry {
+    // The title follows the first comma outside quoted attribute values.
+    let mut in_quotes = false;
+    let split = info.char_indices().find(|&(_, c)| {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        c == ',' && !in_quotes
+    });
+    let (head, title) = match split {
+        Some((index, _)) => (&info[..index], info[index + 1..].trim()),
+        None => (info, ""),
+    };
+
+    let duration = head.split_whitespace().next().unwrap_or_default();
+    let mut entry = PlaylistEntry {
+        duration_secs: parse_duration(duration),
+        image: extinf_attribute(head, "tvg-logo"),
+        ..Default::default()
+    };
+    if !title.is_empty() {
+        // "Artist - Title" is the common convention.
+        match title.split_once(" - ") {
+            Some((artist, title)) => {
+                entry.artist = Some(artist.trim().to_string());
+                entry.title = Some(title.trim().to_string());
+            }
+            None => entry.title = Some(title.to_string()),
+        }
+    }
+    entry
+}
+
+fn extinf_attribute(head: &str, name: &str) -> Option<String> {
+    let start = head.find(&format!("{name}=\""))? + name.len() + 2;
+    let end = head[start..].find('"')? + start;
+    Some(head[start..end].to_string()).filter(|value| !value.is_empty())
+}
+
+/// Parses a PLS playlist, keeping `FileN` order by `N` and attaching the
+/// matching `TitleN` and `LengthN`.
+pub fn parse_pls(content: &str) -> Vec<PlaylistEntry> {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+
+    for line in lines(content) {
+        if line.starts_with(['[', ';', '#']) {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        let Some((field, number)) = ["file", "title", "length"].iter().find_map(|field| {
+            let number = key.strip_prefix(field)?.parse::<u32>().ok()?;
+            Some((*field, number))
+        }) else {
+            continue;
+        };
+
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.location = value.to_string(),
+            "title" if !value.is_empty() => entry.title = Some(value.to_string()),
+            "length" => entry.duration_secs = parse_duration(value),
+            _ => {}
+        }
+    }
+
+    entries
+        .into_values()
+        .filter(|entry| !entry.location.is_empty())
+        .collect()
+}
+
+/// Parses XSPF `<track>` elements. Relative locations are percent-decoded so
+/// they can be resolved as paths; absolute URIs are kept as they are.
+pub fn parse_xspf(content: &str) -> anyhow::Result<Vec<PlaylistEntry>> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut entries = Vec::new();
+    let mut track: Option<PlaylistEntry> = None;
+    let mut element = String::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => {
+                element = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                if element == "track" {
+                    track = Some(PlaylistEntry::default());
+                }
+            }
+            Event::End(end) => {
+                if end.local_name().as_ref() == b"track" {
+                    if let Some(track) = track.take().filter(|track| !track.location.is_empty()) {
+                        entries.push(track);
+                    }
+                }
+                element.clear();
+            }
+            Event::Text(text) => {
+                if let Some(track) = track.as_mut() {
+                    apply_xspf_field(track, &element, &text.unescape()?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(track) = track.as_mut() {
+                    apply_xspf_field(track, &element, &String::from_utf8_lossy(&data));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+fn apply_xspf_field(track: &mut PlaylistEntry, element: &str, value: &str) {
+    let value = value.trim();
+    match element {
+        // Only the first location is used; later ones are alternatives.
+        "location" if track.location.is_empty() => {
+            track.location = if value.contains("://") {
+                value.to_string()
+            } else {
+                percent_decode_str(value).decode_utf8_lossy().to_string()
+            };
+        }
+        "title" => track.title = Some(value.to_string()),
+        "creator" => track.artist = Some(value.to_string()),
+        "image" => track.image = Some(value.to_string()),
+        // From VLC's extension, `<vlc:option>start-time=30</vlc:option>`.
+        "option" => apply_vlc_option(track, value),
+        "duration" => {
+            track.duration_secs = value
+                .parse::<f64>()
+                .ok()
+                .filter(|ms| *ms > 0.0)
+                .map(|ms| ms / 1000.0);
+        }
+        _ => {}
+    }
+}
+
+/// Serialises entries in the given format, the inverse of [`parse_playlist`].
+pub fn write_playlist(entries: &[PlaylistEntry], format: PlaylistFormat) -> String {
+    match format {
+        PlaylistFormat::M3u => write_m3u(entries),
+        PlaylistFormat::Pls => write_pls(entries),
+        PlaylistFormat::Xspf => write_xspf(entries),
+    }
+}
+
+/// The "Artist - Title" form M3U and PLS titles use.
+fn display_title(entry: &PlaylistEntry) -> Option<String> {
+    match (&entry.artist, &entry.title) {
+        (Some(artist), Some(title)) => Some(format!("{artist} - {title}")),
+        (_, title) => title.clone(),
+    }
+}
+
+/// The VLC options that carry the entry's start and stop times.
+fn vlc_options(entry: &PlaylistEntry) -> Vec<(&'static str, f64)> {
+    [
+        ("start-time", entry.start_secs),
+        ("stop-time", entry.end_secs),
+    ]
+    .into_iter()
+    .filter_map(|(option, value)| Some((option, value?)))
+    .collect()
+}
+
+fn write_m3u(entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for entry in entries {
+        let title = display_title(entry).unwrap_or_default();
+        if !title.is_empty() || entry.duration_secs.is_some() || entry.image.is_some() {
+            let _ = write!(out, "#EXTINF:{}", entry.duration_secs.unwrap_or(-1.0));
+            if let Some(image) = &entry.image {
+                let _ = write!(out, " tvg-logo=\"{image}\"");
+            }
+            let _ = writeln!(out, ",{title}");
+        }
+        for (option, value) in vlc_options(entry) {
+            let _ = writeln!(out, "#EXTVLCOPT:{option}={value}");
+        }
+        let _ = writeln!(out, "{}", entry.location);
+    }
+    out
+}
+
+fn write_pls(entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from("[playlist]\n");
+    for (index, entry) in entries.iter().enumerate() {
+        let number = index + 1;
+        let _ = writeln!(out, "File{number}={}", entry.location);
+        if let Some(title) = display_title(entry) {
+            let _ = writeln!(out, "Title{number}={title}");
+        }
+        let _ = writeln!(
+            out,
+            "Length{number}={}",
+            entry.duration_secs.unwrap_or(-1.0)
+        );
+    }
+    let _ = writeln!(out, "NumberOfEntries={}\nVersion=2", entries.len());
+    out
+}
+
+fn write_xspf(entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <trackList>\n",
+    );
+    for entry in entries {
+        let location = if entry.location.contains("://") {
+            entry.location.clone()
+        } else if Path::new(&entry.location).is_absolute() {
+            url::Url::from_file_path(&entry.location)
+                .map(String::from)
+                .unwrap_or_else(|_| entry.location.clone())
+        } else {
+            utf8_percent_encode(&entry.location, URI_PATH).to_string()
+        };
+        out.push_str("    <track>\n");
+        let _ = writeln!(
+            out,
+            "      <location>{}</location>",
+            escape(location.as_str())
+        );
+        let fields = [
+            ("title", entry.title.as_deref()),
+            ("creator", entry.artist.as_deref()),
+            ("image", entry.image.as_deref()),
+        ];
+        for (element, value) in fields {
+            if let Some(value) = value {
+                let _ = writeln!(out, "      <{element}>{}</{element}>", escape(value));
+            }
+        }
+        if let Some(duration) = entry.duration_secs {
+            let _ = writeln!(
+                out,
+                "      <duration>{}</duration>",
+                (duration * 1000.0).round() as u64
+            );
+        }
+        let options = vlc_options(entry);
+        if !options.is_empty() {
+            out.push_str(
+                "      <extension application=\"http://www.videolan.org/vlc/playlist/0\" \
+                 xmlns:vlc=\"http://www.videolan.org/vlc/playlist/ns/0/\">\n",
+            );
+            for (option, value) in options {
+                let _ = writeln!(out, "        <vlc:option>{option}={value}</vlc:option>");
+            }
+            out.push_str("      </extension>\n");
+        }
+        out.push_str("    </track>\n");
+    }
+    out.push_str("  </trackList>\n</playlist>\n");
+    out
+}
+
+/// `path` relative to the directory `base`. Both must be absolute.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    relative
+}
+
+/// Writes the queue, in play order, to a playlist whose format follows the
+/// file extension. Local files are written relative to the playlist's
+/// directory unless `absolute_paths` is set.
+pub fn save_queue(queue: &Queue, path: &Path, absolute_paths: bool) -> anyhow::Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let format = PlaylistFormat::from_extension(extension).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Can't save a playlist as {}: use .m3u8, .pls or .xspf",
+            path.display()
+        )
+    })?;
+    let path = std::path::absolute(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new("/"));
+
+    let mut entries = Vec::new();
+    for entry in queue.iter() {
+        let location = match &entry.source {
+            MediaSource::Remote(url) => url.clone(),
+            MediaSource::Local(media) => {
+                let media = std::path::absolute(media)?;
+                let media = if absolute_paths {
+                    media
+                } else {
+                    relative_path(&media, base_dir)
+                };
+                media.to_string_lossy().to_string()
+            }
+            // Torrent files only exist while the session runs.
+            MediaSource::Stream { .. } => continue,
+        };
+        entries.push(PlaylistEntry {
+            location,
+            title: entry.title.clone(),
+            artist: entry.artist.clone(),
+            duration_secs: entry.duration_secs,
+            image: entry.image.clone(),
+            start_secs: entry.start_secs,
+            end_secs: entry
+                .start_secs
+                .zip(entry.duration_secs)
+                .map(|(start, duration)| start + duration),
+        });
+    }
+    // PLS has no fields for them, and the whole files would play instead.
+    if format == PlaylistFormat::Pls && entries.iter().any(|entry| entry.segment().is_some()) {
+        anyhow::bail!(
+            "Can't save start and end times to {}: use .m3u8 or .xspf",
+            path.display()
+        );
+    }
+    std::fs::write(&path, write_playlist(&entries, format))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const M3U: &str = include_str!("../tests/fixtures/playlists/radio.m3u");
+    const PLS: &str = include_str!("../tests/fixtures/playlists/mixtape.pls");
+    const XSPF: &str = include_str!("../tests/fixtures/playlists/album.xspf");
+
+    #[test]
+    fn test_parse_m3u() {
+        let entries = parse_playlist(M3U, PlaylistFormat::M3u).unwrap();
+        let locations: Vec<&str> = entries.iter().map(|e| e.location.as_str()).collect();
+        assert_eq!(
+            locations,
+            [
+                "Music/Artist/01 Intro.mp3",
+                "http://radio.example.com/stream",
+                "C:\\Music\\Track.flac",
+                "plain.ogg",
+            ]
+        );
+
+        assert_eq!(entries[0].artist.as_deref(), Some("Artist"));
+        assert_eq!(entries[0].title.as_deref(), Some("Intro, Part 1"));
+        assert_eq!(entries[0].duration_secs, Some(215.0));
+        assert_eq!(entries[1].title.as_deref(), Some("Example Radio"));
+        assert_eq!(entries[1].duration_secs, None);
+        assert_eq!(
+            entries[1].image.as_deref(),
+            Some("http://radio.example.com/logo,small.png")
+        );
+        assert_eq!(entries[2].duration_secs, Some(61.5));
+        assert_eq!(
+            entries[3],
+            PlaylistEntry {
+                location: "plain.ogg".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_pls() {
+        let entries = parse_playlist(PLS, PlaylistFormat::Pls).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].location, "first.mp3");
+        assert_eq!(entries[0].title.as_deref(), Some("First = Best"));
+        assert_eq!(entries[0].duration_secs, Some(180.0));
+        assert_eq!(entries[1].location, "http://stream.example.com:8000/");
+        assert_eq!(entries[1].duration_secs, None);
+        assert_eq!(entries[2].location, "tenth.mp3");
+        assert_eq!(entries[2].title, None);
+    }
+
+    #[test]
+    fn test_parse_xspf() {
+        let entries = parse_playlist(XSPF, PlaylistFormat::Xspf).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].location, "Album/01 Café & Bar.flac");
+        assert_eq!(entries[0].title.as_deref(), Some("Café & Bar"));
+        assert_eq!(entries[0].artist.as_deref(), Some("Band"));
+        assert_eq!(entries[0].duration_secs, Some(242.5));
+        assert_eq!(
+            entries[0].image.as_deref(),
+            Some("http://example.com/cover.jpg")
+        );
+        assert_eq!(entries[1].location, "file:///music/Album/02%20Two.flac");
+        assert_eq!(entries[1].title.as_deref(), Some("Two <live>"));
+
+        assert!(parse_xspf("<playlist><trackList><track>").is_ok());
+        assert!(parse_xspf("<playlist></trackList>").is_err());
+    }
+
+    #[test]
+    fn test_write_playlist_round_trips() {
+        let entries = vec![
+            PlaylistEntry {
+                location: "Album/01 Café & Bar.flac".to_string(),
+                title: Some("Café & Bar".to_string()),
+                artist: Some("Band".to_string()),
+                duration_secs: Some(242.5),
+                image: Some("http://example.com/cover.jpg".to_string()),
+                ..Default::default()
+            },
+            PlaylistEntry {
+                location: "http://radio.example.com/stream".to_string(),
+                title: Some("Radio <live>".to_string()),
+                ..Default::default()
+            },
+            PlaylistEntry {
+                location: "/music/plain #1.ogg".to_string(),
+                ..Default::default()
+            },
+            PlaylistEntry {
+                location: "talk.mp3".to_string(),
+                start_secs: Some(30.0),
+                end_secs: Some(95.5),
+                ..Default::default()
+            },
+        ];
+        let written = write_playlist(&entries, PlaylistFormat::M3u);
+        assert_eq!(parse_m3u(&written), entries);
+
+        // PLS has no artist or image fields.
+        let written = write_playlist(&entries, PlaylistFormat::Pls);
+        let parsed = parse_pls(&written);
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[0].title.as_deref(), Some("Band - Café & Bar"));
+        assert_eq!(parsed[0].duration_secs, Some(242.5));
+        assert_eq!(parsed[2].location, "/music/plain #1.ogg");
+        assert!(written.ends_with("NumberOfEntries=4\nVersion=2\n"));
+
+        let written = write_playlist(&entries, PlaylistFormat::Xspf);
+        assert!(written.contains("<location>Album/01%20Caf"));
+        assert!(written.contains("<location>file:///music/plain%20%231.ogg</location>"));
+        let parsed = parse_xspf(&written).unwrap();
+        assert_eq!(parsed[..2], entries[..2]);
+        assert_eq!(parsed[2].location, "file:///music/plain%20%231.ogg");
+        assert_eq!(parsed[3], entries[3]);
+    }
+
+    #[test]
+    fn test_parse_segments() {
+        let entries = parse_m3u(
+            "#EXTM3U\n\
+             #EXTVLCOPT:start-time=90\n\
+             #EXTINF:3600,Lecture\n\
+             #EXTVLCOPT:stop-time=150.5\n\
+             lecture.mp4\n\
+             #EXTVLCOPT:stop-time=60\n\
+             intro.mp4\n\
+             #EXTVLCOPT:start-time=-5\n\
+             whole.mp4\n",
+        );
+        assert_eq!(entries[0].title.as_deref(), Some("Lecture"));
+        assert_eq!(entries[0].segment(), Some((90.0, Some(60.5))));
+        assert_eq!(entries[1].segment(), Some((0.0, Some(60.0))));
+        assert_eq!(entries[2].segment(), None);
+
+        let entries = parse_xspf(
+            "<playlist xmlns:vlc=\"http://www.videolan.org/vlc/playlist/ns/0/\"><trackList>\
+             <track><location>a.mp4</location>\
+             <extension application=\"http://www.videolan.org/vlc/playlist/0\">\
+             <vlc:option>start-time=12</vlc:option><vlc:option>network-caching=1000</vlc:option>\
+             </extension></track></trackList></playlist>",
+        )
+        .unwrap();
+        assert_eq!(entries[0].segment(), Some((12.0, None)));
+    }
+
+    #[test]
+    fn test_save_queue() {
+        use crate::queue::QueueEntry;
+
+        let dir = tempfile::tempdir().unwrap();
+        let song = dir.path().join("music").join("a song.mp3");
+        let mut entry = QueueEntry::new(MediaSource::Local(song.clone()));
+        entry.title = Some("A Song".to_string());
+        let queue = Queue::new(
+            vec![
+                entry,
+                QueueEntry::new(MediaSource::Remote("http://host/b.mp3".to_string())),
+            ],
+            false,
+            None,
+        );
+
+        let target = dir.path().join("lists").join("saved.m3u8");
+        std::fs::create_dir(target.parent().unwrap()).unwrap();
+        save_queue(&queue, &target, false).unwrap();
+        let saved = std::fs::read_to_string(&target).unwrap();
+        assert_eq!(
+            saved,
+            "#EXTM3U\n#EXTINF:-1,A Song\n../music/a song.mp3\nhttp://host/b.mp3\n"
+        );
+
+        let target = dir.path().join("saved.pls");
+        save_queue(&queue, &target, true).unwrap();
+        let saved = parse_pls(&std::fs::read_to_string(&target).unwrap());
+        assert_eq!(saved[0].location, song.to_string_lossy());
+
+        assert!(save_queue(&queue, &dir.path().join("saved.txt"), false).is_err());
+    }
+
+    #[test]
+    fn test_save_segments() {
+        use crate::queue::QueueEntry;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut entry = QueueEntry::new(MediaSource::Remote("http://host/talk.mp3".to_string()));
+        entry.trim(Some(30.0), Some(95.5));
+        let queue = Queue::new(vec![entry], false, None);
+
+        let target = dir.path().join("saved.pls");
+        assert!(save_queue(&queue, &target, false).is_err());
+        assert!(!target.exists());
+
+        let target = dir.path().join("saved.m3u8");
+        save_queue(&queue, &target, false).unwrap();
+        let saved = parse_m3u(&std::fs::read_to_string(&target).unwrap());
+        assert_eq!(saved[0].segment(), Some((30.0, Some(65.5))));
+    }
+
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(
+            PlaylistFormat::from_extension("M3U8"),
+            Some(PlaylistFormat::M3u)
+        );
+        assert_eq!(PlaylistFormat::from_extension("cue"), None);
+    }
+}
@@ -0,0 +1,268 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// A podcast feed, RSS 2.0 or Atom, with iTunes extensions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Feed {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub image: Option<String>,
+    pub episodes: Vec<Episode>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Episode {
+    pub title: Option<String>,
+    /// Publication date as `YYYY-MM-DD` when it could be parsed.
+    pub published: Option<String>,
+    pub duration_secs: Option<f64>,
+    /// The enclosure.
+    pub url: String,
+    pub content_type: Option<String>,
+    pub image: Option<String>,
+}
+
+impl Feed {
+    /// The `count` most recently published episodes, newest first.
+    pub fn latest(&self, count: usize) -> Vec<&Episode> {
+        let mut episodes: Vec<&Episode> = self.episodes.iter().collect();
+        episodes.sort_by(|a, b| b.published.cmp(&a.published));
+        episodes.truncate(count);
+        episodes
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Normalises RFC 2822 (`Tue, 05 Mar 2024 10:00:00 +0000`, RSS) and RFC 3339
+/// (`2024-03-05T10:00:00Z`, Atom) dates to `YYYY-MM-DD`. Anything else is
+/// kept as it is.
+fn parse_date(raw: &str) -> String {
+    let raw = raw.trim();
+    let bytes = raw.as_bytes();
+    if bytes.len() >= 10 && bytes[4] == b'-' && bytes[7] == b'-' {
+        return raw[..10].to_string();
+    }
+
+    let mut parts = raw.split_whitespace();
+    if raw.contains(',') {
+        parts.next();
+    }
+    let date = (|| {
+        let day: u32 = parts.next()?.parse().ok()?;
+        let month = parts.next()?.get(..3)?.to_lowercase();
+        let month = MONTHS.iter().position(|name| *name == month)? + 1;
+        let year: u32 = parts.next()?.parse().ok()?;
+        Some(format!("{year:04}-{month:02}-{day:02}"))
+    })();
+    date.unwrap_or_else(|| raw.to_string())
+}
+
+/// Parses `itunes:duration`: `HH:MM:SS`, `MM:SS` or plain seconds.
+fn parse_duration(raw: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in raw.trim().split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds).filter(|seconds| *seconds > 0.0)
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Option<String> {
+    let value = element
+        .try_get_attribute(name)
+        .ok()??
+        .unescape_value()
+        .ok()?;
+    Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/// Applies an element's attributes: enclosures and `itunes:image`.
+fn apply_attributes(element: &BytesStart, feed: &mut Feed, episode: Option<&mut Episode>) {
+    let name = element.name();
+    match (name.as_ref(), episode) {
+        (b"enclosure", Some(episode)) if episode.url.is_empty() => {
+            episode.url = attribute(element, "url").unwrap_or_default();
+            episode.content_type = attribute(element, "type");
+        }
+        (b"link", Some(episode))
+            if episode.url.is_empty()
+                && attribute(element, "rel").as_deref() == Some("enclosure") =>
+        {
+            episode.url = attribute(element, "href").unwrap_or_default();
+            episode.content_type = attribute(element, "type");
+        }
+        (b"itunes:image", Some(episode)) => episode.image = attribute(element, "href"),
+        (b"itunes:image", None) => {
+            // The iTunes artwork is usually larger than the RSS image.
+            feed.image = attribute(element, "href").or(feed.image.take());
+        }
+        _ => {}
+    }
+}
+
+/// Applies the text content of an element named `name` inside `parent`.
+fn apply_text(
+    name: &str,
+    parent: &str,
+    text: String,
+    feed: &mut Feed,
+    episode: Option<&mut Episode>,
+) {
+    if text.is_empty() {
+        return;
+    }
+    match episode {
+        Some(episode) if matches!(parent, "item" | "entry") => match name {
+            "title" => episode.title = Some(text),
+            "pubDate" | "published" => episode.published = Some(parse_date(&text)),
+            "updated" if episode.published.is_none() => episode.published = Some(parse_date(&text)),
+            "itunes:duration" => episode.duration_secs = parse_duration(&text),
+            _ => {}
+        },
+        Some(_) => {}
+        None => match (parent, name) {
+            ("channel" | "feed", "title") => feed.title = Some(text),
+            ("channel", "itunes:author") => feed.author = Some(text),
+            ("author", "name") if feed.author.is_none() => feed.author = Some(text),
+            ("image", "url") | ("feed", "logo" | "icon") if feed.image.is_none() => {
+                feed.image = Some(text)
+            }
+            _ => {}
+        },
+    }
+}
+
+/// Parses an RSS or Atom feed. Entries without an enclosure are skipped.
+pub fn parse_feed(content: &str) -> anyhow::Result<Feed> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+
+    let mut feed = Feed::default();
+    let mut episode: Option<Episode> = None;
+    let mut stack: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut saw_root = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.name().as_ref()).to_string();
+                if stack.is_empty() {
+                    saw_root = matches!(name.as_str(), "rss" | "feed" | "rdf:RDF");
+                }
+                if matches!(name.as_str(), "item" | "entry") {
+                    episode = Some(Episode::default());
+                }
+                apply_attributes(&element, &mut feed, episode.as_mut());
+                stack.push(name);
+                text.clear();
+            }
+            Event::Empty(element) => apply_attributes(&element, &mut feed, episode.as_mut()),
+            Event::Text(content) => text.push_str(&content.unescape()?),
+            Event::CData(data) => text.push_str(&String::from_utf8_lossy(&data)),
+            Event::End(_) => {
+                let name = stack.pop().unwrap_or_default();
+                let parent = stack.last().map(String::as_str).unwrap_or_default();
+                apply_text(
+                    &name,
+                    parent,
+                    std::mem::take(&mut text).trim().to_string(),
+                    &mut feed,
+                    episode.as_mut(),
+                );
+                if matches!(name.as_str(), "item" | "entry") {
+                    if let Some(episode) = episode.take().filter(|e| !e.url.is_empty()) {
+                        feed.episodes.push(episode);
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !saw_root {
+        return Err(anyhow::anyhow!("Not an RSS or Atom feed"));
+    }
+    Ok(feed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS: &str = include_str!("../tests/fixtures/podcasts/rss.xml");
+    const ATOM: &str = include_str!("../tests/fixtures/podcasts/atom.xml");
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("Tue, 05 Mar 2024 10:00:00 +0000"), "2024-03-05");
+        assert_eq!(parse_date("5 March 2024 10:00 GMT"), "2024-03-05");
+        assert_eq!(parse_date("2024-03-05T10:00:00Z"), "2024-03-05");
+        assert_eq!(parse_date("last week"), "last week");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1:02:03"), Some(3723.0));
+        assert_eq!(parse_duration("62:03"), Some(3723.0));
+        assert_eq!(parse_duration("3723"), Some(3723.0));
+        assert_eq!(parse_duration("0"), None);
+        assert_eq!(parse_duration("an hour"), None);
+    }
+
+    #[test]
+    fn test_parse_rss() {
+        let feed = parse_feed(RSS).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Rust & Friends"));
+        assert_eq!(feed.author.as_deref(), Some("Ferris"));
+        assert_eq!(
+            feed.image.as_deref(),
+            Some("https://example.com/artwork-3000.jpg")
+        );
+        assert_eq!(feed.episodes.len(), 3);
+
+        let first = &feed.episodes[0];
+        assert_eq!(first.title.as_deref(), Some("Episode 3: <Lifetimes>"));
+        assert_eq!(first.published.as_deref(), Some("2024-03-19"));
+        assert_eq!(first.duration_secs, Some(3723.0));
+        assert_eq!(first.url, "https://cdn.example.com/ep3.mp3?source=rss");
+        assert_eq!(first.content_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(first.image.as_deref(), Some("https://example.com/ep3.jpg"));
+        assert_eq!(feed.episodes[1].duration_secs, Some(1800.0));
+        assert_eq!(feed.episodes[2].duration_secs, Some(1500.0));
+        assert_eq!(feed.episodes[1].image, None);
+
+        let latest: Vec<Option<&str>> = feed
+            .latest(2)
+            .iter()
+            .map(|episode| episode.title.as_deref())
+            .collect();
+        assert_eq!(
+            latest,
+            [Some("Episode 3: <Lifetimes>"), Some("Episode 2: Traits")]
+        );
+    }
+
+    #[test]
+    fn test_parse_atom() {
+        let feed = parse_feed(ATOM).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Atom Cast"));
+        assert_eq!(feed.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(feed.image.as_deref(), Some("https://example.com/logo.png"));
+        assert_eq!(feed.episodes.len(), 1);
+        let episode = &feed.episodes[0];
+        assert_eq!(episode.title.as_deref(), Some("Pilot"));
+        assert_eq!(episode.published.as_deref(), Some("2023-11-02"));
+        assert_eq!(episode.url, "https://example.com/pilot.m4a");
+        assert_eq!(episode.content_type.as_deref(), Some("audio/mp4"));
+        assert_eq!(episode.duration_secs, Some(754.0));
+    }
+
+    #[test]
+    fn test_parse_feed_errors() {
+        assert!(parse_feed("<html><body>Not a feed</body></html>").is_err());
+        assert!(parse_feed("<rss><channel><item></channel></rss>").is_err());
+    }
+}
@@ -0,0 +1,628 @@
+use crate::server::StreamSource;
+use crate::shuffle::Shuffler;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where a queue entry's media lives.
+#[derive(Clone, Debug)]
+pub enum MediaSource {
+    Local(PathBuf),
+    Remote(String),
+    /// Media read while it is served, such as a file in a torrent. `name` is
+    /// its file name, used for the title and content type.
+    Stream {
+        name: String,
+        source: Arc<dyn StreamSource>,
+    },
+}
+
+impl PartialEq for MediaSource {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MediaSource::Local(a), MediaSource::Local(b)) => a == b,
+            (MediaSource::Remote(a), MediaSource::Remote(b)) => a == b,
+            (MediaSource::Stream { source: a, .. }, MediaSource::Stream { source: b, .. }) => {
+                Arc::ptr_eq(a, b)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl MediaSource {
+    pub fn display(&self) -> String {
+        match self {
+            MediaSource::Local(path) => path.display().to_string(),
+            MediaSource::Remote(url) => url.clone(),
+            MediaSource::Stream { name, .. } => name.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueueEntry {
+    pub source: MediaSource,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration_secs: Option<f64>,
+    /// Artwork URL from the playlist, used when the media has no cover.
+    pub image: Option<String>,
+    pub album: Option<String>,
+    /// MIME type from the playlist or feed, for URLs without an extension.
+    pub content_type: Option<String>,
+    /// Offset into the media where the entry starts, for tracks that share a
+    /// file (CUE sheets) and segments of a file. The entry then runs for
+    /// `duration_secs`, or to the end of the file.
+    pub start_secs: Option<f64>,
+}
+
+impl QueueEntry {
+    pub fn new(source: MediaSource) -> Self {
+        QueueEntry {
+            source,
+            title: None,
+            artist: None,
+            duration_secs: None,
+            image: None,
+            album: None,
+            content_type: None,
+            start_secs: None,
+        }
+    }
+
+    /// "Artist - Title (m:ss)", falling back to the file name or URL.
+    pub fn label(&self) -> String {
+        let mut label = match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => format!("{artist} - {title}"),
+            (None, Some(title)) => title.clone(),
+            _ => self.source.display(),
+        };
+        if let Some(duration) = self.duration_secs {
+            let seconds = duration.round() as u64;
+            label.push_str(&format!(" ({}:{:02})", seconds / 60, seconds % 60));
+        }
+        label
+    }
+
+    /// Limits the entry to the part of its media from `start` to `end`
+    /// seconds (`--start`/`--end`). Entries that already play only part of
+    /// their media keep it.
+    pub fn trim(&mut self, start: Option<f64>, end: Option<f64>) {
+        if self.start_secs.is_some() || (start.is_none() && end.is_none()) {
+            return;
+        }
+        let start = start.unwrap_or_default();
+        self.start_secs = Some(start);
+        self.duration_secs = end
+            .or(self.duration_secs)
+            .map(|end| end - start)
+            .filter(|length| *length > 0.0);
+    }
+
+    /// Whether `time` (seconds into the media) falls within this track.
+    /// Always false for entries that aren't tracks within a longer file.
+    pub fn contains(&self, time: f64) -> bool {
+        let Some(start) = self.start_secs else {
+            return false;
+        };
+        time >= start
+            && self
+                .duration_secs
+                .is_none_or(|duration| time < start + duration)
+    }
+
+    /// Whether `time` is just past the end of this track, where playback
+    /// runs into the next track of the same file rather than seeking there.
+    pub fn played_past_end(&self, time: f64) -> bool {
+        self.start_secs
+            .zip(self.duration_secs)
+            .is_some_and(|(start, duration)| {
+                let end = start + duration;
+                time >= end && time < end + TRACK_END_SLACK_SECS
+            })
+    }
+}
+
+/// What [`Queue::follow_time`] did with the receiver's playback position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackChange {
+    Unchanged,
+    /// The current entry is now the track being played.
+    Moved,
+    /// Playback ran into a track that doesn't follow in queue order, so the
+    /// new current entry has to be seeked to or loaded.
+    Load,
+    /// Playback ran past the last queued track.
+    Ended,
+}
+
+/// How far past a track's end the position may be and still count as having
+/// played into the next track rather than a seek.
+const TRACK_END_SLACK_SECS: f64 = 3.0;
+
+/// Resolves a playlist entry: http(s) URLs are kept, `file://` URIs are
+/// decoded and relative paths are taken relative to `base_dir`. Local files
+/// that don't exist are an error.
+pub fn resolve_entry(base_dir: &Path, raw: &str) -> anyhow::Result<MediaSource> {
+    let raw = raw.trim();
+    if raw.starts_with("http://") || raw.starts_with("https://") {
+        return Ok(MediaSource::Remote(raw.to_string()));
+    }
+
+    let path = if raw.starts_with("file://") {
+        url::Url::parse(raw)
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid file URI: {raw}"))?
+    } else {
+        base_dir.join(raw)
+    };
+    if !path.is_file() {
+        return Err(anyhow::anyhow!("File not found: {}", path.display()));
+    }
+    Ok(MediaSource::Local(path))
+}
+
+/// A play queue. `order` holds indexes into `entries` in play order, so
+/// shuffling never loses the original order.
+#[derive(Clone, Debug, Default)]
+pub struct Queue {
+    entries: Vec<QueueEntry>,
+    order: Vec<usize>,
+    position: usize,
+    loop_playback: bool,
+    shuffler: Option<Shuffler>,
+    /// The order drawn ahead of time for the next loop, see [`Queue::next_loop`].
+    next_order: Option<Vec<usize>>,
+}
+
+impl Queue {
+    pub fn new(
+        entries: Vec<QueueEntry>,
+        loop_playback: bool,
+        mut shuffler: Option<Shuffler>,
+    ) -> Self {
+        let order = match &mut shuffler {
+            Some(shuffler) => shuffler.order(&entries),
+            None => (0..entries.len()).collect(),
+        };
+        Queue {
+            entries,
+            order,
+            position: 0,
+            loop_playback,
+            shuffler,
+            next_order: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Entries in play order.
+    pub fn iter(&self) -> impl Iterator<Item = &QueueEntry> {
+        self.order.iter().map(|&index| &self.entries[index])
+    }
+
+    /// Entries in play order, each with its index in the original order,
+    /// which stays the same however the queue is shuffled.
+    pub fn indexed(&self) -> impl Iterator<Item = (usize, &QueueEntry)> {
+        self.order
+            .iter()
+            .map(|&index| (index, &self.entries[index]))
+    }
+
+    pub fn entry(&self, index: usize) -> Option<&QueueEntry> {
+        self.entries.get(index)
+    }
+
+    /// Whether the queue wraps around at either end.
+    pub fn loops(&self) -> bool {
+        self.loop_playback
+    }
+
+    pub fn set_loop(&mut self, loop_playback: bool) {
+        self.loop_playback = loop_playback;
+    }
+
+    /// The 0-based position in play order.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The current entry's index in the original order.
+    pub fn current_index(&self) -> Option<usize> {
+        self.order.get(self.position).copied()
+    }
+
+    pub fn current(&self) -> Option<&QueueEntry> {
+        self.order
+            .get(self.position)
+            .map(|&index| &self.entries[index])
+    }
+
+    /// Moves to the next entry, wrapping around when looping. A shuffled
+    /// queue is shuffled again for each loop. Returns `None` at the end of
+    /// the queue.
+    pub fn advance(&mut self) -> Option<&QueueEntry> {
+        if self.position + 1 < self.order.len() {
+            self.position += 1;
+        } else if self.loop_playback && !self.order.is_empty() {
+            self.reshuffle();
+            self.position = 0;
+        } else {
+            return None;
+        }
+        self.current()
+    }
+
+    /// Moves to the previous entry, wrapping around when looping.
+    pub fn previous(&mut self) -> Option<&QueueEntry> {
+        if self.position > 0 {
+            self.position -= 1;
+        } else if self.loop_playback && !self.order.is_empty() {
+            self.position = self.order.len() - 1;
+        } else {
+            return None;
+        }
+        self.current()
+    }
+
+    /// Up to `count` entries after the current one, with their positions.
+    pub fn upcoming(&self, count: usize) -> impl Iterator<Item = (usize, &QueueEntry)> {
+        self.iter().enumerate().skip(self.position + 1).take(count)
+    }
+
+    /// Makes `position` the current entry.
+    pub fn jump_to(&mut self, position: usize) -> Option<&QueueEntry> {
+        if position >= self.order.len() {
+            return None;
+        }
+        self.position = position;
+        self.current()
+    }
+
+    /// The play order of the next loop, as indexes in the original order. A
+    /// shuffled queue draws it now and keeps it for when the loop starts.
+    pub fn next_loop(&mut self) -> Vec<usize> {
+        if self.shuffler.is_none() {
+            return self.order.clone();
+        }
+        self.next_order
+            .get_or_insert_with(|| Self::draw_order(&mut self.shuffler, &self.entries, &self.order))
+            .clone()
+    }
+
+    /// Starts the next loop at its first entry, in the order given by
+    /// [`Queue::next_loop`].
+    pub fn start_next_loop(&mut self) -> Option<&QueueEntry> {
+        self.reshuffle();
+        self.jump_to(0)
+    }
+
+    fn reshuffle(&mut self) {
+        if let Some(order) = self.next_order.take() {
+            self.order = order;
+        } else if self.shuffler.is_some() {
+            self.order = Self::draw_order(&mut self.shuffler, &self.entries, &self.order);
+        }
+    }
+
+    /// Draws a new shuffled order, moving the entry that ends the current
+    /// loop away from the front so it doesn't play twice in a row.
+    fn draw_order(
+        shuffler: &mut Option<Shuffler>,
+        entries: &[QueueEntry],
+        current: &[usize],
+    ) -> Vec<usize> {
+        let Some(shuffler) = shuffler else {
+            return current.to_vec();
+        };
+        let mut order = shuffler.order_after(entries, current.last().copied());
+        if order.len() > 1 && current.last() == Some(&order[0]) {
+            order.swap(0, 1);
+        }
+        order
+    }
+
+    /// Whether playback at `time` is done with the current entry: just past
+    /// its end, or anywhere beyond it that no other track of the file covers,
+    /// as after a seek past `--end`.
+    pub fn ended_at(&self, time: f64) -> bool {
+        let Some(current) = self.current() else {
+            return false;
+        };
+        let past_end = current
+            .start_secs
+            .zip(current.duration_secs)
+            .is_some_and(|(start, duration)| time >= start + duration);
+        current.played_past_end(time)
+            || (past_end
+                && !self
+                    .entries
+                    .iter()
+                    .any(|entry| entry.source == current.source && entry.contains(time)))
+    }
+
+    /// Keeps the queue in step with a file that holds several tracks.
+    /// Playing past the current track's end advances the queue; a position
+    /// elsewhere in the file, e.g. after a seek, selects the track there.
+    pub fn follow_time(&mut self, time: f64) -> TrackChange {
+        let ended = self.ended_at(time);
+        let Some(current) = self.current() else {
+            return TrackChange::Unchanged;
+        };
+        if current.start_secs.is_none() || current.contains(time) {
+            return TrackChange::Unchanged;
+        }
+        let source = current.source.clone();
+        if ended {
+            return match self.advance() {
+                None => TrackChange::Ended,
+                Some(next) if next.source == source && next.contains(time) => TrackChange::Moved,
+                Some(_) => TrackChange::Load,
+            };
+        }
+        let found = self.order.iter().position(|&index| {
+            let entry = &self.entries[index];
+            entry.source == source && entry.contains(time)
+        });
+        match found {
+            Some(position) => {
+                self.position = position;
+                TrackChange::Moved
+            }
+            None => TrackChange::Unchanged,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shuffle::ShuffleMode;
+    use std::fs;
+
+    fn entries(names: &[&str]) -> Vec<QueueEntry> {
+        names
+            .iter()
+            .map(|name| QueueEntry::new(MediaSource::Remote(name.to_string())))
+            .collect()
+    }
+
+    fn names(queue: &Queue) -> String {
+        queue.current().unwrap().source.display()
+    }
+
+    #[test]
+    fn test_resolve_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let song = dir.path().join("sub").join("a song.mp3");
+        fs::write(&song, b"").unwrap();
+
+        assert_eq!(
+            resolve_entry(dir.path(), "sub/a song.mp3").unwrap(),
+            MediaSource::Local(song.clone())
+        );
+        let uri = url::Url::from_file_path(&song).unwrap().to_string();
+        assert!(uri.contains("a%20song"));
+        assert_eq!(
+            resolve_entry(Path::new("/elsewhere"), &uri).unwrap(),
+            MediaSource::Local(song.clone())
+        );
+        assert_eq!(
+            resolve_entry(dir.path(), song.to_str().unwrap()).unwrap(),
+            MediaSource::Local(song)
+        );
+        assert_eq!(
+            resolve_entry(dir.path(), " http://host/a.mp3 ").unwrap(),
+            MediaSource::Remote("http://host/a.mp3".to_string())
+        );
+        assert!(resolve_entry(dir.path(), "missing.mp3").is_err());
+        assert!(resolve_entry(dir.path(), "sub").is_err());
+    }
+
+    #[test]
+    fn test_label() {
+        let mut entry = QueueEntry::new(MediaSource::Remote("http://host/a.mp3".to_string()));
+        assert_eq!(entry.label(), "http://host/a.mp3");
+        entry.title = Some("Song".to_string());
+        entry.duration_secs = Some(125.4);
+        assert_eq!(entry.label(), "Song (2:05)");
+        entry.artist = Some("Band".to_string());
+        assert_eq!(entry.label(), "Band - Song (2:05)");
+    }
+
+    #[test]
+    fn test_queue_advances_in_order() {
+        let mut queue = Queue::new(entries(&["a", "b", "c"]), false, None);
+        assert_eq!(names(&queue), "a");
+        assert!(queue.previous().is_none());
+        assert_eq!(queue.advance().unwrap().source.display(), "b");
+        assert_eq!(queue.advance().unwrap().source.display(), "c");
+        assert!(queue.advance().is_none());
+        assert_eq!(names(&queue), "c");
+        assert_eq!(queue.previous().unwrap().source.display(), "b");
+    }
+
+    #[test]
+    fn test_queue_loops() {
+        let mut queue = Queue::new(entries(&["a", "b"]), true, None);
+        assert_eq!(queue.previous().unwrap().source.display(), "b");
+        assert_eq!(queue.advance().unwrap().source.display(), "a");
+        assert_eq!(queue.advance().unwrap().source.display(), "b");
+        assert_eq!(queue.position(), 1);
+    }
+
+    #[test]
+    fn test_queue_shuffle_keeps_every_entry() {
+        let shuffler = Shuffler::new(ShuffleMode::Uniform, None);
+        let mut queue = Queue::new(entries(&["a", "b", "c", "d", "e"]), false, Some(shuffler));
+        let mut played = vec![names(&queue)];
+        while let Some(entry) = queue.advance() {
+            played.push(entry.source.display());
+        }
+        played.sort();
+        assert_eq!(played, ["a", "b", "c", "d", "e"]);
+        let shuffler = Shuffler::new(ShuffleMode::Uniform, None);
+        assert!(Queue::new(Vec::new(), true, Some(shuffler))
+            .current()
+            .is_none());
+    }
+
+    #[test]
+    fn test_queue_reshuffles_each_loop() {
+        let shuffler = Shuffler::new(ShuffleMode::Uniform, Some(3));
+        let mut queue = Queue::new(entries(&["a", "b", "c", "d", "e"]), true, Some(shuffler));
+        let mut loops: Vec<Vec<String>> = Vec::new();
+        for _ in 0..6 {
+            let mut played = vec![names(&queue)];
+            for _ in 1..5 {
+                played.push(queue.advance().unwrap().source.display());
+            }
+            let next = queue.advance().unwrap().source.display();
+            assert_ne!(&next, played.last().unwrap());
+
+            let mut sorted = played.clone();
+            sorted.sort();
+            assert_eq!(sorted, ["a", "b", "c", "d", "e"]);
+            loops.push(played);
+        }
+        assert!(loops.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn test_upcoming() {
+        let mut queue = Queue::new(entries(&["a", "b", "c", "d"]), false, None);
+        queue.advance();
+        let upcoming: Vec<(usize, String)> = queue
+            .upcoming(5)
+            .map(|(position, entry)| (position, entry.source.display()))
+            .collect();
+        assert_eq!(upcoming, [(2, "c".to_string()), (3, "d".to_string())]);
+        assert_eq!(queue.upcoming(1).count(), 1);
+        queue.jump_to(3);
+        assert_eq!(queue.upcoming(5).count(), 0);
+    }
+
+    #[test]
+    fn test_next_loop_is_drawn_ahead() {
+        let shuffler = Shuffler::new(ShuffleMode::Uniform, Some(5));
+        let mut queue = Queue::new(entries(&["a", "b", "c", "d"]), true, Some(shuffler));
+        let next = queue.next_loop();
+        assert_eq!(queue.next_loop(), next);
+        let last = queue.indexed().last().unwrap().0;
+        assert_ne!(next[0], last);
+
+        queue.jump_to(3).unwrap();
+        queue.advance().unwrap();
+        assert_eq!(
+            queue.indexed().map(|(index, _)| index).collect::<Vec<_>>(),
+            next
+        );
+        assert_eq!(queue.current_index(), Some(next[0]));
+        assert!(queue.jump_to(4).is_none());
+
+        let mut plain = Queue::new(entries(&["a", "b"]), true, None);
+        assert_eq!(plain.next_loop(), [0, 1]);
+        plain.jump_to(1);
+        assert_eq!(plain.start_next_loop().unwrap().source.display(), "a");
+    }
+
+    fn tracks(spans: &[(f64, Option<f64>)]) -> Vec<QueueEntry> {
+        spans
+            .iter()
+            .map(|&(start, duration)| QueueEntry {
+                start_secs: Some(start),
+                duration_secs: duration,
+                ..QueueEntry::new(MediaSource::Local(PathBuf::from("album.flac")))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_follow_time() {
+        let mut queue = Queue::new(
+            tracks(&[(0.0, Some(100.0)), (100.0, Some(50.0)), (150.0, None)]),
+            false,
+            None,
+        );
+        assert_eq!(queue.follow_time(99.0), TrackChange::Unchanged);
+        assert_eq!(queue.follow_time(101.0), TrackChange::Moved);
+        assert_eq!(queue.position(), 1);
+        // A seek back into the first track.
+        assert_eq!(queue.follow_time(20.0), TrackChange::Moved);
+        assert_eq!(queue.position(), 0);
+        assert_eq!(queue.follow_time(400.0), TrackChange::Moved);
+        assert_eq!(queue.position(), 2);
+        assert_eq!(queue.follow_time(1000.0), TrackChange::Unchanged);
+
+        // Out of file order, the next queued track has to be seeked to.
+        let mut queue = Queue::new(
+            tracks(&[(100.0, Some(50.0)), (0.0, Some(100.0))]),
+            false,
+            None,
+        );
+        assert_eq!(queue.follow_time(151.0), TrackChange::Load);
+        assert_eq!(queue.position(), 1);
+        assert_eq!(queue.follow_time(101.0), TrackChange::Ended);
+
+        let mut queue = Queue::new(entries(&["a", "b"]), false, None);
+        assert_eq!(queue.follow_time(500.0), TrackChange::Unchanged);
+    }
+
+    #[test]
+    fn test_seek_past_segment_end() {
+        // `--end 90`, then a seek well past it.
+        let mut segment = QueueEntry::new(MediaSource::Remote("a".to_string()));
+        segment.trim(None, Some(90.0));
+        let mut queue = Queue::new(
+            vec![
+                segment,
+                QueueEntry::new(MediaSource::Remote("b".to_string())),
+            ],
+            false,
+            None,
+        );
+        assert!(!queue.ended_at(60.0));
+        assert!(queue.ended_at(150.0));
+        assert_eq!(queue.follow_time(150.0), TrackChange::Load);
+        assert_eq!(queue.position(), 1);
+
+        // Within a CUE sheet the seek selects the track there instead.
+        let queue = Queue::new(
+            tracks(&[(0.0, Some(100.0)), (100.0, Some(50.0)), (150.0, None)]),
+            false,
+            None,
+        );
+        assert!(!queue.ended_at(400.0));
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut entry = QueueEntry {
+            duration_secs: Some(300.0),
+            ..QueueEntry::new(MediaSource::Remote("a".to_string()))
+        };
+        entry.trim(None, None);
+        assert_eq!((entry.start_secs, entry.duration_secs), (None, Some(300.0)));
+        entry.trim(Some(60.0), None);
+        assert_eq!(
+            (entry.start_secs, entry.duration_secs),
+            (Some(60.0), Some(240.0))
+        );
+        // Already a segment.
+        entry.trim(Some(10.0), Some(20.0));
+        assert_eq!(entry.start_secs, Some(60.0));
+
+        let mut entry = QueueEntry::new(MediaSource::Remote("b".to_string()));
+        entry.trim(None, Some(90.0));
+        assert_eq!(
+            (entry.start_secs, entry.duration_secs),
+            (Some(0.0), Some(90.0))
+        );
+        assert!(entry.played_past_end(91.0));
+    }
+}
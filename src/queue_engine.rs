@@ -0,0 +1,546 @@
+use crate::queue::{MediaSource, Queue, TrackChange};
+use rust_cast::channels::media::{IdleReason, PlayerState, StatusEntry};
+
+/// How far into an entry "previous" starts it over instead of going back.
+const PREVIOUS_RESTARTS_AFTER_SECS: f64 = 3.0;
+
+/// What the receiver reported for the current item on one status poll.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReceiverState {
+    /// Playing, paused or buffering, at this position in seconds.
+    Active(Option<f64>),
+    /// Idle because the item played to its end.
+    Finished,
+    /// Idle for any other reason: stopped, failed, or not started yet.
+    Idle,
+    /// No media session, as after a finished item is cleared.
+    NoMedia,
+}
+
+impl ReceiverState {
+    pub fn from_status(status: Option<&StatusEntry>) -> Self {
+        match status {
+            None => ReceiverState::NoMedia,
+            Some(entry) if entry.player_state == PlayerState::Idle => match entry.idle_reason {
+                Some(IdleReason::Finished) => ReceiverState::Finished,
+                _ => ReceiverState::Idle,
+            },
+            Some(entry) => ReceiverState::Active(entry.current_time.map(f64::from)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    /// Replay the current entry.
+    One,
+    /// Wrap around at the end of the queue (`--loop-playback`).
+    All,
+}
+
+/// What the player has to do on the receiver.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Switch to the queue's current entry. `playing` is the media the
+    /// receiver still has loaded, so another track of it is reached by
+    /// seeking rather than loading.
+    Play {
+        playing: Option<MediaSource>,
+    },
+    /// Seek back to the start of the current entry.
+    Restart,
+    Stop,
+}
+
+/// Something for the UI to show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueueEvent {
+    /// The current entry changed, or started over.
+    NowPlaying,
+    /// "Previous" went back to the start of the current entry.
+    Restarted,
+    /// The queue wrapped around to its first entry.
+    Looped,
+    EndOfQueue,
+    /// Playback stopped at the end of an entry, as asked.
+    StoppedAfterCurrent,
+    RepeatChanged(RepeatMode),
+    StopAfterCurrentChanged(bool),
+}
+
+/// Drives a [`Queue`] from the receiver's status: when an item finishes it
+/// moves on according to the repeat mode and tells the player what to load.
+#[derive(Debug, Default)]
+pub struct QueueEngine {
+    repeat_one: bool,
+    stop_after_current: bool,
+    /// Set once the receiver reports the current item, so a finished or
+    /// missing status afterwards means it has ended rather than not started.
+    item_active: bool,
+    events: Vec<QueueEvent>,
+}
+
+impl QueueEngine {
+    pub fn repeat_mode(&self, queue: &Queue) -> RepeatMode {
+        match (self.repeat_one, queue.loops()) {
+            (true, _) => RepeatMode::One,
+            (false, true) => RepeatMode::All,
+            (false, false) => RepeatMode::Off,
+        }
+    }
+
+    /// Cycles off, all, one.
+    pub fn cycle_repeat(&mut self, queue: &mut Queue) -> RepeatMode {
+        let mode = match self.repeat_mode(queue) {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        };
+        self.repeat_one = mode == RepeatMode::One;
+        queue.set_loop(mode != RepeatMode::Off);
+        self.events.push(QueueEvent::RepeatChanged(mode));
+        mode
+    }
+
+    pub fn toggle_stop_after_current(&mut self) -> bool {
+        self.stop_after_current = !self.stop_after_current;
+        self.events
+            .push(QueueEvent::StopAfterCurrentChanged(self.stop_after_current));
+        self.stop_after_current
+    }
+
+    /// Events since the last call, oldest first.
+    pub fn take_events(&mut self) -> Vec<QueueEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Takes one status poll and returns what to do about it, if anything.
+    pub fn on_status(&mut self, queue: &mut Queue, state: ReceiverState) -> Option<Action> {
+        match state {
+            ReceiverState::Finished | ReceiverState::NoMedia if self.item_active => {
+                self.item_active = false;
+                self.finished(queue)
+            }
+            ReceiverState::Active(time) => {
+                self.item_active = true;
+                self.follow(queue, time?)
+            }
+            _ => {
+                self.item_active = false;
+                None
+            }
+        }
+    }
+
+    /// Takes one status poll while the receiver runs the queue itself (see
+    /// [`crate::cast_queue::CastQueue`]). `moved` is set when it has moved on
+    /// to another item, to whether that wrapped around to the start.
+    pub fn on_receiver_queue(
+        &mut self,
+        state: ReceiverState,
+        moved: Option<bool>,
+    ) -> Option<Action> {
+        if let Some(looped) = moved {
+            if looped {
+                self.events.push(QueueEvent::Looped);
+            }
+            self.events.push(QueueEvent::NowPlaying);
+            if self.stop_after_current {
+                // The receiver has already started the next item.
+                self.stop_after_current = false;
+                self.item_active = false;
+                self.events.push(QueueEvent::StoppedAfterCurrent);
+                return Some(Action::Stop);
+            }
+        }
+        match state {
+            ReceiverState::Finished | ReceiverState::NoMedia if self.item_active => {
+                self.item_active = false;
+                if self.stop_after_current {
+                    self.stop_after_current = false;
+                    self.events.push(QueueEvent::StoppedAfterCurrent);
+                } else {
+                    self.events.push(QueueEvent::EndOfQueue);
+                }
+            }
+            ReceiverState::Active(_) => self.item_active = true,
+            _ => self.item_active = false,
+        }
+        None
+    }
+
+    /// Moves to the next or previous entry at the user's request.
+    pub fn skip(&mut self, queue: &mut Queue, forward: bool) -> Option<Action> {
+        let playing = queue.current().map(|entry| entry.source.clone());
+        let moved = if forward {
+            queue.advance()
+        } else {
+            queue.previous()
+        };
+        moved?;
+        Some(self.play(playing))
+    }
+
+    /// Goes back to the previous entry, or to the start of the current one
+    /// once `time` (seconds into the media) is a few seconds into it.
+    pub fn previous(&mut self, queue: &mut Queue, time: Option<f64>) -> Option<Action> {
+        self.restart(queue, time)
+            .or_else(|| self.skip(queue, false))
+    }
+
+    /// The restart half of [`QueueEngine::previous`], for when the receiver
+    /// moves between items itself.
+    pub fn restart(&mut self, queue: &Queue, time: Option<f64>) -> Option<Action> {
+        let entry = queue.current()?;
+        let elapsed = time? - entry.start_secs.unwrap_or_default();
+        if elapsed <= PREVIOUS_RESTARTS_AFTER_SECS {
+            return None;
+        }
+        self.events.push(QueueEvent::Restarted);
+        Some(Action::Restart)
+    }
+
+    /// Moves to the entry at `position` in play order.
+    pub fn jump(&mut self, queue: &mut Queue, position: usize) -> Option<Action> {
+        let playing = queue.current().map(|entry| entry.source.clone());
+        queue.jump_to(position)?;
+        Some(self.play(playing))
+    }
+
+    fn play(&mut self, playing: Option<MediaSource>) -> Action {
+        self.item_active = false;
+        self.events.push(QueueEvent::NowPlaying);
+        Action::Play { playing }
+    }
+
+    /// Advances past the entry that just ended, unless it repeats or
+    /// playback should stop here.
+    fn finished(&mut self, queue: &mut Queue) -> Option<Action> {
+        if self.repeat_one {
+            return Some(self.play(None));
+        }
+        if self.stop_after_current {
+            self.stop_after_current = false;
+            self.events.push(QueueEvent::StoppedAfterCurrent);
+            return None;
+        }
+        let was_last = queue.position() + 1 == queue.len();
+        if queue.advance().is_none() {
+            self.events.push(QueueEvent::EndOfQueue);
+            return None;
+        }
+        if was_last {
+            self.events.push(QueueEvent::Looped);
+        }
+        Some(self.play(None))
+    }
+
+    /// Keeps up with tracks that share one file (CUE sheets), where the
+    /// receiver plays on from one track into the next by itself.
+    fn follow(&mut self, queue: &mut Queue, time: f64) -> Option<Action> {
+        let playing = queue.current()?.source.clone();
+        if queue.ended_at(time) {
+            if self.repeat_one {
+                return Some(self.play(Some(playing)));
+            }
+            if self.stop_after_current {
+                self.stop_after_current = false;
+                self.events.push(QueueEvent::StoppedAfterCurrent);
+                return Some(Action::Stop);
+            }
+        }
+
+        let was_last = queue.position() + 1 == queue.len();
+        match queue.follow_time(time) {
+            TrackChange::Unchanged => None,
+            TrackChange::Moved => {
+                self.events.push(QueueEvent::NowPlaying);
+                None
+            }
+            TrackChange::Load => {
+                if was_last && queue.position() == 0 {
+                    self.events.push(QueueEvent::Looped);
+                }
+                Some(self.play(Some(playing)))
+            }
+            TrackChange::Ended => {
+                self.events.push(QueueEvent::EndOfQueue);
+                Some(Action::Stop)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::QueueEntry;
+    use std::path::PathBuf;
+    use ReceiverState::{Active, Finished, Idle, NoMedia};
+
+    fn queue(names: &[&str], loop_playback: bool) -> Queue {
+        let entries = names
+            .iter()
+            .map(|name| QueueEntry::new(MediaSource::Remote(name.to_string())))
+            .collect();
+        Queue::new(entries, loop_playback, None)
+    }
+
+    fn current(queue: &Queue) -> String {
+        queue.current().unwrap().source.display()
+    }
+
+    /// Feeds `states` to the engine and returns the actions taken.
+    fn drive(
+        engine: &mut QueueEngine,
+        queue: &mut Queue,
+        states: &[ReceiverState],
+    ) -> Vec<Option<Action>> {
+        states
+            .iter()
+            .map(|state| engine.on_status(queue, *state))
+            .collect()
+    }
+
+    const LOAD: Option<Action> = Some(Action::Play { playing: None });
+
+    #[test]
+    fn test_advances_when_an_item_finishes() {
+        let mut engine = QueueEngine::default();
+        let mut queue = queue(&["a", "b"], false);
+
+        // A stale FINISHED before the item started playing is ignored.
+        let actions = drive(
+            &mut engine,
+            &mut queue,
+            &[
+                Finished,
+                Idle,
+                Active(Some(0.0)),
+                Active(Some(5.0)),
+                Finished,
+            ],
+        );
+        assert_eq!(actions, [None, None, None, None, LOAD]);
+        assert_eq!(current(&queue), "b");
+        assert_eq!(engine.take_events(), [QueueEvent::NowPlaying]);
+
+        // The old status can linger until the next item loads.
+        let actions = drive(&mut engine, &mut queue, &[Finished, Active(None), NoMedia]);
+        assert_eq!(actions, [None, None, None]);
+        assert_eq!(current(&queue), "b");
+        assert_eq!(engine.take_events(), [QueueEvent::EndOfQueue]);
+    }
+
+    #[test]
+    fn test_stopped_items_do_not_advance() {
+        let mut engine = QueueEngine::default();
+        let mut queue = queue(&["a", "b"], false);
+        let actions = drive(&mut engine, &mut queue, &[Active(Some(1.0)), Idle, NoMedia]);
+        assert_eq!(actions, [None, None, None]);
+        assert_eq!(current(&queue), "a");
+    }
+
+    #[test]
+    fn test_repeat_modes() {
+        let mut engine = QueueEngine::default();
+        let mut queue = queue(&["a", "b"], true);
+        assert_eq!(engine.repeat_mode(&queue), RepeatMode::All);
+
+        let actions = drive(
+            &mut engine,
+            &mut queue,
+            &[Active(None), Finished, Active(None), Finished],
+        );
+        assert_eq!(actions, [None, LOAD, None, LOAD]);
+        assert_eq!(current(&queue), "a");
+        assert_eq!(
+            engine.take_events(),
+            [
+                QueueEvent::NowPlaying,
+                QueueEvent::Looped,
+                QueueEvent::NowPlaying
+            ]
+        );
+
+        assert_eq!(engine.cycle_repeat(&mut queue), RepeatMode::One);
+        let actions = drive(&mut engine, &mut queue, &[Active(None), Finished]);
+        assert_eq!(actions, [None, LOAD]);
+        assert_eq!(current(&queue), "a");
+
+        assert_eq!(engine.cycle_repeat(&mut queue), RepeatMode::Off);
+        assert!(!queue.loops());
+        assert_eq!(engine.cycle_repeat(&mut queue), RepeatMode::All);
+        assert!(queue.loops());
+    }
+
+    #[test]
+    fn test_stop_after_current() {
+        let mut engine = QueueEngine::default();
+        let mut queue = queue(&["a", "b", "c"], false);
+        assert!(engine.toggle_stop_after_current());
+        engine.take_events();
+
+        let actions = drive(
+            &mut engine,
+            &mut queue,
+            &[Active(None), Finished, Active(None), Finished],
+        );
+        // Only the first item stops; the flag clears once it has.
+        assert_eq!(actions, [None, None, None, LOAD]);
+        assert_eq!(current(&queue), "b");
+        assert_eq!(
+            engine.take_events(),
+            [QueueEvent::StoppedAfterCurrent, QueueEvent::NowPlaying]
+        );
+    }
+
+    #[test]
+    fn test_skip() {
+        let mut engine = QueueEngine::default();
+        let mut queue = queue(&["a", "b"], false);
+        assert_eq!(engine.skip(&mut queue, false), None);
+        assert_eq!(
+            engine.skip(&mut queue, true),
+            Some(Action::Play {
+                playing: Some(MediaSource::Remote("a".to_string()))
+            })
+        );
+        assert_eq!(engine.skip(&mut queue, true), None);
+        assert_eq!(current(&queue), "b");
+    }
+
+    #[test]
+    fn test_receiver_queue() {
+        let mut engine = QueueEngine::default();
+        assert_eq!(engine.on_receiver_queue(Active(None), None), None);
+        assert_eq!(engine.on_receiver_queue(Active(None), Some(false)), None);
+        assert_eq!(engine.on_receiver_queue(Active(None), Some(true)), None);
+        assert_eq!(
+            engine.take_events(),
+            [
+                QueueEvent::NowPlaying,
+                QueueEvent::Looped,
+                QueueEvent::NowPlaying
+            ]
+        );
+
+        engine.toggle_stop_after_current();
+        assert_eq!(
+            engine.on_receiver_queue(Active(None), Some(false)),
+            Some(Action::Stop)
+        );
+        assert_eq!(engine.on_receiver_queue(Idle, None), None);
+        assert_eq!(engine.on_receiver_queue(Active(None), None), None);
+        assert_eq!(engine.on_receiver_queue(Finished, None), None);
+        assert_eq!(engine.on_receiver_queue(NoMedia, None), None);
+        assert_eq!(
+            engine.take_events(),
+            [
+                QueueEvent::StopAfterCurrentChanged(true),
+                QueueEvent::NowPlaying,
+                QueueEvent::StoppedAfterCurrent,
+                QueueEvent::EndOfQueue
+            ]
+        );
+    }
+
+    #[test]
+    fn test_previous_restarts_a_few_seconds_in() {
+        let mut engine = QueueEngine::default();
+        let mut queue = queue(&["a", "b"], false);
+        queue.advance();
+
+        assert_eq!(
+            engine.previous(&mut queue, Some(12.0)),
+            Some(Action::Restart)
+        );
+        assert_eq!(current(&queue), "b");
+        assert_eq!(engine.take_events(), [QueueEvent::Restarted]);
+
+        assert!(matches!(
+            engine.previous(&mut queue, Some(2.0)),
+            Some(Action::Play { .. })
+        ));
+        assert_eq!(current(&queue), "a");
+        // Nothing before the first entry, but it can still start over.
+        assert_eq!(engine.previous(&mut queue, None), None);
+        assert_eq!(
+            engine.previous(&mut queue, Some(5.0)),
+            Some(Action::Restart)
+        );
+
+        // Within a CUE track, time counts from the track's start.
+        let mut queue = cue_queue();
+        queue.advance();
+        assert!(engine.restart(&queue, Some(102.0)).is_none());
+        assert_eq!(engine.restart(&queue, Some(104.0)), Some(Action::Restart));
+    }
+
+    #[test]
+    fn test_jump() {
+        let mut engine = QueueEngine::default();
+        let mut queue = queue(&["a", "b", "c"], false);
+        assert_eq!(engine.jump(&mut queue, 3), None);
+        assert_eq!(
+            engine.jump(&mut queue, 2),
+            Some(Action::Play {
+                playing: Some(MediaSource::Remote("a".to_string()))
+            })
+        );
+        assert_eq!(current(&queue), "c");
+        assert_eq!(engine.take_events(), [QueueEvent::NowPlaying]);
+    }
+
+    fn cue_queue() -> Queue {
+        let entries = [(0.0, 100.0), (100.0, 50.0)]
+            .iter()
+            .map(|&(start, duration)| QueueEntry {
+                start_secs: Some(start),
+                duration_secs: Some(duration),
+                ..QueueEntry::new(MediaSource::Local(PathBuf::from("album.flac")))
+            })
+            .collect();
+        Queue::new(entries, false, None)
+    }
+
+    #[test]
+    fn test_follows_tracks_in_one_file() {
+        let album = Some(MediaSource::Local(PathBuf::from("album.flac")));
+        let mut engine = QueueEngine::default();
+        let mut queue = cue_queue();
+        let actions = drive(
+            &mut engine,
+            &mut queue,
+            &[Active(Some(50.0)), Active(Some(101.0)), Active(Some(151.0))],
+        );
+        assert_eq!(actions, [None, None, Some(Action::Stop)]);
+        assert_eq!(
+            engine.take_events(),
+            [QueueEvent::NowPlaying, QueueEvent::EndOfQueue]
+        );
+
+        // Repeat-one seeks back to the start of the track.
+        let mut queue = cue_queue();
+        engine.cycle_repeat(&mut queue);
+        engine.cycle_repeat(&mut queue);
+        assert_eq!(
+            engine.on_status(&mut queue, Active(Some(100.5))),
+            Some(Action::Play {
+                playing: album.clone()
+            })
+        );
+        assert_eq!(queue.position(), 0);
+
+        let mut engine = QueueEngine::default();
+        let mut queue = cue_queue();
+        engine.toggle_stop_after_current();
+        assert_eq!(
+            engine.on_status(&mut queue, Active(Some(100.5))),
+            Some(Action::Stop)
+        );
+        assert_eq!(queue.position(), 0);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::queue::{MediaSource, QueueEntry};
+use crate::timecode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The state file in the home directory when `--resume-file` isn't given.
+const DEFAULT_RESUME_FILE: &str = ".castnow_positions.json";
+/// Positions closer to the start than this aren't worth resuming.
+const MIN_RESUME_SECS: f64 = 10.0;
+/// Items this close to their end count as finished.
+const FINISHED_WITHIN_SECS: f64 = 30.0;
+/// How often positions are written out while playing.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+/// The oldest positions are dropped beyond this many.
+const MAX_POSITIONS: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct SavedPosition {
+    position_secs: f64,
+    /// Seconds since the Unix epoch.
+    updated: u64,
+}
+
+/// Playback positions by media, kept in a JSON state file so stopped items
+/// can be resumed later.
+#[derive(Debug, Default)]
+pub struct ResumeState {
+    path: PathBuf,
+    /// `--resume`: resume without asking.
+    auto: bool,
+    positions: BTreeMap<String, SavedPosition>,
+    /// The item last reported playing, which a FINISHED status refers to.
+    playing: Option<String>,
+    changed: bool,
+    last_save: Option<Instant>,
+    /// Set when the state file couldn't be read, so it isn't overwritten.
+    read_only: bool,
+}
+
+/// The key positions of `entry` are kept under: the file's path, size and
+/// modification time, or the URL. Tracks and `--start`/`--end` segments
+/// share the key of the whole file; streams have none.
+pub fn media_key(entry: &QueueEntry) -> Option<String> {
+    match &entry.source {
+        MediaSource::Local(path) => {
+            let path = fs::canonicalize(path).ok()?;
+            let metadata = fs::metadata(&path).ok()?;
+            let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+            Some(format!(
+                "{}|{}|{}",
+                path.display(),
+                metadata.len(),
+                modified.as_secs()
+            ))
+        }
+        MediaSource::Remote(url) => Some(url.clone()),
+        MediaSource::Stream { .. } => None,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+impl ResumeState {
+    /// Reads the state file at `path`, or `~/.castnow_positions.json`. A
+    /// missing file starts out empty; an unreadable one is left alone, and
+    /// nothing is saved for the rest of the run.
+    pub fn load(path: Option<&Path>, auto: bool) -> Option<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(DEFAULT_RESUME_FILE),
+        };
+        let positions = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).map_err(anyhow::Error::from),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        };
+        let read_only = positions.is_err();
+        let positions = positions.unwrap_or_else(|e| {
+            eprintln!(
+                "Can't read playback positions from {}: {e}. Positions won't be saved this time.",
+                path.display()
+            );
+            BTreeMap::new()
+        });
+        Some(ResumeState {
+            path,
+            auto,
+            positions,
+            read_only,
+            ..Default::default()
+        })
+    }
+
+    /// The saved position of `entry`, in seconds, if it falls within the
+    /// entry's part of the media.
+    pub fn position(&self, entry: &QueueEntry) -> Option<f64> {
+        let key = media_key(entry)?;
+        let position = self.positions.get(&key)?.position_secs;
+        (entry.start_secs.is_none() || entry.contains(position)).then_some(position)
+    }
+
+    /// Where to start `entry`: its saved position if the user wants it,
+    /// asking on the terminal unless `--resume` was given.
+    pub fn offer(&self, entry: &QueueEntry) -> Option<f64> {
+        let position = self.position(entry)?;
+        if !self.auto {
+            if !io::stdin().is_terminal() {
+                return None;
+            }
+            print!(
+                "Resume {} from {}? [Y/n] ",
+                entry.label(),
+                timecode::format_time(position)
+            );
+            io::stdout().flush().ok()?;
+            if !accepts(&mut io::stdin().lock()) {
+                return None;
+            }
+        }
+        println!("Resuming at {}", timecode::format_time(position));
+        Some(position)
+    }
+
+    /// Like [`ResumeState::offer`] for items started during playback, when
+    /// the terminal can't be asked: only with `--resume`.
+    pub fn resume_at(&self, entry: &QueueEntry) -> Option<f64> {
+        if !self.auto {
+            return None;
+        }
+        let position = self.position(entry)?;
+        println!("Resuming at {}", timecode::format_time(position));
+        Some(position)
+    }
+
+    /// Notes that `entry` is playing at `time` seconds of `duration`.
+    /// Positions near the start of the entry aren't kept; near its end, the
+    /// end of a track or `--end` if it has one, it counts as finished.
+    pub fn update(&mut self, entry: &QueueEntry, time: f64, duration: Option<f64>) {
+        let Some(key) = media_key(entry) else {
+            return;
+        };
+        self.playing = Some(key.clone());
+        let start = entry.start_secs.unwrap_or_default();
+        let end = entry
+            .duration_secs
+            .filter(|_| entry.start_secs.is_some())
+            .map(|length| start + length)
+            .or(duration);
+        let near_end = end.is_some_and(|end| time >= end - FINISHED_WITHIN_SECS);
+        if time < start + MIN_RESUME_SECS || near_end {
+            self.changed |= self.positions.remove(&key).is_some();
+            return;
+        }
+        self.positions.insert(
+            key,
+            SavedPosition {
+                position_secs: time,
+                updated: now(),
+            },
+        );
+        self.changed = true;
+    }
+
+    /// Forgets the position of `entry` once the receiver reports it finished.
+    /// A FINISHED status left over from an earlier item is ignored.
+    pub fn finish(&mut self, entry: &QueueEntry) {
+        let key = media_key(entry);
+        if key.is_none() || key != self.playing {
+            return;
+        }
+        self.playing = None;
+        if let Some(key) = key {
+            self.changed |= self.positions.remove(&key).is_some();
+        }
+    }
+
+    /// Writes the state file if anything changed since the last write more
+    /// than a few seconds ago.
+    pub fn save_if_due(&mut self) {
+        if self
+            .last_save
+            .is_some_and(|last_save| last_save.elapsed() < SAVE_INTERVAL)
+        {
+            return;
+        }
+        if let Err(e) = self.save() {
+            eprintln!("Error saving playback positions: {e}");
+        }
+    }
+
+    /// Writes the state file if anything changed.
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        self.last_save = Some(Instant::now());
+        if !self.changed || self.read_only {
+            return Ok(());
+        }
+        while self.positions.len() > MAX_POSITIONS {
+            let oldest = self
+                .positions
+                .iter()
+                .min_by_key(|(_, saved)| saved.updated)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            self.positions.remove(&oldest);
+        }
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        // Written aside and renamed, so an interrupted save can't lose the file.
+        let temp = self.path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_string_pretty(&self.positions)?)?;
+        fs::rename(&temp, &self.path)?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+/// Reads a yes/no answer, yes being the default.
+fn accepts(input: &mut impl BufRead) -> bool {
+    let mut answer = String::new();
+    if input.read_line(&mut answer).is_err() {
+        return false;
+    }
+    !matches!(answer.trim().to_lowercase().as_str(), "n" | "no")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn local(dir: &Path, name: &str) -> QueueEntry {
+        let path = dir.join(name);
+        fs::write(&path, b"media").unwrap();
+        QueueEntry::new(MediaSource::Local(path))
+    }
+
+    #[test]
+    fn test_media_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = local(dir.path(), "movie.mkv");
+        let key = media_key(&entry).unwrap();
+        assert!(key.contains("movie.mkv|5|"), "{key}");
+
+        // A changed file is a different media.
+        fs::write(dir.path().join("movie.mkv"), b"longer media").unwrap();
+        assert_ne!(media_key(&entry).unwrap(), key);
+
+        let url = QueueEntry::new(MediaSource::Remote("http://host/a.mp4".to_string()));
+        assert_eq!(media_key(&url).unwrap(), "http://host/a.mp4");
+        let track = QueueEntry {
+            start_secs: Some(60.0),
+            ..url
+        };
+        assert_eq!(media_key(&track).unwrap(), "http://host/a.mp4");
+    }
+
+    #[test]
+    fn test_segment_positions() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("positions.json");
+        let movie = local(dir.path(), "movie.mkv");
+        let mut segment = movie.clone();
+        segment.trim(None, Some(90.0));
+
+        // `--end 90` resumes like the whole file.
+        let mut state = ResumeState::load(Some(&state_file), true).unwrap();
+        state.update(&segment, 40.0, Some(3600.0));
+        assert_eq!(state.position(&segment), Some(40.0));
+        assert_eq!(state.position(&movie), Some(40.0));
+        // Near `--end` rather than the end of the file counts as finished.
+        state.update(&segment, 70.0, Some(3600.0));
+        assert_eq!(state.position(&segment), None);
+
+        // Positions outside a `--start 100` segment aren't offered.
+        let mut later = movie.clone();
+        later.trim(Some(100.0), None);
+        state.update(&movie, 40.0, Some(3600.0));
+        assert_eq!(state.position(&later), None);
+        state.update(&later, 105.0, Some(3600.0));
+        assert_eq!(state.position(&later), None);
+        state.update(&later, 500.0, Some(3600.0));
+        assert_eq!(state.position(&later), Some(500.0));
+    }
+
+    #[test]
+    fn test_positions_are_saved_and_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("state").join("positions.json");
+        let movie = local(dir.path(), "movie.mkv");
+        let song = local(dir.path(), "song.mp3");
+
+        let mut state = ResumeState::load(Some(&state_file), true).unwrap();
+        state.update(&movie, 5.0, Some(3600.0));
+        assert_eq!(state.position(&movie), None);
+        state.update(&movie, 1800.0, Some(3600.0));
+        state.update(&song, 100.0, None);
+        state.save().unwrap();
+
+        let mut state = ResumeState::load(Some(&state_file), false).unwrap();
+        assert_eq!(state.position(&movie), Some(1800.0));
+        assert_eq!(state.position(&song), Some(100.0));
+
+        // Near the end counts as finished.
+        state.update(&movie, 3590.0, Some(3600.0));
+        assert_eq!(state.position(&movie), None);
+        // FINISHED only clears the item that was playing.
+        state.finish(&song);
+        assert_eq!(state.position(&song), Some(100.0));
+        state.update(&song, 200.0, None);
+        state.finish(&song);
+        assert_eq!(state.position(&song), None);
+        state.save().unwrap();
+        let state = ResumeState::load(Some(&state_file), false).unwrap();
+        assert!(state.positions.is_empty());
+    }
+
+    #[test]
+    fn test_unreadable_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("positions.json");
+        fs::write(&state_file, b"not json").unwrap();
+        let mut state = ResumeState::load(Some(&state_file), false).unwrap();
+        assert!(state.positions.is_empty());
+
+        // The bad file is kept for the user to look at, not overwritten.
+        state.update(&local(dir.path(), "movie.mkv"), 600.0, None);
+        state.save().unwrap();
+        assert_eq!(fs::read(&state_file).unwrap(), b"not json");
+    }
+
+    #[test]
+    fn test_accepts() {
+        assert!(accepts(&mut Cursor::new("\n")));
+        assert!(accepts(&mut Cursor::new("y\n")));
+        assert!(!accepts(&mut Cursor::new("N\n")));
+        assert!(!accepts(&mut Cursor::new(" no \n")));
+    }
+}
@@ -0,0 +1,667 @@
+use crate::metadata;
+use crate::tags;
+use clap::ValueEnum;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::cmp::Ordering;
+use std::io::{self, BufRead, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// What kind of media a file holds, by extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum MediaKind {
+    Audio,
+    Video,
+    Image,
+}
+
+impl MediaKind {
+    pub fn of(path: &Path) -> Option<MediaKind> {
+        let content_type = content_type(path)?;
+        match content_type.split('/').next()? {
+            "audio" => Some(MediaKind::Audio),
+            "video" => Some(MediaKind::Video),
+            "image" => Some(MediaKind::Image),
+            _ => None,
+        }
+    }
+}
+
+/// The MIME type of a file the receiver can show: audio and video, plus the
+/// still images the Default Media Receiver displays.
+pub fn content_type(path: &Path) -> Option<&'static str> {
+    if let Some(content_type) = metadata::content_type_for_path(path) {
+        return Some(content_type);
+    }
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let content_type = match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+/// The order scanned files are queued in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// Albums in path order, each played through by disc and track number.
+    #[default]
+    Album,
+    /// Path order, with numbers compared by value (`2` before `10`).
+    Name,
+    /// Oldest modification time first.
+    Mtime,
+    /// Artist, album, disc and track number from the tags, then name.
+    Tags,
+}
+
+/// Which files under a directory are queued, and in what order.
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+    /// Levels of subdirectories to descend into; `None` for no limit.
+    pub max_depth: Option<usize>,
+    pub follow_links: bool,
+    pub hidden: bool,
+    /// Globs matched against the path relative to the scanned directory.
+    /// Files must match one of `include` (when given) and none of `exclude`.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub kinds: Vec<MediaKind>,
+    pub sort: SortOrder,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            max_depth: None,
+            follow_links: false,
+            hidden: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            kinds: vec![MediaKind::Audio, MediaKind::Video],
+            sort: SortOrder::Album,
+        }
+    }
+}
+
+fn glob_set(patterns: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder
+            .add(Glob::new(pattern).map_err(|e| anyhow::anyhow!("Invalid glob {pattern}: {e}"))?);
+    }
+    Ok(builder.build()?)
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// Lists the media files under `dir` that pass the options' filters, sorted.
+/// Unreadable entries and symlink loops are skipped.
+pub fn scan(dir: &Path, options: &ScanOptions) -> anyhow::Result<Vec<PathBuf>> {
+    let include = glob_set(&options.include)?;
+    let exclude = glob_set(&options.exclude)?;
+
+    let mut walker = WalkDir::new(dir).follow_links(options.follow_links);
+    if let Some(depth) = options.max_depth {
+        // Depth 1 is the directory's own files.
+        walker = walker.max_depth(depth + 1);
+    }
+    let mut files: Vec<PathBuf> = walker
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || options.hidden || !is_hidden(entry.path()))
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(walkdir::DirEntry::into_path)
+        .filter(|path| {
+            let relative = path.strip_prefix(dir).unwrap_or(path);
+            MediaKind::of(path).is_some_and(|kind| options.kinds.contains(&kind))
+                && (options.include.is_empty() || include.is_match(relative))
+                && !exclude.is_match(relative)
+        })
+        .collect();
+    sort_files(&mut files, options.sort);
+    Ok(files)
+}
+
+pub fn sort_files(files: &mut [PathBuf], order: SortOrder) {
+    match order {
+        SortOrder::Album => sort_by_album(files),
+        SortOrder::Name => files.sort_by(|a, b| natural_path_cmp(a, b)),
+        SortOrder::Mtime => {
+            let mtime = |path: &Path| {
+                path.metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH)
+            };
+            files.sort_by_cached_key(|path| mtime(path));
+        }
+        SortOrder::Tags => {
+            let mut keyed: Vec<(TrackTags, PathBuf)> = files
+                .iter()
+                .map(|path| (TrackTags::read(path), path.clone()))
+                .collect();
+            keyed.sort_by(|(a, a_path), (b, b_path)| {
+                a.tags_key()
+                    .cmp(&b.tags_key())
+                    .then_with(|| natural_path_cmp(a_path, b_path))
+            });
+            for (file, (_, path)) in files.iter_mut().zip(keyed) {
+                *file = path;
+            }
+        }
+    }
+}
+
+/// The tags that decide a file's place in the queue, lowercased for
+/// comparison.
+#[derive(Debug, Default)]
+struct TrackTags {
+    artist: Option<String>,
+    album_artist: Option<String>,
+    album: Option<String>,
+    disc: Option<u32>,
+    track: Option<u32>,
+}
+
+impl TrackTags {
+    fn read(path: &Path) -> Self {
+        let tags = tags::read_tags(path).unwrap_or_default();
+        let lowercase = |value: Option<String>| value.map(|value| value.to_lowercase());
+        TrackTags {
+            artist: lowercase(tags.artist),
+            album_artist: lowercase(tags.album_artist),
+            album: lowercase(tags.album),
+            // The folder an album is split into ("Disc 2", "CD2") stands in
+            // for a missing disc tag.
+            disc: tags.disc.or_else(|| disc_from_dir(path)),
+            track: tags.track,
+        }
+    }
+
+    /// Sort key for [`SortOrder::Tags`]; untagged files come last.
+    fn tags_key(&self) -> (bool, &str, &str, u32, u32) {
+        let artist = self.album_artist.as_ref().or(self.artist.as_ref());
+        (
+            artist.is_none() && self.album.is_none(),
+            artist.map(String::as_str).unwrap_or_default(),
+            self.album.as_deref().unwrap_or_default(),
+            self.disc.unwrap_or(0),
+            self.track.unwrap_or(u32::MAX),
+        )
+    }
+}
+
+/// The disc number in a folder name like `Disc 2`, `disk-2` or `CD2`.
+fn disc_from_dir(path: &Path) -> Option<u32> {
+    let name = path.parent()?.file_name()?.to_string_lossy().to_lowercase();
+    let rest = ["disc", "disk", "cd"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))?;
+    let digits: String = rest
+        .trim_start_matches([' ', '-', '_', '.'])
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+/// The directory an album lives in, above any per-disc folders.
+fn album_dir(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    match disc_from_dir(path) {
+        Some(_) => parent.parent().unwrap_or(parent).to_path_buf(),
+        None => parent.to_path_buf(),
+    }
+}
+
+/// Which album a file belongs to: its album tag, told apart by album artist
+/// or, without one, by folder, or just its folder when untagged.
+#[derive(Debug, PartialEq, Eq)]
+enum AlbumKey {
+    Tagged {
+        album: String,
+        artist_or_dir: Result<String, PathBuf>,
+    },
+    Untagged(PathBuf),
+}
+
+impl AlbumKey {
+    fn of(path: &Path, tags: &TrackTags) -> Self {
+        match &tags.album {
+            Some(album) => AlbumKey::Tagged {
+                album: album.clone(),
+                artist_or_dir: tags.album_artist.clone().ok_or_else(|| album_dir(path)),
+            },
+            None => AlbumKey::Untagged(album_dir(path)),
+        }
+    }
+}
+
+/// Keeps each album's files together, albums ordered by where they first
+/// appear in path order, and orders each album by disc and track number.
+/// Files without numbers keep their path order after the numbered ones.
+fn sort_by_album(files: &mut [PathBuf]) {
+    files.sort_by(|a, b| natural_path_cmp(a, b));
+
+    let mut albums: Vec<(AlbumKey, Vec<(TrackTags, PathBuf)>)> = Vec::new();
+    for path in files.iter() {
+        let tags = TrackTags::read(path);
+        let key = AlbumKey::of(path, &tags);
+        match albums.iter_mut().find(|(album, _)| *album == key) {
+            Some((_, tracks)) => tracks.push((tags, path.clone())),
+            None => albums.push((key, vec![(tags, path.clone())])),
+        }
+    }
+
+    let sorted = albums.into_iter().flat_map(|(_, mut tracks)| {
+        // Stable, so ties keep the natural path order.
+        tracks.sort_by_key(|(tags, _)| (tags.disc.unwrap_or(1), tags.track.unwrap_or(u32::MAX)));
+        tracks.into_iter().map(|(_, path)| path)
+    });
+    for (file, path) in files.iter_mut().zip(sorted.collect::<Vec<_>>()) {
+        *file = path;
+    }
+}
+
+/// Compares paths component by component with [`natural_cmp`].
+pub fn natural_path_cmp(a: &Path, b: &Path) -> Ordering {
+    let name = |component: Component| component.as_os_str().to_string_lossy().to_string();
+    let mut a = a.components().map(name);
+    let mut b = b.components().map(name);
+    loop {
+        return match (a.next(), b.next()) {
+            (Some(a), Some(b)) => match natural_cmp(&a, &b) {
+                Ordering::Equal => continue,
+                ordering => ordering,
+            },
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+    }
+}
+
+/// Case-insensitive comparison that orders runs of digits by value, so
+/// `2 - b.flac` comes before `10 - a.flac`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let (a_next, b_next) = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => (*a, *b),
+        };
+        if a_next.is_ascii_digit() && b_next.is_ascii_digit() {
+            let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                let mut digits = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(digit);
+                }
+                digits
+            };
+            let (a_number, b_number) = (take_number(&mut a_chars), take_number(&mut b_chars));
+            let (a_value, b_value) = (
+                a_number.trim_start_matches('0'),
+                b_number.trim_start_matches('0'),
+            );
+            let ordering = a_value
+                .len()
+                .cmp(&b_value.len())
+                .then_with(|| a_value.cmp(b_value));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        } else {
+            let ordering = a_next.to_lowercase().cmp(b_next.to_lowercase());
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            a_chars.next();
+            b_chars.next();
+        }
+    }
+    // Equal ignoring case and zero padding; fall back to a strict order.
+    a.cmp(b)
+}
+
+/// Parses a selection like `1,3-5` into zero-based indices, in the order
+/// given. Empty input or `all` selects everything.
+pub fn parse_selection(input: &str, count: usize) -> Result<Vec<usize>, String> {
+    let input = input.trim();
+    if input.is_empty() || input.eq_ignore_ascii_case("all") {
+        return Ok((0..count).collect());
+    }
+
+    let number = |part: &str| -> Result<usize, String> {
+        let number: usize = part
+            .trim()
+            .parse()
+            .map_err(|_| format!("{part} is not a number"))?;
+        if number == 0 || number > count {
+            return Err(format!("{number} is not between 1 and {count}"));
+        }
+        Ok(number - 1)
+    };
+    let mut selected = Vec::new();
+    for part in input.split(',') {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (number(start)?, number(end)?);
+                if start > end {
+                    return Err(format!("{} comes after {}", start + 1, end + 1));
+                }
+                selected.extend(start..=end);
+            }
+            None => selected.push(number(part)?),
+        }
+    }
+    Ok(selected)
+}
+
+/// Lists `files` and asks which to play until the answer parses.
+pub fn pick_files(
+    files: &[PathBuf],
+    base_dir: &Path,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Vec<PathBuf>> {
+    for (i, file) in files.iter().enumerate() {
+        let relative = file.strip_prefix(base_dir).unwrap_or(file);
+        writeln!(output, "{}: {}", i + 1, relative.display())?;
+    }
+    loop {
+        write!(
+            output,
+            "Enter the files to play (e.g. 1,3-5; empty for all): "
+        )?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "No selection made",
+            ));
+        }
+        match parse_selection(&line, files.len()) {
+            Ok(selected) => return Ok(selected.into_iter().map(|i| files[i].clone()).collect()),
+            Err(e) => writeln!(output, "Invalid selection: {e}. Please try again.")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn names(files: &[PathBuf], base: &Path) -> Vec<String> {
+        files
+            .iter()
+            .map(|file| {
+                file.strip_prefix(base)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = vec![
+            "10 - x.flac",
+            "2 - y.flac",
+            "1 - z.flac",
+            "02 - w.flac",
+            "B.mp3",
+            "a.mp3",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            [
+                "1 - z.flac",
+                "02 - w.flac",
+                "2 - y.flac",
+                "10 - x.flac",
+                "a.mp3",
+                "B.mp3"
+            ]
+        );
+        assert_eq!(
+            natural_path_cmp(Path::new("Disc 2/1.mp3"), Path::new("Disc 10/1.mp3")),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn test_scan_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in [
+            "10 - ten.mp3",
+            "2 - two.mp3",
+            "film.mkv",
+            "cover.jpg",
+            "notes.txt",
+            ".hidden.mp3",
+            ".secret/a.mp3",
+            "sub/deep/b.flac",
+            "sub/c.ogg",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+
+        let files = scan(root, &ScanOptions::default()).unwrap();
+        assert_eq!(
+            names(&files, root),
+            [
+                "2 - two.mp3",
+                "10 - ten.mp3",
+                "film.mkv",
+                "sub/c.ogg",
+                "sub/deep/b.flac"
+            ]
+        );
+
+        let options = ScanOptions {
+            max_depth: Some(1),
+            hidden: true,
+            kinds: vec![MediaKind::Audio, MediaKind::Image],
+            sort: SortOrder::Name,
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&scan(root, &options).unwrap(), root),
+            [
+                ".hidden.mp3",
+                ".secret/a.mp3",
+                "2 - two.mp3",
+                "10 - ten.mp3",
+                "cover.jpg",
+                "sub/c.ogg"
+            ]
+        );
+
+        let options = ScanOptions {
+            include: vec!["sub/**".to_string(), "*.mkv".to_string()],
+            exclude: vec!["**/deep/**".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&scan(root, &options).unwrap(), root),
+            ["film.mkv", "sub/c.ogg"]
+        );
+
+        let options = ScanOptions {
+            include: vec!["[".to_string()],
+            ..Default::default()
+        };
+        assert!(scan(root, &options).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let music = dir.path().join("music");
+        let elsewhere = dir.path().join("elsewhere");
+        fs::create_dir_all(&music).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(music.join("a.mp3"), b"").unwrap();
+        fs::write(elsewhere.join("b.mp3"), b"").unwrap();
+        std::os::unix::fs::symlink(&elsewhere, music.join("linked")).unwrap();
+        // A loop back to the top is skipped rather than followed forever.
+        std::os::unix::fs::symlink(&music, music.join("loop")).unwrap();
+
+        let files = scan(&music, &ScanOptions::default()).unwrap();
+        assert_eq!(names(&files, &music), ["a.mp3"]);
+
+        let options = ScanOptions {
+            follow_links: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&scan(&music, &options).unwrap(), &music),
+            ["a.mp3", "linked/b.mp3"]
+        );
+    }
+
+    fn write_track(path: &Path, album: Option<&str>, disc: Option<u32>, track: Option<u32>) {
+        use id3::TagLike;
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        if let Some(album) = album {
+            tag.set_album(album);
+        }
+        if let Some(disc) = disc {
+            tag.set_disc(disc);
+        }
+        if let Some(track) = track {
+            tag.set_track(track);
+        }
+        tag.write_to_path(path, id3::Version::Id3v24).unwrap();
+    }
+
+    #[test]
+    fn test_disc_from_dir() {
+        assert_eq!(disc_from_dir(Path::new("a/Disc 2/x.mp3")), Some(2));
+        assert_eq!(disc_from_dir(Path::new("a/CD10/x.mp3")), Some(10));
+        assert_eq!(disc_from_dir(Path::new("a/disk-3 (bonus)/x.mp3")), Some(3));
+        assert_eq!(disc_from_dir(Path::new("a/Discography/x.mp3")), None);
+        assert_eq!(disc_from_dir(Path::new("x.mp3")), None);
+    }
+
+    #[test]
+    fn test_sort_by_album() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        // Tags out of step with file names: disc 2 named first, track 10
+        // sorting before track 2 as text.
+        write_track(&root.join("Live/a.mp3"), Some("Live"), Some(2), Some(1));
+        write_track(&root.join("Live/b.mp3"), Some("Live"), Some(1), Some(10));
+        write_track(&root.join("Live/c.mp3"), Some("Live"), Some(1), Some(2));
+        // Discs split into folders without disc tags.
+        write_track(&root.join("Set/CD2/01.mp3"), Some("Set"), None, Some(1));
+        write_track(&root.join("Set/CD1/02.mp3"), Some("Set"), None, Some(2));
+        write_track(&root.join("Set/CD1/01.mp3"), Some("Set"), None, Some(1));
+        // No tags at all: natural file name order.
+        write_track(&root.join("Untagged/10 - x.mp3"), None, None, None);
+        write_track(&root.join("Untagged/2 - y.mp3"), None, None, None);
+        // A stray file of the Live album elsewhere in the library is still
+        // only grouped with its own folder, as no album artist ties it in.
+        write_track(&root.join("Zoo/Live.mp3"), Some("Live"), None, Some(3));
+
+        let options = ScanOptions::default();
+        assert_eq!(
+            names(&scan(root, &options).unwrap(), root),
+            [
+                "Live/c.mp3",
+                "Live/b.mp3",
+                "Live/a.mp3",
+                "Set/CD1/01.mp3",
+                "Set/CD1/02.mp3",
+                "Set/CD2/01.mp3",
+                "Untagged/2 - y.mp3",
+                "Untagged/10 - x.mp3",
+                "Zoo/Live.mp3",
+            ]
+        );
+
+        let options = ScanOptions {
+            sort: SortOrder::Name,
+            ..Default::default()
+        };
+        assert_eq!(
+            names(
+                &scan(&root.join("Live"), &options).unwrap(),
+                &root.join("Live")
+            ),
+            ["a.mp3", "b.mp3", "c.mp3"]
+        );
+    }
+
+    #[test]
+    fn test_sort_by_album_keeps_albums_contiguous() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        // One album whose files are spread over two folders, with another
+        // album's folder sorting between them.
+        let tagged = |path: &str, album: &str, track: u32| {
+            let path = root.join(path);
+            write_track(&path, Some(album), None, Some(track));
+            let mut tag = id3::Tag::read_from_path(&path).unwrap();
+            id3::TagLike::set_album_artist(&mut tag, "Band");
+            tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+        };
+        tagged("A/1.mp3", "First", 1);
+        tagged("B/1.mp3", "Second", 1);
+        tagged("C/2.mp3", "First", 2);
+
+        assert_eq!(
+            names(&scan(root, &ScanOptions::default()).unwrap(), root),
+            ["A/1.mp3", "C/2.mp3", "B/1.mp3"]
+        );
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("", 3), Ok(vec![0, 1, 2]));
+        assert_eq!(parse_selection("all", 3), Ok(vec![0, 1, 2]));
+        assert_eq!(parse_selection("3, 1-2", 3), Ok(vec![2, 0, 1]));
+        assert!(parse_selection("4", 3).is_err());
+        assert!(parse_selection("0", 3).is_err());
+        assert!(parse_selection("3-1", 3).is_err());
+        assert!(parse_selection("one", 3).is_err());
+    }
+
+    #[test]
+    fn test_pick_files() {
+        let files = vec![PathBuf::from("/m/a.mp3"), PathBuf::from("/m/b.mp3")];
+        let mut input = io::Cursor::new("5\n2\n");
+        let mut output = Vec::new();
+        let picked = pick_files(&files, Path::new("/m"), &mut input, &mut output).unwrap();
+        assert_eq!(picked, [PathBuf::from("/m/b.mp3")]);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("1: a.mp3\n2: b.mp3\n"));
+        assert!(output.contains("Invalid selection: 5 is not between 1 and 2"));
+
+        let mut input = io::Cursor::new("");
+        assert!(pick_files(&files, Path::new("/m"), &mut input, &mut Vec::new()).is_err());
+    }
+}
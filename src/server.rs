@@ -1,49 +1,264 @@
 use bytes::Bytes;
-use http_body_util::Full;
-use hyper::body::Incoming;
-use hyper::header::CONTENT_TYPE;
+use futures::stream;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{
+    HeaderValue, ACCEPT_RANGES, ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_LENGTH, CONTENT_RANGE,
+    CONTENT_TYPE, RANGE,
+};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
+use std::collections::HashMap;
 use std::convert::Infallible;
-use std::fs::File;
-use std::io::Read;
-use std::net::SocketAddr;
+use std::fmt;
+use std::io::{self, SeekFrom};
+use std::net::{Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
 use tokio::sync::oneshot;
 
+/// Size of the chunks file and stream bodies are sent in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Content served next to the media file (subtitles, cover art, queued media, ...).
+#[derive(Clone, Debug)]
+pub struct Resource {
+    pub content_type: String,
+    pub body: ResourceBody,
+}
+
+/// A seekable byte source that is read while it is served, such as a file
+/// inside a torrent that is still downloading.
+pub trait StreamSource: Send + Sync + fmt::Debug {
+    fn size(&self) -> u64;
+    fn open(&self) -> io::Result<Box<dyn MediaReader>>;
+}
+
+pub trait MediaReader: AsyncRead + AsyncSeek + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncSeek + Send + Unpin> MediaReader for T {}
+
+#[derive(Clone, Debug)]
+pub enum ResourceBody {
+    Bytes(Bytes),
+    /// Read from disk on each request.
+    File(PathBuf),
+    Stream(Arc<dyn StreamSource>),
+}
+
+impl PartialEq for ResourceBody {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ResourceBody::Bytes(a), ResourceBody::Bytes(b)) => a == b,
+            (ResourceBody::File(a), ResourceBody::File(b)) => a == b,
+            (ResourceBody::Stream(a), ResourceBody::Stream(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Resource {
+    pub fn new(content_type: impl Into<String>, body: impl Into<Bytes>) -> Self {
+        Resource {
+            content_type: content_type.into(),
+            body: ResourceBody::Bytes(body.into()),
+        }
+    }
+
+    pub fn file(content_type: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Resource {
+            content_type: content_type.into(),
+            body: ResourceBody::File(path.into()),
+        }
+    }
+
+    pub fn stream(content_type: impl Into<String>, source: Arc<dyn StreamSource>) -> Self {
+        Resource {
+            content_type: content_type.into(),
+            body: ResourceBody::Stream(source),
+        }
+    }
+}
+
+/// Resources keyed by request path. Shared with the server so entries can be
+/// replaced while it is running.
+pub type Resources = Arc<RwLock<HashMap<String, Resource>>>;
+
+type Body = UnsyncBoxBody<Bytes, io::Error>;
+
+fn full(body: impl Into<Bytes>) -> Body {
+    Full::new(body.into())
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+fn status_response(status: StatusCode, body: &'static str) -> Response<Body> {
+    let mut response = Response::new(full(body));
+    *response.status_mut() = status;
+    response
+}
+
+/// Parses a single `bytes=` range into inclusive start and end offsets.
+/// `Some(None)` means the range can't be satisfied; multiple ranges and
+/// malformed headers are ignored (`None`) and the whole body is served.
+pub fn parse_range(header: &str, len: u64) -> Option<Option<(u64, u64)>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+    let range = if start.is_empty() {
+        // The last `end` bytes.
+        let suffix: u64 = end.parse().ok()?;
+        (suffix > 0 && len > 0).then(|| (len.saturating_sub(suffix), len - 1))
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            len.saturating_sub(1)
+        } else {
+            end.parse::<u64>().ok()?.min(len.saturating_sub(1))
+        };
+        (start < len && start <= end).then_some((start, end))
+    };
+    Some(range)
+}
+
+/// Streams `len` bytes of `reader` in chunks.
+fn reader_body(reader: Box<dyn MediaReader>, len: u64) -> Body {
+    let chunks = stream::unfold(Some(reader.take(len)), |reader| async move {
+        let mut reader = reader?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+        match reader.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok(Frame::data(Bytes::from(buffer))), Some(reader)))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+    BodyExt::boxed_unsync(StreamBody::new(chunks))
+}
+
+/// Serves a resource, honouring a `Range` header so receivers can seek.
+async fn serve_resource(resource: Resource, range: Option<&str>) -> io::Result<Response<Body>> {
+    let len = match &resource.body {
+        ResourceBody::Bytes(bytes) => bytes.len() as u64,
+        ResourceBody::File(path) => tokio::fs::metadata(path).await?.len(),
+        ResourceBody::Stream(source) => source.size(),
+    };
+    let range = range.and_then(|range| parse_range(range, len));
+    if let Some(None) = range {
+        let mut response = status_response(StatusCode::RANGE_NOT_SATISFIABLE, "");
+        response.headers_mut().insert(
+            CONTENT_RANGE,
+            HeaderValue::from_str(&format!("bytes */{len}")).unwrap(),
+        );
+        return Ok(response);
+    }
+    let (start, end) = range.flatten().unwrap_or((0, len.saturating_sub(1)));
+    let body_len = if len == 0 { 0 } else { end - start + 1 };
+
+    let body = match resource.body {
+        ResourceBody::Bytes(bytes) => {
+            full(bytes.slice(start as usize..(start + body_len) as usize))
+        }
+        ResourceBody::File(path) => {
+            let mut file = tokio::fs::File::open(path).await?;
+            file.seek(SeekFrom::Start(start)).await?;
+            reader_body(Box::new(file), body_len)
+        }
+        ResourceBody::Stream(source) => {
+            let mut reader = source.open()?;
+            reader.seek(SeekFrom::Start(start)).await?;
+            reader_body(reader, body_len)
+        }
+    };
+
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(&resource.content_type)
+            .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+    );
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(body_len));
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    // The receiver fetches text tracks and images cross-origin.
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    if range.is_some() {
+        headers.insert(
+            CONTENT_RANGE,
+            HeaderValue::from_str(&format!("bytes {start}-{end}/{len}")).unwrap(),
+        );
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+    }
+    Ok(response)
+}
+
 async fn handle_request(
     req: Request<Incoming>,
-    file_path: PathBuf,
-) -> Result<Response<Full<Bytes>>, Infallible> {
+    file_path: Option<PathBuf>,
+    resources: Resources,
+) -> Result<Response<Body>, Infallible> {
     let path = req.uri().path();
-    if path == "/" {
-        let file = File::open(&file_path).unwrap();
-        let mut reader = std::io::BufReader::new(file);
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer).unwrap();
-
-        let mut response = Response::new(Full::new(Bytes::from(buffer)));
-        response
-            .headers_mut()
-            .insert(CONTENT_TYPE, "video/mp4".parse().unwrap());
-        *response.status_mut() = StatusCode::OK;
-        Ok(response)
-    } else {
-        let mut not_found = Response::new(Full::new(Bytes::from("Not Found")));
-        *not_found.status_mut() = StatusCode::NOT_FOUND;
-        Ok(not_found)
-    }
+    let resource = match (&file_path, path) {
+        (Some(file_path), "/") => Some(Resource::file("video/mp4", file_path)),
+        _ => resources.read().unwrap().get(path).cloned(),
+    };
+    let Some(resource) = resource else {
+        return Ok(status_response(StatusCode::NOT_FOUND, "Not Found"));
+    };
+
+    let range = req
+        .headers()
+        .get(RANGE)
+        .and_then(|range| range.to_str().ok());
+    Ok(serve_resource(resource, range)
+        .await
+        .unwrap_or_else(|_| status_response(StatusCode::NOT_FOUND, "Not Found")))
 }
 
 pub async fn start_server(
     file_path: PathBuf,
     shutdown_rx: oneshot::Receiver<()>,
 ) -> anyhow::Result<(SocketAddr, tokio::task::JoinHandle<()>)> {
-    let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+    start_server_with_resources(file_path, Resources::default(), shutdown_rx).await
+}
+
+pub async fn start_server_with_resources(
+    file_path: PathBuf,
+    resources: Resources,
+    shutdown_rx: oneshot::Receiver<()>,
+) -> anyhow::Result<(SocketAddr, tokio::task::JoinHandle<()>)> {
+    serve(Some(file_path), resources, shutdown_rx).await
+}
+
+/// Starts a server for `resources` only, with nothing at `/`.
+pub async fn start_resource_server(
+    resources: Resources,
+    shutdown_rx: oneshot::Receiver<()>,
+) -> anyhow::Result<(SocketAddr, tokio::task::JoinHandle<()>)> {
+    serve(None, resources, shutdown_rx).await
+}
+
+/// Listens on all interfaces so the receiver can reach the server. The
+/// returned address is the loopback one; receivers need the LAN address
+/// with the same port.
+async fn serve(
+    file_path: Option<PathBuf>,
+    resources: Resources,
+    shutdown_rx: oneshot::Receiver<()>,
+) -> anyhow::Result<(SocketAddr, tokio::task::JoinHandle<()>)> {
+    // All interfaces, since the receiver fetches from the local network.
+    let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0));
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    let local_addr = listener.local_addr()?;
+    let local_addr = SocketAddr::from((Ipv4Addr::LOCALHOST, listener.local_addr()?.port()));
 
     let server_handle = tokio::spawn(async move {
         let mut shutdown_rx = shutdown_rx;
@@ -52,7 +267,10 @@ pub async fn start_server(
                 res = listener.accept() => {
                     if let Ok((stream, _)) = res {
                         let file_path = file_path.clone();
-                        let service = service_fn(move |req| handle_request(req, file_path.clone()));
+                        let resources = resources.clone();
+                        let service = service_fn(move |req| {
+                            handle_request(req, file_path.clone(), resources.clone())
+                        });
                         let io = TokioIo::new(stream);
                         tokio::spawn(async move {
                             if let Err(err) = http1::Builder::new().serve_connection(io, service).await {
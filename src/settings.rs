@@ -1,3 +1,4 @@
+use crate::shuffle::ShuffleMode;
 use clap::Parser;
 use serde::Deserialize;
 
@@ -12,10 +13,38 @@ pub struct Settings {
     #[arg(long, short)]
     pub device: Option<String>,
 
-    /// Path to subtitles file
+    /// Path to subtitles file, for the first item of the queue
     #[arg(long)]
     pub subtitles: Option<String>,
 
+    /// Embedded subtitle tracks to cast (comma-separated track numbers, or "all")
+    #[arg(long)]
+    pub subtitle_tracks: Option<String>,
+
+    /// External subtitle extractor (ffmpeg) for containers other than Matroska
+    #[arg(long)]
+    pub subtitle_extractor: Option<String>,
+
+    /// Subtitle font scale (1.0 is the receiver default)
+    #[arg(long)]
+    pub subtitle_scale: Option<f32>,
+
+    /// Subtitle text colour (name, #RGB, #RRGGBB or #RRGGBBAA)
+    #[arg(long)]
+    pub subtitle_color: Option<String>,
+
+    /// Subtitle background colour (name, #RGB, #RRGGBB or #RRGGBBAA)
+    #[arg(long)]
+    pub subtitle_background: Option<String>,
+
+    /// Subtitle edge type (none, outline, drop-shadow, raised, depressed)
+    #[arg(long)]
+    pub subtitle_edge: Option<String>,
+
+    /// Subtitle font family (serif, sans-serif, monospace, ... or a font name)
+    #[arg(long)]
+    pub subtitle_font: Option<String>,
+
     /// Disable the search for Chromecast devices
     #[arg(long)]
     #[serde(default)]
@@ -31,10 +60,49 @@ pub struct Settings {
     #[serde(default)]
     pub shuffle: bool,
 
-    /// Start playing at a specific time
+    /// Shuffle strategy (implies --shuffle)
+    #[arg(long, value_enum)]
+    pub shuffle_mode: Option<ShuffleMode>,
+
+    /// Seed for the shuffle, to replay the same order (implies --shuffle)
+    #[arg(long)]
+    pub shuffle_seed: Option<u64>,
+
+    /// Let the receiver run the queue, preloading each next item
+    #[arg(long)]
+    #[serde(default)]
+    pub native_queue: bool,
+
+    /// Save the queue to this playlist (.m3u8, .pls or .xspf) on exit and with the `w` key
+    #[arg(long)]
+    pub save_playlist: Option<String>,
+
+    /// Write absolute paths to saved playlists instead of relative ones
+    #[arg(long)]
+    #[serde(default)]
+    pub absolute_paths: bool,
+
+    /// Resume each item where it was last stopped without asking
+    #[arg(long)]
+    #[serde(default)]
+    pub resume: bool,
+
+    /// Where playback positions are remembered (default: ~/.castnow_positions.json)
+    #[arg(long)]
+    pub resume_file: Option<String>,
+
+    /// Start playing at this time: 90, 1:30, 01:02:03, 1h2m3s or 25%
     #[arg(long)]
     pub seek: Option<String>,
 
+    /// Play each item from this time on, as with --seek but without percentages
+    #[arg(long)]
+    pub start: Option<String>,
+
+    /// Stop each item at this time, moving on to the next one
+    #[arg(long)]
+    pub end: Option<String>,
+
     /// Set the volume step
     #[arg(long)]
     pub volume_step: Option<f32>,
@@ -87,16 +155,16 @@ mod tests {
     #[test]
     fn test_default_settings() {
         let settings = Settings::parse_from(vec!["gemini_castnow"]);
-        assert_eq!(settings.no_search, false);
-        assert_eq!(settings.loop_playback, false);
-        assert_eq!(settings.shuffle, false);
+        assert!(!settings.no_search);
+        assert!(!settings.loop_playback);
+        assert!(!settings.shuffle);
         assert_eq!(settings.volume_step, None);
-        assert_eq!(settings.tomp4, false);
-        assert_eq!(settings.quiet, false);
-        assert_eq!(settings.no_metadata, false);
-        assert_eq!(settings.no_cover, false);
-        assert_eq!(settings.show_options, false);
-        assert_eq!(settings.exit, false);
+        assert!(!settings.tomp4);
+        assert!(!settings.quiet);
+        assert!(!settings.no_metadata);
+        assert!(!settings.no_cover);
+        assert!(!settings.show_options);
+        assert!(!settings.exit);
     }
 
     #[test]
@@ -108,8 +176,8 @@ mod tests {
             "--volume-step",
             "0.1",
         ]);
-        assert_eq!(settings.no_search, true);
-        assert_eq!(settings.loop_playback, true);
+        assert!(settings.no_search);
+        assert!(settings.loop_playback);
         assert_eq!(settings.volume_step, Some(0.1));
     }
 
@@ -120,8 +188,8 @@ mod tests {
             "volume_step": 0.2
         }"#;
         let settings: Settings = serde_json::from_str(json).unwrap();
-        assert_eq!(settings.no_search, true);
+        assert!(settings.no_search);
         assert_eq!(settings.volume_step, Some(0.2));
-        assert_eq!(settings.loop_playback, false); // Default from serde
+        assert!(!settings.loop_playback); // Default from serde
     }
 }
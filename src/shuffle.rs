@@ -0,0 +1,292 @@
+use crate::queue::{MediaSource, QueueEntry};
+use crate::tags;
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::path::Path;
+
+/// How a shuffled queue is ordered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShuffleMode {
+    /// Every entry once, in random order.
+    #[default]
+    Uniform,
+    /// Albums in random order, each played through in order.
+    Album,
+    /// Random order, avoiding the same artist twice in a row.
+    ArtistSpread,
+}
+
+/// Produces shuffled play orders from a seeded generator, so the same seed
+/// and queue always give the same orders, loop after loop.
+#[derive(Clone, Debug)]
+pub struct Shuffler {
+    mode: ShuffleMode,
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Shuffler {
+    /// A random seed is picked when none is given; [`Shuffler::seed`]
+    /// reports it so the shuffle can be replayed.
+    pub fn new(mode: ShuffleMode, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+        Shuffler {
+            mode,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// A play order over `entries`: indexes into it, each exactly once.
+    pub fn order(&mut self, entries: &[QueueEntry]) -> Vec<usize> {
+        self.order_after(entries, None)
+    }
+
+    /// Like [`Shuffler::order`], for an order that follows entry `last`, as
+    /// when a looping queue is shuffled again. Artist spread then avoids
+    /// opening with `last`'s artist.
+    pub fn order_after(&mut self, entries: &[QueueEntry], last: Option<usize>) -> Vec<usize> {
+        match self.mode {
+            ShuffleMode::Uniform => {
+                let mut order: Vec<usize> = (0..entries.len()).collect();
+                order.shuffle(&mut self.rng);
+                order
+            }
+            ShuffleMode::Album => {
+                let mut albums = group_by(entries, album_of);
+                albums.shuffle(&mut self.rng);
+                albums.into_iter().flatten().collect()
+            }
+            ShuffleMode::ArtistSpread => self.artist_spread(entries, last),
+        }
+    }
+
+    /// Shuffles each artist's entries, then repeatedly takes one from the
+    /// artist with the most left that didn't play last, breaking ties at
+    /// random. Back-to-back artists only happen when one artist has more
+    /// than half the queue. The artist of entry `previous` counts as having
+    /// played last.
+    fn artist_spread(&mut self, entries: &[QueueEntry], previous: Option<usize>) -> Vec<usize> {
+        let mut artists = group_by(entries, artist_of);
+        for artist in &mut artists {
+            artist.shuffle(&mut self.rng);
+        }
+        artists.shuffle(&mut self.rng);
+
+        let mut order = Vec::with_capacity(entries.len());
+        let mut last = previous
+            .and_then(|previous| artists.iter().position(|artist| artist.contains(&previous)));
+        while order.len() < entries.len() {
+            let candidates = || {
+                artists
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, artist)| !artist.is_empty())
+            };
+            let pick = candidates()
+                .filter(|(i, _)| Some(*i) != last)
+                .max_by_key(|(_, artist)| artist.len())
+                .or_else(|| candidates().next())
+                .map(|(i, _)| i)
+                .unwrap();
+            order.push(artists[pick].pop().unwrap());
+            last = Some(pick);
+        }
+        order
+    }
+}
+
+/// Groups entry indexes by `key`, groups in order of first appearance.
+fn group_by(entries: &[QueueEntry], key: impl Fn(&QueueEntry) -> String) -> Vec<Vec<usize>> {
+    let mut keys: Vec<String> = Vec::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let key = key(entry);
+        match keys.iter().position(|existing| *existing == key) {
+            Some(group) => groups[group].push(index),
+            None => {
+                keys.push(key);
+                groups.push(vec![index]);
+            }
+        }
+    }
+    groups
+}
+
+fn local_tags(entry: &QueueEntry) -> tags::AudioTags {
+    match &entry.source {
+        MediaSource::Local(path) => tags::read_tags(path).unwrap_or_default(),
+        _ => tags::AudioTags::default(),
+    }
+}
+
+/// The entry's album with its artist, so same-named albums ("Greatest
+/// Hits") by different artists stay apart; or else the folder it's in.
+fn album_of(entry: &QueueEntry) -> String {
+    let tags = local_tags(entry);
+    let album = entry.album.clone().or(tags.album);
+    let artist = tags.album_artist.or(entry.artist.clone()).or(tags.artist);
+    match (album, &entry.source) {
+        (Some(album), _) => format!(
+            "{}\0{}",
+            artist.unwrap_or_default().to_lowercase(),
+            album.to_lowercase()
+        ),
+        (None, MediaSource::Local(path)) => path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_string_lossy()
+            .to_string(),
+        (None, source) => source.display(),
+    }
+}
+
+/// The entry's artist; entries without one count as one artist each.
+fn artist_of(entry: &QueueEntry) -> String {
+    let artist = entry.artist.clone().or_else(|| {
+        let tags = local_tags(entry);
+        tags.artist.or(tags.album_artist)
+    });
+    match artist {
+        Some(artist) => artist.to_lowercase(),
+        None => format!("\0{}", entry.source.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, artist: &str, album: &str) -> QueueEntry {
+        QueueEntry {
+            artist: Some(artist.to_string()),
+            album: Some(album.to_string()),
+            ..QueueEntry::new(MediaSource::Remote(name.to_string()))
+        }
+    }
+
+    fn library() -> Vec<QueueEntry> {
+        vec![
+            entry("a1", "A", "First"),
+            entry("a2", "A", "First"),
+            entry("a3", "A", "First"),
+            entry("b1", "B", "Second"),
+            entry("b2", "B", "Second"),
+            entry("c1", "C", "Third"),
+            entry("a4", "A", "Fourth"),
+        ]
+    }
+
+    fn names(entries: &[QueueEntry], order: &[usize]) -> Vec<String> {
+        order
+            .iter()
+            .map(|&index| entries[index].source.display())
+            .collect()
+    }
+
+    fn is_permutation(order: &[usize], len: usize) -> bool {
+        let mut sorted = order.to_vec();
+        sorted.sort();
+        sorted == (0..len).collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn test_seed_reproduces_order() {
+        let entries = library();
+        for mode in [
+            ShuffleMode::Uniform,
+            ShuffleMode::Album,
+            ShuffleMode::ArtistSpread,
+        ] {
+            let mut first = Shuffler::new(mode, Some(7));
+            let mut second = Shuffler::new(mode, Some(7));
+            for _ in 0..3 {
+                let order = first.order(&entries);
+                assert!(is_permutation(&order, entries.len()), "{mode:?}");
+                assert_eq!(order, second.order(&entries), "{mode:?}");
+            }
+        }
+        assert_eq!(Shuffler::new(ShuffleMode::Uniform, Some(7)).seed(), 7);
+    }
+
+    #[test]
+    fn test_album_shuffle_keeps_tracks_in_order() {
+        let entries = library();
+        for seed in 0..20 {
+            let order = Shuffler::new(ShuffleMode::Album, Some(seed)).order(&entries);
+            let played = names(&entries, &order);
+            let position = |name: &str| played.iter().position(|n| n == name).unwrap();
+            assert_eq!(position("a2"), position("a1") + 1);
+            assert_eq!(position("a3"), position("a2") + 1);
+            assert_eq!(position("b2"), position("b1") + 1);
+        }
+    }
+
+    #[test]
+    fn test_artist_spread_avoids_back_to_back_artists() {
+        let entries = library();
+        for seed in 0..20 {
+            let order = Shuffler::new(ShuffleMode::ArtistSpread, Some(seed)).order(&entries);
+            assert!(is_permutation(&order, entries.len()));
+            for pair in order.windows(2) {
+                assert_ne!(
+                    entries[pair[0]].artist,
+                    entries[pair[1]].artist,
+                    "seed {seed}: {:?}",
+                    names(&entries, &order)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_album_shuffle_keeps_same_named_albums_apart() {
+        let entries = vec![
+            entry("x1", "X", "Greatest Hits"),
+            entry("x2", "X", "Greatest Hits"),
+            entry("y1", "Y", "Greatest Hits"),
+        ];
+        assert_eq!(group_by(&entries, album_of), [vec![0, 1], vec![2]]);
+    }
+
+    #[test]
+    fn test_artist_spread_follows_the_previous_loop() {
+        let entries = vec![
+            entry("a1", "A", "First"),
+            entry("a2", "A", "First"),
+            entry("b1", "B", "Second"),
+            entry("c1", "C", "Third"),
+        ];
+        for seed in 0..20 {
+            let mut shuffler = Shuffler::new(ShuffleMode::ArtistSpread, Some(seed));
+            let order = shuffler.order_after(&entries, Some(0));
+            assert!(is_permutation(&order, entries.len()));
+            assert_ne!(
+                entries[order[0]].artist.as_deref(),
+                Some("A"),
+                "seed {seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_untagged_entries() {
+        let entries: Vec<QueueEntry> = ["x", "y", "z"]
+            .iter()
+            .map(|name| QueueEntry::new(MediaSource::Remote(name.to_string())))
+            .collect();
+        let order = Shuffler::new(ShuffleMode::ArtistSpread, Some(1)).order(&entries);
+        assert!(is_permutation(&order, 3));
+        assert!(Shuffler::new(ShuffleMode::Album, Some(1))
+            .order(&[])
+            .is_empty());
+    }
+}
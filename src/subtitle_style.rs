@@ -0,0 +1,235 @@
+use crate::settings::Settings;
+use serde_json::{json, Value};
+
+const FOREGROUND_PRESETS: [&str; 4] = ["#FFFFFFFF", "#FFFF00FF", "#00FFFFFF", "#00FF00FF"];
+const BACKGROUND_PRESETS: [&str; 3] = ["#00000000", "#00000080", "#000000FF"];
+const EDGE_TYPES: [&str; 5] = ["NONE", "OUTLINE", "DROP_SHADOW", "RAISED", "DEPRESSED"];
+const GENERIC_FONT_FAMILIES: [&str; 7] = [
+    "SANS_SERIF",
+    "MONOSPACED_SANS_SERIF",
+    "SERIF",
+    "MONOSPACED_SERIF",
+    "CASUAL",
+    "CURSIVE",
+    "SMALL_CAPITALS",
+];
+const MIN_FONT_SCALE: f32 = 0.5;
+const MAX_FONT_SCALE: f32 = 3.0;
+/// Edge colour used whenever an edge type is set.
+const EDGE_COLOR: &str = "#000000FF";
+
+/// The Cast `TextTrackStyle`. Unset fields keep the receiver's defaults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextTrackStyle {
+    pub font_scale: Option<f32>,
+    pub foreground_color: Option<String>,
+    pub background_color: Option<String>,
+    pub edge_type: Option<String>,
+    pub font_family: Option<String>,
+}
+
+impl TextTrackStyle {
+    pub fn from_settings(settings: &Settings) -> anyhow::Result<Self> {
+        Ok(TextTrackStyle {
+            font_scale: settings
+                .subtitle_scale
+                .map(|scale| scale.clamp(MIN_FONT_SCALE, MAX_FONT_SCALE)),
+            foreground_color: settings
+                .subtitle_color
+                .as_deref()
+                .map(parse_color)
+                .transpose()?,
+            background_color: settings
+                .subtitle_background
+                .as_deref()
+                .map(parse_color)
+                .transpose()?,
+            edge_type: settings
+                .subtitle_edge
+                .as_deref()
+                .map(parse_edge_type)
+                .transpose()?,
+            font_family: settings.subtitle_font.clone(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == TextTrackStyle::default()
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut style = json!({});
+        if let Some(scale) = self.font_scale {
+            style["fontScale"] = json!(scale);
+        }
+        if let Some(color) = &self.foreground_color {
+            style["foregroundColor"] = json!(color);
+        }
+        if let Some(color) = &self.background_color {
+            style["backgroundColor"] = json!(color);
+        }
+        if let Some(edge_type) = &self.edge_type {
+            style["edgeType"] = json!(edge_type);
+            style["edgeColor"] = json!(EDGE_COLOR);
+        }
+        if let Some(family) = &self.font_family {
+            match generic_font_family(family) {
+                Some(generic) => style["fontGenericFamily"] = json!(generic),
+                None => style["fontFamily"] = json!(family),
+            }
+        }
+        style
+    }
+
+    pub fn scale_by(&mut self, step: f32) {
+        let scale = self.font_scale.unwrap_or(1.0) + step;
+        self.font_scale = Some(scale.clamp(MIN_FONT_SCALE, MAX_FONT_SCALE));
+    }
+
+    pub fn cycle_foreground(&mut self) {
+        self.foreground_color = Some(cycle(&FOREGROUND_PRESETS, self.foreground_color.as_deref()));
+    }
+
+    pub fn cycle_background(&mut self) {
+        self.background_color = Some(cycle(&BACKGROUND_PRESETS, self.background_color.as_deref()));
+    }
+
+    pub fn cycle_edge_type(&mut self) {
+        self.edge_type = Some(cycle(&EDGE_TYPES, self.edge_type.as_deref()));
+    }
+
+    pub fn cycle_font_family(&mut self) {
+        let current = self.font_family.as_deref().and_then(generic_font_family);
+        self.font_family = Some(cycle(&GENERIC_FONT_FAMILIES, current));
+    }
+}
+
+fn cycle(presets: &[&str], current: Option<&str>) -> String {
+    let next = current
+        .and_then(|current| presets.iter().position(|preset| *preset == current))
+        .map_or(0, |index| (index + 1) % presets.len());
+    presets[next].to_string()
+}
+
+fn generic_font_family(family: &str) -> Option<&'static str> {
+    let normalized = family.trim().to_uppercase().replace(['-', ' '], "_");
+    let normalized = match normalized.as_str() {
+        "SANS" => "SANS_SERIF",
+        "MONOSPACE" | "MONO" => "MONOSPACED_SANS_SERIF",
+        other => other,
+    };
+    GENERIC_FONT_FAMILIES
+        .iter()
+        .find(|generic| **generic == normalized)
+        .copied()
+}
+
+/// Accepts `#RGB`, `#RRGGBB`, `#RRGGBBAA` or a basic colour name and returns
+/// the `#RRGGBBAA` form the receiver expects.
+pub fn parse_color(color: &str) -> anyhow::Result<String> {
+    let color = color.trim();
+    let named = match color.to_lowercase().as_str() {
+        "white" => Some("#FFFFFF"),
+        "black" => Some("#000000"),
+        "yellow" => Some("#FFFF00"),
+        "red" => Some("#FF0000"),
+        "green" => Some("#00FF00"),
+        "blue" => Some("#0000FF"),
+        "cyan" => Some("#00FFFF"),
+        "magenta" => Some("#FF00FF"),
+        "transparent" => Some("#00000000"),
+        _ => None,
+    };
+    let hex = named.unwrap_or(color).trim_start_matches('#');
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!("Invalid colour: {color}"));
+    }
+
+    let expanded = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect::<String>() + "FF",
+        6 => format!("{hex}FF"),
+        8 => hex.to_string(),
+        _ => return Err(anyhow::anyhow!("Invalid colour: {color}")),
+    };
+    Ok(format!("#{}", expanded.to_uppercase()))
+}
+
+pub fn parse_edge_type(edge_type: &str) -> anyhow::Result<String> {
+    let normalized = edge_type.trim().to_uppercase().replace('-', "_");
+    EDGE_TYPES
+        .iter()
+        .find(|known| **known == normalized)
+        .map(|known| known.to_string())
+        .ok_or_else(|| anyhow::anyhow!("Invalid subtitle edge type: {edge_type}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("yellow").unwrap(), "#FFFF00FF");
+        assert_eq!(parse_color("#fff").unwrap(), "#FFFFFFFF");
+        assert_eq!(parse_color("#00000080").unwrap(), "#00000080");
+        assert_eq!(parse_color("123456").unwrap(), "#123456FF");
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("purple-ish").is_err());
+    }
+
+    #[test]
+    fn test_parse_edge_type() {
+        assert_eq!(parse_edge_type("drop-shadow").unwrap(), "DROP_SHADOW");
+        assert_eq!(parse_edge_type("Outline").unwrap(), "OUTLINE");
+        assert!(parse_edge_type("glow").is_err());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let style = TextTrackStyle {
+            font_scale: Some(1.5),
+            foreground_color: Some("#FFFF00FF".to_string()),
+            background_color: None,
+            edge_type: Some("OUTLINE".to_string()),
+            font_family: Some("serif".to_string()),
+        };
+        let json = style.to_json();
+
+        assert_eq!(json["fontScale"], 1.5);
+        assert_eq!(json["foregroundColor"], "#FFFF00FF");
+        assert!(json.get("backgroundColor").is_none());
+        assert_eq!(json["edgeType"], "OUTLINE");
+        assert_eq!(json["edgeColor"], EDGE_COLOR);
+        assert_eq!(json["fontGenericFamily"], "SERIF");
+
+        let custom = TextTrackStyle {
+            font_family: Some("Droid Sans".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(custom.to_json()["fontFamily"], "Droid Sans");
+    }
+
+    #[test]
+    fn test_adjustments() {
+        let mut style = TextTrackStyle::default();
+        assert!(style.is_empty());
+
+        style.scale_by(0.1);
+        assert_eq!(style.font_scale, Some(1.1));
+        style.scale_by(10.0);
+        assert_eq!(style.font_scale, Some(MAX_FONT_SCALE));
+
+        style.cycle_foreground();
+        assert_eq!(style.foreground_color.as_deref(), Some("#FFFFFFFF"));
+        style.cycle_foreground();
+        assert_eq!(style.foreground_color.as_deref(), Some("#FFFF00FF"));
+
+        style.edge_type = Some("DEPRESSED".to_string());
+        style.cycle_edge_type();
+        assert_eq!(style.edge_type.as_deref(), Some("NONE"));
+
+        style.font_family = Some("monospace".to_string());
+        style.cycle_font_family();
+        assert_eq!(style.font_family.as_deref(), Some("SERIF"));
+    }
+}
@@ -0,0 +1,559 @@
+use crate::matroska::{self, TextBlock};
+use crate::messenger::TextTrack;
+use crate::server::{Resource, Resources};
+use crate::settings::Settings;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::process::Command;
+
+/// Codecs the external extractor can convert to WebVTT.
+const EXTRACTOR_TEXT_CODECS: [&str; 7] =
+    ["subrip", "srt", "ass", "ssa", "webvtt", "mov_text", "text"];
+/// Used when a block carries no duration.
+const DEFAULT_CUE_DURATION_MS: i64 = 2000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cue {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+}
+
+/// A text track embedded in a media container.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmbeddedTrack {
+    /// Matroska track number, or stream index when using the external extractor.
+    pub number: u64,
+    pub codec: String,
+    pub language: Option<String>,
+    pub name: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+}
+
+/// A subtitle track ready to be served to the receiver.
+#[derive(Clone, Debug)]
+pub struct SubtitleTrack {
+    pub id: u32,
+    pub name: Option<String>,
+    pub language: Option<String>,
+    pub cues: Vec<Cue>,
+}
+
+impl SubtitleTrack {
+    /// Where the track of queue entry `index` is served.
+    pub fn path(&self, index: usize) -> String {
+        format!("/subtitles/{index}/{}.vtt", self.id)
+    }
+
+    pub fn text_track(&self, base_url: &str, index: usize) -> TextTrack {
+        TextTrack {
+            id: self.id,
+            url: format!("{base_url}{}", self.path(index)),
+            name: self.name.clone(),
+            language: self.language.clone(),
+        }
+    }
+}
+
+/// Parses `HH:MM:SS,mmm`, `HH:MM:SS.mmm` or `MM:SS.mmm` into milliseconds.
+/// Some files give fewer fraction digits, as in `00:00:01,5`.
+pub fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let timestamp = timestamp.trim().replace(',', ".");
+    let (clock, fraction) = timestamp.split_once('.')?;
+    let digits = fraction.get(..fraction.len().min(3))?;
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let millis = digits.parse::<i64>().ok()? * 10i64.pow(3 - digits.len() as u32);
+
+    let mut seconds = 0i64;
+    for part in clock.split(':') {
+        seconds = seconds * 60 + part.parse::<i64>().ok()?;
+    }
+    Some(seconds * 1000 + millis)
+}
+
+fn format_timestamp(ms: i64) -> String {
+    let ms = ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Parses SubRip or WebVTT content. Both formats are blocks separated by blank
+/// lines with a `start --> end` timing line, so one parser covers them.
+pub fn parse_cues(content: &str) -> Vec<Cue> {
+    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut cues = Vec::new();
+
+    for block in content.split("\n\n") {
+        let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+        let Some(timing) = lines.next() else {
+            continue;
+        };
+        let Some((start, end)) = timing.split_once("-->") else {
+            continue;
+        };
+        // WebVTT cue settings may follow the end timestamp.
+        let end = end.split_whitespace().next().unwrap_or_default();
+        if let (Some(start_ms), Some(end_ms)) = (parse_timestamp(start), parse_timestamp(end)) {
+            cues.push(Cue {
+                start_ms,
+                end_ms,
+                text: lines.collect::<Vec<_>>().join("\n"),
+            });
+        }
+    }
+    cues
+}
+
+/// Renders cues as WebVTT, shifted by `offset_ms`. Cues pushed entirely
+/// before zero are dropped.
+pub fn to_webvtt(cues: &[Cue], offset_ms: i64) -> String {
+    let mut output = String::from("WEBVTT\n\n");
+    for cue in cues {
+        let start = cue.start_ms + offset_ms;
+        let end = cue.end_ms + offset_ms;
+        if end <= 0 {
+            continue;
+        }
+        output.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_timestamp(start),
+            format_timestamp(end),
+            cue.text
+        ));
+    }
+    output
+}
+
+/// (Re)publishes every track of queue entry `index` as WebVTT shifted by
+/// `offset_ms`.
+pub fn serve_tracks(tracks: &[SubtitleTrack], index: usize, resources: &Resources, offset_ms: i64) {
+    let mut resources = resources.write().unwrap();
+    for track in tracks {
+        resources.insert(
+            track.path(index),
+            Resource::new("text/vtt", to_webvtt(&track.cues, offset_ms)),
+        );
+    }
+}
+
+/// The track after `active` in `tracks`, cycling through "off" (`None`).
+pub fn next_track(tracks: &[SubtitleTrack], active: Option<u32>) -> Option<u32> {
+    match active {
+        None => tracks.first().map(|track| track.id),
+        Some(id) => tracks
+            .iter()
+            .skip_while(|track| track.id != id)
+            .nth(1)
+            .map(|track| track.id),
+    }
+}
+
+pub fn read_subtitle_file(path: &Path) -> anyhow::Result<Vec<Cue>> {
+    let content = fs::read_to_string(path)?;
+    Ok(parse_cues(&content))
+}
+
+/// Strips the leading fields and override tags of a Matroska ASS/SSA event.
+fn ass_event_text(data: &str) -> String {
+    let text = data.splitn(9, ',').nth(8).unwrap_or(data);
+    let mut output = String::new();
+    let mut in_override = false;
+    for c in text.chars() {
+        match c {
+            '{' => in_override = true,
+            '}' if in_override => in_override = false,
+            _ if !in_override => output.push(c),
+            _ => {}
+        }
+    }
+    output
+        .replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+}
+
+fn block_to_cue(codec: &str, block: &TextBlock) -> Cue {
+    let data = String::from_utf8_lossy(&block.data);
+    let text = match codec {
+        "S_TEXT/ASS" | "S_TEXT/SSA" => ass_event_text(&data),
+        _ => data.trim_end_matches('\0').to_string(),
+    };
+    Cue {
+        start_ms: block.start_ms,
+        end_ms: block.start_ms + block.duration_ms.unwrap_or(DEFAULT_CUE_DURATION_MS),
+        text,
+    }
+}
+
+/// Lists the embedded text tracks of `path`. Matroska files are parsed
+/// directly; other containers need the external extractor (ffmpeg).
+pub fn list_embedded_tracks(
+    path: &Path,
+    extractor: Option<&str>,
+) -> anyhow::Result<Vec<EmbeddedTrack>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if extractor.is_none() && matroska::is_matroska(&mut reader)? {
+        let tracks = matroska::read_subtitle_tracks(&mut reader)?;
+        return Ok(tracks
+            .into_iter()
+            .filter(|track| track.is_text())
+            .map(|track| EmbeddedTrack {
+                number: track.number,
+                codec: track.codec_id,
+                language: track.language,
+                name: track.name,
+                default: track.default,
+                forced: track.forced,
+            })
+            .collect());
+    }
+
+    match extractor {
+        Some(extractor) => list_with_extractor(path, extractor),
+        None => Err(anyhow::anyhow!(
+            "{} is not a Matroska file; use --subtitle-extractor to read its subtitles",
+            path.display()
+        )),
+    }
+}
+
+/// Extracts the cues of each of `tracks`, in the same order.
+pub fn extract_embedded_tracks(
+    path: &Path,
+    tracks: &[EmbeddedTrack],
+    extractor: Option<&str>,
+) -> anyhow::Result<Vec<Vec<Cue>>> {
+    if let Some(extractor) = extractor {
+        return tracks
+            .iter()
+            .map(|track| extract_with_extractor(path, extractor, track.number))
+            .collect();
+    }
+
+    let numbers: Vec<u64> = tracks.iter().map(|track| track.number).collect();
+    let mut reader = BufReader::new(File::open(path)?);
+    let blocks = matroska::read_text_blocks(&mut reader, &numbers)?;
+    Ok(tracks
+        .iter()
+        .map(|track| {
+            blocks
+                .iter()
+                .filter(|block| block.track == track.number)
+                .map(|block| block_to_cue(&track.codec, block))
+                .collect()
+        })
+        .collect())
+}
+
+fn ffprobe_path(extractor: &str) -> String {
+    let path = Path::new(extractor);
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) if name.contains("ffmpeg") => path
+            .with_file_name(name.replace("ffmpeg", "ffprobe"))
+            .to_string_lossy()
+            .into_owned(),
+        _ => "ffprobe".to_string(),
+    }
+}
+
+fn list_with_extractor(path: &Path, extractor: &str) -> anyhow::Result<Vec<EmbeddedTrack>> {
+    let output = Command::new(ffprobe_path(extractor))
+        .args(["-v", "error", "-select_streams", "s", "-show_entries"])
+        .arg("stream=index,codec_name:stream_tags=language,title:stream_disposition=default,forced")
+        .args(["-of", "json"])
+        .arg(path)
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    parse_ffprobe_streams(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_ffprobe_streams(json: &str) -> anyhow::Result<Vec<EmbeddedTrack>> {
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    let streams = value["streams"].as_array().cloned().unwrap_or_default();
+    Ok(streams
+        .iter()
+        .filter(|stream| {
+            stream["codec_name"]
+                .as_str()
+                .is_some_and(|codec| EXTRACTOR_TEXT_CODECS.contains(&codec))
+        })
+        .filter_map(|stream| {
+            Some(EmbeddedTrack {
+                number: stream["index"].as_u64()?,
+                codec: stream["codec_name"].as_str()?.to_string(),
+                language: stream["tags"]["language"]
+                    .as_str()
+                    .filter(|language| *language != "und")
+                    .map(str::to_string),
+                name: stream["tags"]["title"].as_str().map(str::to_string),
+                default: stream["disposition"]["default"].as_u64() == Some(1),
+                forced: stream["disposition"]["forced"].as_u64() == Some(1),
+            })
+        })
+        .collect())
+}
+
+fn extract_with_extractor(path: &Path, extractor: &str, stream: u64) -> anyhow::Result<Vec<Cue>> {
+    let output = Command::new(extractor)
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args(["-map", &format!("0:{stream}"), "-f", "webvtt", "-"])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{extractor} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(parse_cues(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Resolves `all` or a comma-separated list of track numbers against `available`.
+pub fn select_tracks(
+    selection: &str,
+    available: &[EmbeddedTrack],
+) -> anyhow::Result<Vec<EmbeddedTrack>> {
+    if selection.trim().eq_ignore_ascii_case("all") {
+        return Ok(available.to_vec());
+    }
+
+    selection
+        .split(',')
+        .map(|part| {
+            let number: u64 = part
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid subtitle track number: {part}"))?;
+            available
+                .iter()
+                .find(|track| track.number == number)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("No embedded text track {number}"))
+        })
+        .collect()
+}
+
+/// Collects the sidecar subtitle file and the selected embedded tracks of
+/// `media`. The sidecar file only goes with the `first` item of a queue, the
+/// one it was given for. Other items play without embedded tracks they
+/// don't have, with a warning.
+pub fn load_subtitle_tracks(
+    media: &Path,
+    settings: &Settings,
+    first: bool,
+) -> anyhow::Result<Vec<SubtitleTrack>> {
+    let mut tracks = Vec::new();
+
+    if let Some(subtitles) = settings.subtitles.as_ref().filter(|_| first) {
+        let path = Path::new(subtitles);
+        tracks.push(SubtitleTrack {
+            id: 1,
+            name: path.file_stem().map(|s| s.to_string_lossy().into_owned()),
+            language: None,
+            cues: read_subtitle_file(path)?,
+        });
+    }
+
+    if let Some(selection) = &settings.subtitle_tracks {
+        let extractor = settings.subtitle_extractor.as_deref();
+        let embedded = list_embedded_tracks(media, extractor).and_then(|available| {
+            let selected = select_tracks(selection, &available)?;
+            let extracted = extract_embedded_tracks(media, &selected, extractor)?;
+            Ok(selected.into_iter().zip(extracted).collect::<Vec<_>>())
+        });
+        let embedded = match embedded {
+            Ok(embedded) => embedded,
+            Err(e) if !first => {
+                eprintln!("No subtitles for {}: {e}", media.display());
+                Vec::new()
+            }
+            Err(e) => return Err(e),
+        };
+        for (track, cues) in embedded {
+            tracks.push(SubtitleTrack {
+                id: tracks.len() as u32 + 1,
+                name: track.name.or(track.language.clone()),
+                language: track.language,
+                cues,
+            });
+        }
+    }
+
+    Ok(tracks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ResourceBody;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("00:01:02,345"), Some(62_345));
+        assert_eq!(parse_timestamp("01:00:00.000"), Some(3_600_000));
+        assert_eq!(parse_timestamp("02:03.500"), Some(123_500));
+        assert_eq!(parse_timestamp("00:00:01,5"), Some(1_500));
+        assert_eq!(parse_timestamp("00:00:01,05"), Some(1_050));
+        assert_eq!(parse_timestamp("00:00:01.1234"), Some(1_123));
+        assert_eq!(parse_timestamp("00:00:01,"), None);
+        assert_eq!(parse_timestamp("garbage"), None);
+    }
+
+    #[test]
+    fn test_parse_srt() {
+        let srt = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\nworld\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\n<i>Bye</i>\r\n";
+        let cues = parse_cues(srt);
+
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].start_ms, 1000);
+        assert_eq!(cues[0].end_ms, 2500);
+        assert_eq!(cues[0].text, "Hello\nworld");
+        assert_eq!(cues[1].text, "<i>Bye</i>");
+    }
+
+    #[test]
+    fn test_parse_webvtt_skips_header_and_settings() {
+        let vtt = "WEBVTT\n\nNOTE a comment\n\n00:01.000 --> 00:02.000 align:start\nHi\n";
+        let cues = parse_cues(vtt);
+
+        assert_eq!(
+            cues,
+            vec![Cue {
+                start_ms: 1000,
+                end_ms: 2000,
+                text: "Hi".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_to_webvtt_with_offset() {
+        let cues = vec![
+            Cue {
+                start_ms: 100,
+                end_ms: 300,
+                text: "gone".to_string(),
+            },
+            Cue {
+                start_ms: 3_599_900,
+                end_ms: 3_601_000,
+                text: "kept".to_string(),
+            },
+        ];
+        let vtt = to_webvtt(&cues, -400);
+
+        assert_eq!(vtt, "WEBVTT\n\n00:59:59.500 --> 01:00:00.600\nkept\n\n");
+    }
+
+    #[test]
+    fn test_ass_event_text() {
+        let event = "12,0,Default,,0,0,0,,{\\i1}Hello,{\\i0}\\Nthere\\hyou";
+        assert_eq!(ass_event_text(event), "Hello,\nthere you");
+    }
+
+    #[test]
+    fn test_block_to_cue_default_duration() {
+        let block = TextBlock {
+            track: 3,
+            start_ms: 1000,
+            duration_ms: None,
+            data: b"Line".to_vec(),
+        };
+        let cue = block_to_cue("S_TEXT/UTF8", &block);
+        assert_eq!(cue.end_ms, 1000 + DEFAULT_CUE_DURATION_MS);
+        assert_eq!(cue.text, "Line");
+    }
+
+    #[test]
+    fn test_parse_ffprobe_streams() {
+        let json = r#"{"streams": [
+            {"index": 2, "codec_name": "subrip", "disposition": {"default": 1, "forced": 0}, "tags": {"language": "eng", "title": "English"}},
+            {"index": 3, "codec_name": "hdmv_pgs_subtitle", "tags": {"language": "jpn"}},
+            {"index": 4, "codec_name": "mov_text", "tags": {"language": "und"}}
+        ]}"#;
+        let tracks = parse_ffprobe_streams(json).unwrap();
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].number, 2);
+        assert_eq!(tracks[0].name.as_deref(), Some("English"));
+        assert!(tracks[0].default);
+        assert_eq!(tracks[1].language, None);
+    }
+
+    #[test]
+    fn test_select_tracks() {
+        let track = |number| EmbeddedTrack {
+            number,
+            codec: "S_TEXT/UTF8".to_string(),
+            language: None,
+            name: None,
+            default: false,
+            forced: false,
+        };
+        let available = vec![track(2), track(5)];
+
+        assert_eq!(select_tracks("all", &available).unwrap().len(), 2);
+        assert_eq!(select_tracks("5", &available).unwrap()[0].number, 5);
+        assert!(select_tracks("3", &available).is_err());
+        assert!(select_tracks("x", &available).is_err());
+    }
+
+    #[test]
+    fn test_next_track_cycles_through_off() {
+        let track = |id| SubtitleTrack {
+            id,
+            name: None,
+            language: None,
+            cues: Vec::new(),
+        };
+        let tracks = vec![track(1), track(2)];
+
+        assert_eq!(next_track(&tracks, None), Some(1));
+        assert_eq!(next_track(&tracks, Some(1)), Some(2));
+        assert_eq!(next_track(&tracks, Some(2)), None);
+        assert_eq!(next_track(&[], None), None);
+    }
+
+    #[test]
+    fn test_serve_tracks_applies_offset() {
+        let tracks = vec![SubtitleTrack {
+            id: 1,
+            name: None,
+            language: None,
+            cues: vec![Cue {
+                start_ms: 1000,
+                end_ms: 2000,
+                text: "Hi".to_string(),
+            }],
+        }];
+        let resources = Resources::default();
+        serve_tracks(&tracks, 3, &resources, 100);
+
+        let resource = resources.read().unwrap()["/subtitles/3/1.vtt"].clone();
+        assert_eq!(resource.content_type, "text/vtt");
+        assert_eq!(
+            resource.body,
+            ResourceBody::Bytes("WEBVTT\n\n00:00:01.100 --> 00:00:02.100\nHi\n\n".into())
+        );
+    }
+
+    #[test]
+    fn test_ffprobe_path() {
+        assert_eq!(ffprobe_path("ffmpeg"), "ffprobe");
+        assert_eq!(ffprobe_path("/opt/bin/ffmpeg"), "/opt/bin/ffprobe");
+    }
+}
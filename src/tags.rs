@@ -0,0 +1,634 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use id3::frame::PictureType;
+use id3::TagLike;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const FLAC_VORBIS_COMMENT: u8 = 4;
+const FLAC_PICTURE: u8 = 6;
+/// Picture type used by ID3 and FLAC for the front cover.
+const FRONT_COVER: u32 = 3;
+/// Atoms whose children hold the iTunes metadata list.
+const MP4_PATH: [&[u8; 4]; 4] = [b"moov", b"udta", b"meta", b"ilst"];
+/// Upper bound on a metadata container we are willing to read into memory.
+const MAX_METADATA_SIZE: u64 = 64 * 1024 * 1024;
+
+/// An embedded picture.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Picture {
+    pub mime_type: String,
+    pub front_cover: bool,
+    pub data: Vec<u8>,
+}
+
+/// Tags read from an audio file, normalised across tag formats.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AudioTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub date: Option<String>,
+    pub track: Option<u32>,
+    pub total_tracks: Option<u32>,
+    pub disc: Option<u32>,
+    pub total_discs: Option<u32>,
+    pub comment: Option<String>,
+    pub pictures: Vec<Picture>,
+}
+
+impl AudioTags {
+    /// The front cover, or the first picture when none is marked as such.
+    pub fn cover(&self) -> Option<&Picture> {
+        self.pictures
+            .iter()
+            .find(|picture| picture.front_cover)
+            .or_else(|| self.pictures.first())
+    }
+
+    /// Applies a Vorbis comment (`KEY=value`) as found in FLAC, Ogg and Opus.
+    fn apply_vorbis_comment(&mut self, key: &str, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        match key.to_uppercase().as_str() {
+            "TITLE" => set_once(&mut self.title, value),
+            "ARTIST" => set_once(&mut self.artist, value),
+            "ALBUM" => set_once(&mut self.album, value),
+            "ALBUMARTIST" | "ALBUM ARTIST" | "ALBUM_ARTIST" => {
+                set_once(&mut self.album_artist, value)
+            }
+            "GENRE" => set_once(&mut self.genre, value),
+            "DATE" | "YEAR" => set_once(&mut self.date, value),
+            "COMMENT" | "DESCRIPTION" => set_once(&mut self.comment, value),
+            "TRACKNUMBER" => {
+                let (number, total) = parse_number_pair(value);
+                self.track = self.track.or(number);
+                self.total_tracks = self.total_tracks.or(total);
+            }
+            "TRACKTOTAL" | "TOTALTRACKS" => self.total_tracks = value.parse().ok(),
+            "DISCNUMBER" => {
+                let (number, total) = parse_number_pair(value);
+                self.disc = self.disc.or(number);
+                self.total_discs = self.total_discs.or(total);
+            }
+            "DISCTOTAL" | "TOTALDISCS" => self.total_discs = value.parse().ok(),
+            "METADATA_BLOCK_PICTURE" => {
+                if let Some(picture) = BASE64
+                    .decode(value)
+                    .ok()
+                    .and_then(|data| parse_flac_picture(&data))
+                {
+                    self.pictures.push(picture);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn set_once(field: &mut Option<String>, value: &str) {
+    if field.is_none() {
+        *field = Some(value.to_string());
+    }
+}
+
+/// Parses `3` or `3/12` into the number and the optional total.
+fn parse_number_pair(value: &str) -> (Option<u32>, Option<u32>) {
+    let mut parts = value.splitn(2, '/');
+    let number = parts.next().and_then(|part| part.trim().parse().ok());
+    let total = parts.next().and_then(|part| part.trim().parse().ok());
+    (number, total)
+}
+
+/// Reads the tags of an audio file, picking the parser from the file's
+/// signature: FLAC, Ogg (Vorbis and Opus), MP4/M4A or ID3v1/v2.
+pub fn read_tags(path: &Path) -> anyhow::Result<AudioTags> {
+    read_tags_from(BufReader::new(File::open(path)?))
+}
+
+pub fn read_tags_from<R: Read + Seek>(mut reader: R) -> anyhow::Result<AudioTags> {
+    let mut magic = [0u8; 8];
+    let read = read_up_to(&mut reader, &mut magic)?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    match &magic[..read] {
+        [b'f', b'L', b'a', b'C', ..] => read_flac(reader),
+        [b'O', b'g', b'g', b'S', ..] => read_ogg(reader),
+        [_, _, _, _, b'f', b't', b'y', b'p'] => read_mp4(reader),
+        _ => read_id3(reader),
+    }
+}
+
+fn read_up_to(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn read_id3<R: Read + Seek>(reader: R) -> anyhow::Result<AudioTags> {
+    let tag = id3::v1v2::read_from(reader)?;
+    let comment = tag.comments().next().map(|comment| comment.text.clone());
+    Ok(AudioTags {
+        title: tag.title().map(str::to_string),
+        artist: tag.artist().map(str::to_string),
+        album: tag.album().map(str::to_string),
+        album_artist: tag.album_artist().map(str::to_string),
+        genre: tag.genre_parsed().map(|genre| genre.into_owned()),
+        date: tag
+            .date_recorded()
+            .map(|date| date.to_string())
+            .or_else(|| tag.year().map(|year| year.to_string())),
+        track: tag.track(),
+        total_tracks: tag.total_tracks(),
+        disc: tag.disc(),
+        total_discs: tag.total_discs(),
+        comment,
+        pictures: tag
+            .pictures()
+            .map(|picture| Picture {
+                mime_type: picture.mime_type.clone(),
+                front_cover: picture.picture_type == PictureType::CoverFront,
+                data: picture.data.clone(),
+            })
+            .collect(),
+    })
+}
+
+fn read_flac<R: Read + Seek>(mut reader: R) -> anyhow::Result<AudioTags> {
+    let mut tags = AudioTags::default();
+    reader.seek(SeekFrom::Start(4))?;
+
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header)?;
+        let last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+
+        match block_type {
+            FLAC_VORBIS_COMMENT | FLAC_PICTURE => {
+                let mut block = vec![0u8; length as usize];
+                reader.read_exact(&mut block)?;
+                if block_type == FLAC_VORBIS_COMMENT {
+                    for (key, value) in parse_vorbis_comments(&block)? {
+                        tags.apply_vorbis_comment(&key, &value);
+                    }
+                } else if let Some(picture) = parse_flac_picture(&block) {
+                    tags.pictures.push(picture);
+                }
+            }
+            _ => {
+                reader.seek(SeekFrom::Current(i64::from(length)))?;
+            }
+        }
+        if last {
+            return Ok(tags);
+        }
+    }
+}
+
+/// Reads the second packet of the first logical stream, which holds the
+/// comment header for both Vorbis and Opus.
+fn read_ogg<R: Read>(mut reader: R) -> anyhow::Result<AudioTags> {
+    let mut packets: Vec<Vec<u8>> = vec![Vec::new()];
+    let mut serial = None;
+
+    while packets.len() < 3 {
+        let mut header = [0u8; 27];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != b"OggS" {
+            return Err(anyhow::anyhow!("Invalid Ogg page"));
+        }
+        let page_serial = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+        let mut lacing = vec![0u8; usize::from(header[26])];
+        reader.read_exact(&mut lacing)?;
+        let mut body = vec![0u8; lacing.iter().map(|&n| usize::from(n)).sum()];
+        reader.read_exact(&mut body)?;
+
+        if *serial.get_or_insert(page_serial) != page_serial {
+            continue;
+        }
+        let mut offset = 0;
+        for &segment in &lacing {
+            let segment = usize::from(segment);
+            let packet = packets.last_mut().expect("always one open packet");
+            packet.extend_from_slice(&body[offset..offset + segment]);
+            if packet.len() as u64 > MAX_METADATA_SIZE {
+                return Err(anyhow::anyhow!("Ogg comment header too large"));
+            }
+            offset += segment;
+            if segment < 255 {
+                packets.push(Vec::new());
+            }
+        }
+    }
+
+    let comments = if let Some(rest) = packets[1].strip_prefix(b"\x03vorbis") {
+        rest
+    } else if let Some(rest) = packets[1].strip_prefix(b"OpusTags") {
+        rest
+    } else {
+        return Err(anyhow::anyhow!("Unsupported Ogg stream"));
+    };
+
+    let mut tags = AudioTags::default();
+    for (key, value) in parse_vorbis_comments(comments)? {
+        tags.apply_vorbis_comment(&key, &value);
+    }
+    Ok(tags)
+}
+
+/// Parses a Vorbis comment block into key/value pairs.
+fn parse_vorbis_comments(data: &[u8]) -> anyhow::Result<Vec<(String, String)>> {
+    let mut cursor = ByteCursor::new(data);
+    let vendor_length = cursor.u32_le()? as usize;
+    cursor.take(vendor_length)?;
+
+    let count = cursor.u32_le()?;
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let length = cursor.u32_le()? as usize;
+        let comment = String::from_utf8_lossy(cursor.take(length)?);
+        if let Some((key, value)) = comment.split_once('=') {
+            comments.push((key.to_string(), value.to_string()));
+        }
+    }
+    Ok(comments)
+}
+
+/// Parses a FLAC `PICTURE` block, also used base64-encoded in Vorbis comments.
+fn parse_flac_picture(data: &[u8]) -> Option<Picture> {
+    let mut cursor = ByteCursor::new(data);
+    let picture_type = cursor.u32_be().ok()?;
+    let mime_length = cursor.u32_be().ok()? as usize;
+    let mime_type = String::from_utf8_lossy(cursor.take(mime_length).ok()?).to_string();
+    let description_length = cursor.u32_be().ok()? as usize;
+    // Description, then width, height, colour depth and palette size.
+    cursor.take(description_length + 16).ok()?;
+    let data_length = cursor.u32_be().ok()? as usize;
+    Some(Picture {
+        mime_type,
+        front_cover: picture_type == FRONT_COVER,
+        data: cursor.take(data_length).ok()?.to_vec(),
+    })
+}
+
+fn read_mp4<R: Read + Seek>(mut reader: R) -> anyhow::Result<AudioTags> {
+    // Only `moov` is read into memory; `mdat` can be gigabytes.
+    let moov = loop {
+        let Some((kind, size)) = read_atom_header(&mut reader)? else {
+            return Ok(AudioTags::default());
+        };
+        if &kind == MP4_PATH[0] {
+            if size > MAX_METADATA_SIZE {
+                return Err(anyhow::anyhow!("MP4 moov atom too large"));
+            }
+            let mut moov = vec![0u8; size as usize];
+            reader.read_exact(&mut moov)?;
+            break moov;
+        }
+        match size {
+            u64::MAX => return Ok(AudioTags::default()),
+            _ => reader.seek(SeekFrom::Current(size as i64))?,
+        };
+    };
+
+    let mut ilst: &[u8] = &moov;
+    for name in &MP4_PATH[1..] {
+        let Some(child) = find_atom(ilst, name) else {
+            return Ok(AudioTags::default());
+        };
+        // `meta` is a full atom: version and flags come before its children.
+        ilst = if *name == b"meta" {
+            child.get(4..).unwrap_or_default()
+        } else {
+            child
+        };
+    }
+
+    let mut tags = AudioTags::default();
+    for (kind, item) in atoms(ilst) {
+        for (data_kind, data) in atoms(item) {
+            if &data_kind != b"data" || data.len() < 8 {
+                continue;
+            }
+            apply_mp4_item(&mut tags, &kind, u32_be(&data[..4]), &data[8..]);
+        }
+    }
+    Ok(tags)
+}
+
+fn apply_mp4_item(tags: &mut AudioTags, kind: &[u8; 4], data_type: u32, value: &[u8]) {
+    let text = || Some(String::from_utf8_lossy(value).trim().to_string()).filter(|v| !v.is_empty());
+    match kind {
+        b"\xa9nam" => tags.title = tags.title.take().or_else(text),
+        b"\xa9ART" => tags.artist = tags.artist.take().or_else(text),
+        b"aART" => tags.album_artist = tags.album_artist.take().or_else(text),
+        b"\xa9alb" => tags.album = tags.album.take().or_else(text),
+        b"\xa9gen" => tags.genre = tags.genre.take().or_else(text),
+        b"\xa9day" => tags.date = tags.date.take().or_else(text),
+        b"\xa9cmt" => tags.comment = tags.comment.take().or_else(text),
+        b"trkn" | b"disk" if value.len() >= 6 => {
+            let number = u32::from(u16::from_be_bytes([value[2], value[3]]));
+            let total = u32::from(u16::from_be_bytes([value[4], value[5]]));
+            let (number, total) = (
+                Some(number).filter(|n| *n > 0),
+                Some(total).filter(|n| *n > 0),
+            );
+            if kind == b"trkn" {
+                (tags.track, tags.total_tracks) = (number, total);
+            } else {
+                (tags.disc, tags.total_discs) = (number, total);
+            }
+        }
+        b"covr" => tags.pictures.push(Picture {
+            mime_type: match data_type {
+                14 => "image/png",
+                _ => "image/jpeg",
+            }
+            .to_string(),
+            front_cover: true,
+            data: value.to_vec(),
+        }),
+        _ => {}
+    }
+}
+
+/// Reads an atom header and returns its type and the size of its body.
+/// A body running to the end of the file is reported as `u64::MAX`.
+fn read_atom_header(reader: &mut impl Read) -> anyhow::Result<Option<([u8; 4], u64)>> {
+    let mut header = [0u8; 8];
+    if read_up_to(reader, &mut header)? < header.len() {
+        return Ok(None);
+    }
+    let kind = [header[4], header[5], header[6], header[7]];
+    let size = match u32_be(&header[..4]) {
+        0 => u64::MAX,
+        1 => {
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large)?;
+            u64::from_be_bytes(large)
+                .checked_sub(16)
+                .ok_or_else(|| anyhow::anyhow!("Invalid MP4 atom size"))?
+        }
+        size => u64::from(size)
+            .checked_sub(8)
+            .ok_or_else(|| anyhow::anyhow!("Invalid MP4 atom size"))?,
+    };
+    Ok(Some((kind, size)))
+}
+
+/// Iterates over the atoms in `data`, stopping at the first malformed one.
+fn atoms(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let mut header = data;
+        let (kind, size) = read_atom_header(&mut header).ok()??;
+        let header_length = data.len() - header.len();
+        let size = usize::try_from(size).map_or(header.len(), |size| size.min(header.len()));
+        let body = &data[header_length..header_length + size];
+        data = &data[header_length + size..];
+        Some((kind, body))
+    })
+}
+
+fn find_atom<'a>(data: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+    atoms(data)
+        .find(|(kind, _)| kind == name)
+        .map(|(_, body)| body)
+}
+
+fn u32_be(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Bounds-checked reads over an in-memory block.
+struct ByteCursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteCursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        ByteCursor { data }
+    }
+
+    fn take(&mut self, length: usize) -> anyhow::Result<&'a [u8]> {
+        if length > self.data.len() {
+            return Err(anyhow::anyhow!("Truncated tag block"));
+        }
+        let (taken, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u32_le(&mut self) -> anyhow::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u32_be(&mut self) -> anyhow::Result<u32> {
+        Ok(u32_be(self.take(4)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn vorbis_comments(comments: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&6u32.to_le_bytes());
+        data.extend_from_slice(b"vendor");
+        data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for comment in comments {
+            data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            data.extend_from_slice(comment.as_bytes());
+        }
+        data
+    }
+
+    fn flac_picture(picture_type: u32, mime_type: &str, image: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&picture_type.to_be_bytes());
+        data.extend_from_slice(&(mime_type.len() as u32).to_be_bytes());
+        data.extend_from_slice(mime_type.as_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&(image.len() as u32).to_be_bytes());
+        data.extend_from_slice(image);
+        data
+    }
+
+    fn flac_block(block_type: u8, last: bool, body: &[u8]) -> Vec<u8> {
+        let mut block = vec![block_type | if last { 0x80 } else { 0 }];
+        block.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        block.extend_from_slice(body);
+        block
+    }
+
+    fn ogg_page(serial: u32, packets: &[&[u8]]) -> Vec<u8> {
+        let mut lacing = Vec::new();
+        let mut body = Vec::new();
+        for packet in packets {
+            lacing.extend(std::iter::repeat_n(255, packet.len() / 255));
+            lacing.push((packet.len() % 255) as u8);
+            body.extend_from_slice(packet);
+        }
+        let mut page = b"OggS\0\0".to_vec();
+        page.extend_from_slice(&[0u8; 8]);
+        page.extend_from_slice(&serial.to_le_bytes());
+        page.extend_from_slice(&[0u8; 8]);
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(&lacing);
+        page.extend_from_slice(&body);
+        page
+    }
+
+    fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(body);
+        data
+    }
+
+    fn mp4_item(kind: &[u8; 4], data_type: u32, value: &[u8]) -> Vec<u8> {
+        let mut data = data_type.to_be_bytes().to_vec();
+        data.extend_from_slice(&[0u8; 4]);
+        data.extend_from_slice(value);
+        atom(kind, &atom(b"data", &data))
+    }
+
+    #[test]
+    fn test_parse_number_pair() {
+        assert_eq!(parse_number_pair("3/12"), (Some(3), Some(12)));
+        assert_eq!(parse_number_pair("7"), (Some(7), None));
+        assert_eq!(parse_number_pair("x"), (None, None));
+    }
+
+    #[test]
+    fn test_read_flac() {
+        let comments = vorbis_comments(&[
+            "TITLE=Song",
+            "artist=Artist",
+            "ALBUM=Album",
+            "TRACKNUMBER=2/10",
+            "DISCNUMBER=1",
+            "DATE=2004-05-01",
+        ]);
+        let mut file = b"fLaC".to_vec();
+        file.extend(flac_block(0, false, &[0u8; 34]));
+        file.extend(flac_block(FLAC_VORBIS_COMMENT, false, &comments));
+        file.extend(flac_block(
+            FLAC_PICTURE,
+            true,
+            &flac_picture(FRONT_COVER, "image/png", b"png"),
+        ));
+
+        let tags = read_tags_from(Cursor::new(file)).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.artist.as_deref(), Some("Artist"));
+        assert_eq!(tags.album.as_deref(), Some("Album"));
+        assert_eq!((tags.track, tags.total_tracks), (Some(2), Some(10)));
+        assert_eq!((tags.disc, tags.total_discs), (Some(1), None));
+        assert_eq!(tags.date.as_deref(), Some("2004-05-01"));
+        let cover = tags.cover().unwrap();
+        assert_eq!(cover.mime_type, "image/png");
+        assert!(cover.front_cover);
+        assert_eq!(cover.data, b"png");
+    }
+
+    #[test]
+    fn test_read_opus() {
+        let picture = BASE64.encode(flac_picture(FRONT_COVER, "image/jpeg", b"jpg"));
+        let mut tags_packet = b"OpusTags".to_vec();
+        tags_packet.extend(vorbis_comments(&[
+            "TITLE=Opus Song",
+            &format!("METADATA_BLOCK_PICTURE={picture}"),
+            &format!("COMMENT={}", "x".repeat(300)),
+        ]));
+
+        let mut file = ogg_page(7, &[b"OpusHead\x01\x02"]);
+        // A page from another logical stream must be ignored.
+        file.extend(ogg_page(8, &[b"\x03vorbis"]));
+        file.extend(ogg_page(7, &[&tags_packet]));
+
+        let tags = read_tags_from(Cursor::new(file)).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Opus Song"));
+        assert_eq!(tags.comment.as_deref().map(str::len), Some(300));
+        assert_eq!(tags.cover().unwrap().data, b"jpg");
+    }
+
+    #[test]
+    fn test_read_vorbis() {
+        let mut comment_packet = b"\x03vorbis".to_vec();
+        comment_packet.extend(vorbis_comments(&["ALBUMARTIST=Various", "GENRE=Jazz"]));
+        comment_packet.push(1);
+        let file = ogg_page(1, &[b"\x01vorbis", &comment_packet]);
+
+        let tags = read_tags_from(Cursor::new(file)).unwrap();
+        assert_eq!(tags.album_artist.as_deref(), Some("Various"));
+        assert_eq!(tags.genre.as_deref(), Some("Jazz"));
+    }
+
+    #[test]
+    fn test_read_mp4() {
+        let mut ilst = mp4_item(b"\xa9nam", 1, b"M4A Song");
+        ilst.extend(mp4_item(b"\xa9ART", 1, b"Artist"));
+        ilst.extend(mp4_item(b"aART", 1, b"Band"));
+        ilst.extend(mp4_item(b"trkn", 0, &[0, 0, 0, 4, 0, 9, 0, 0]));
+        ilst.extend(mp4_item(b"disk", 0, &[0, 0, 0, 2, 0, 2]));
+        ilst.extend(mp4_item(b"\xa9day", 1, b"1999"));
+        ilst.extend(mp4_item(b"covr", 14, b"png"));
+        let mut meta = vec![0u8; 4];
+        meta.extend(atom(b"hdlr", &[0u8; 25]));
+        meta.extend(atom(b"ilst", &ilst));
+
+        let mut file = atom(b"ftyp", b"M4A \0\0\0\0");
+        file.extend(atom(b"mdat", &[0u8; 64]));
+        file.extend(atom(
+            b"moov",
+            &[
+                atom(b"mvhd", &[0u8; 100]),
+                atom(b"udta", &atom(b"meta", &meta)),
+            ]
+            .concat(),
+        ));
+
+        let tags = read_tags_from(Cursor::new(file)).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("M4A Song"));
+        assert_eq!(tags.artist.as_deref(), Some("Artist"));
+        assert_eq!(tags.album_artist.as_deref(), Some("Band"));
+        assert_eq!((tags.track, tags.total_tracks), (Some(4), Some(9)));
+        assert_eq!((tags.disc, tags.total_discs), (Some(2), Some(2)));
+        assert_eq!(tags.date.as_deref(), Some("1999"));
+        assert_eq!(tags.cover().unwrap().mime_type, "image/png");
+    }
+
+    #[test]
+    fn test_read_id3() {
+        let mut tag = id3::Tag::new();
+        tag.set_title("ID3 Song");
+        tag.set_track(5);
+        tag.set_year(2010);
+        let mut file = Vec::new();
+        tag.write_to(&mut file, id3::Version::Id3v24).unwrap();
+        file.extend_from_slice(&[0u8; 32]);
+
+        let tags = read_tags_from(Cursor::new(file)).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("ID3 Song"));
+        assert_eq!(tags.track, Some(5));
+        assert_eq!(tags.date.as_deref(), Some("2010"));
+
+        assert!(read_tags_from(Cursor::new(vec![0u8; 16])).is_err());
+    }
+}
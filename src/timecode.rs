@@ -0,0 +1,195 @@
+/// A point in the media, as typed by the user.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeSpec {
+    Secs(f64),
+    /// A share of the duration, 0 to 100.
+    Percent(f64),
+}
+
+impl TimeSpec {
+    /// Seconds into media of `duration` seconds; percentages need the
+    /// duration.
+    pub fn resolve(self, duration: Option<f64>) -> Option<f64> {
+        match self {
+            TimeSpec::Secs(secs) => Some(secs),
+            TimeSpec::Percent(percent) => duration.map(|duration| duration * percent / 100.0),
+        }
+    }
+}
+
+/// Parses `90`, `90.5`, `1:30`, `01:02:03`, `1h2m3s` (also `1h30`, `2m`,
+/// `45s`) or `25%`.
+pub fn parse_time(input: &str) -> anyhow::Result<TimeSpec> {
+    let input = input.trim();
+    parse(input)
+        .filter(|spec| match spec {
+            TimeSpec::Secs(secs) => secs.is_finite() && *secs >= 0.0,
+            TimeSpec::Percent(percent) => (0.0..=100.0).contains(percent),
+        })
+        .ok_or_else(|| {
+            anyhow::anyhow!("Invalid time: {input:?} (try 90, 1:30, 01:02:03, 1h2m3s or 25%)")
+        })
+}
+
+/// Like [`parse_time`], for times that must be known before the media's
+/// duration is.
+pub fn parse_secs(input: &str) -> anyhow::Result<f64> {
+    match parse_time(input)? {
+        TimeSpec::Secs(secs) => Ok(secs),
+        TimeSpec::Percent(_) => Err(anyhow::anyhow!(
+            "Invalid time: {:?} (percentages aren't allowed here)",
+            input.trim()
+        )),
+    }
+}
+
+fn parse(input: &str) -> Option<TimeSpec> {
+    if let Some(percent) = input.strip_suffix('%') {
+        return Some(TimeSpec::Percent(number(percent)?));
+    }
+    if input.contains(':') {
+        return clock(input).map(TimeSpec::Secs);
+    }
+    if input.contains(['h', 'm', 's']) {
+        return units(input).map(TimeSpec::Secs);
+    }
+    number(input).map(TimeSpec::Secs)
+}
+
+/// A plain decimal number; no signs, exponents or words like "inf".
+fn number(input: &str) -> Option<f64> {
+    let valid = !input.is_empty()
+        && input.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && input.chars().any(|c| c.is_ascii_digit());
+    valid.then(|| input.parse().ok()).flatten()
+}
+
+/// `m:ss` or `h:mm:ss`, with fractional seconds allowed.
+fn clock(input: &str) -> Option<f64> {
+    let parts: Vec<&str> = input.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    let (seconds, rest) = parts.split_last()?;
+    let seconds = number(seconds)?;
+    let whole = rest
+        .iter()
+        .map(|part| number(part).filter(|_| !part.contains('.')))
+        .collect::<Option<Vec<f64>>>()?;
+    if seconds >= 60.0 || (whole.len() == 2 && whole[1] >= 60.0) {
+        return None;
+    }
+    Some(whole.iter().fold(0.0, |total, part| (total + part) * 60.0) + seconds)
+}
+
+/// `1h2m3s`: each unit at most once and in that order. A trailing number
+/// without a unit counts in the next unit down, as in `1h30` or `2m15`.
+fn units(input: &str) -> Option<f64> {
+    const UNITS: [(char, f64); 3] = [('h', 3600.0), ('m', 60.0), ('s', 1.0)];
+    let mut total = 0.0;
+    let mut next_unit = 0;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let value = number(&rest[..end])?;
+        let unit = match rest[end..].chars().next() {
+            Some(c) => UNITS[next_unit..].iter().position(|&(unit, _)| unit == c)? + next_unit,
+            None if next_unit > 0 && next_unit < UNITS.len() => next_unit,
+            None => return None,
+        };
+        total += value * UNITS[unit].1;
+        next_unit = unit + 1;
+        rest = rest[end..].get(1..).unwrap_or_default();
+    }
+    Some(total)
+}
+
+/// `h:mm:ss`, or `m:ss` under an hour.
+pub fn format_time(secs: f64) -> String {
+    let secs = secs.max(0.0).round() as u64;
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(input: &str) -> f64 {
+        match parse_time(input).unwrap() {
+            TimeSpec::Secs(secs) => secs,
+            spec => panic!("{input}: {spec:?}"),
+        }
+    }
+
+    #[test]
+    fn test_plain_seconds() {
+        assert_eq!(secs("90"), 90.0);
+        assert_eq!(secs(" 90.5 "), 90.5);
+        assert_eq!(secs("0"), 0.0);
+    }
+
+    #[test]
+    fn test_clock_times() {
+        assert_eq!(secs("1:30"), 90.0);
+        assert_eq!(secs("0:05.5"), 5.5);
+        assert_eq!(secs("01:02:03"), 3723.0);
+        assert_eq!(secs("100:00"), 6000.0);
+        assert_eq!(secs("2:00:00.25"), 7200.25);
+    }
+
+    #[test]
+    fn test_unit_times() {
+        assert_eq!(secs("1h2m3s"), 3723.0);
+        assert_eq!(secs("1h"), 3600.0);
+        assert_eq!(secs("2m"), 120.0);
+        assert_eq!(secs("45s"), 45.0);
+        assert_eq!(secs("1.5m"), 90.0);
+        assert_eq!(secs("1h30"), 5400.0);
+        assert_eq!(secs("2m15"), 135.0);
+        assert_eq!(secs("1h5s"), 3605.0);
+    }
+
+    #[test]
+    fn test_percentages() {
+        assert_eq!(parse_time("25%").unwrap(), TimeSpec::Percent(25.0));
+        assert_eq!(parse_time("12.5%").unwrap(), TimeSpec::Percent(12.5));
+        assert_eq!(TimeSpec::Percent(25.0).resolve(Some(200.0)), Some(50.0));
+        assert_eq!(TimeSpec::Percent(25.0).resolve(None), None);
+        assert_eq!(TimeSpec::Secs(3.0).resolve(None), Some(3.0));
+    }
+
+    #[test]
+    fn test_parse_secs() {
+        assert_eq!(parse_secs("1:30").unwrap(), 90.0);
+        assert!(parse_secs("25%").is_err());
+        assert!(parse_secs("x").is_err());
+    }
+
+    #[test]
+    fn test_invalid_times() {
+        for input in [
+            "", " ", "abc", "-5", "+5", "1e3", "inf", "NaN", ".", "1:2:3:4", "1:60", "1:60:00",
+            ":30", "1:", "1::2", "1:-2", "1:+2", "120%", "-1%", "%", "1s2m", "1m1m", "3x",
+            "1h2m3s4", "5s6", "1 30",
+        ] {
+            assert!(parse_time(input).is_err(), "{input:?} should not parse");
+        }
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0.0), "0:00");
+        assert_eq!(format_time(90.4), "1:30");
+        assert_eq!(format_time(3723.0), "1:02:03");
+        for text in ["0:07", "12:34", "1:02:03"] {
+            assert_eq!(format_time(secs(text)), text);
+        }
+    }
+}
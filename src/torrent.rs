@@ -0,0 +1,103 @@
+use crate::metadata;
+use crate::queue::{MediaSource, QueueEntry};
+use crate::server::{MediaReader, StreamSource};
+use librqbit::{AddTorrent, AddTorrentOptions, ManagedTorrent, Session};
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// Where torrent downloads are kept.
+const DOWNLOAD_DIR: &str = "gemini_castnow";
+
+/// One file of a torrent, downloaded in the order it is read.
+pub struct TorrentStream {
+    // Keeps the session, and with it the download, alive while the file is
+    // queued.
+    _session: Arc<Session>,
+    handle: Arc<ManagedTorrent>,
+    file_id: usize,
+    len: u64,
+}
+
+impl fmt::Debug for TorrentStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TorrentStream")
+            .field("file_id", &self.file_id)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl StreamSource for TorrentStream {
+    fn size(&self) -> u64 {
+        self.len
+    }
+
+    fn open(&self) -> io::Result<Box<dyn MediaReader>> {
+        let stream = self
+            .handle
+            .clone()
+            .stream(self.file_id)
+            .map_err(io::Error::other)?;
+        Ok(Box::new(stream))
+    }
+}
+
+/// Adds a torrent (a magnet link, a `.torrent` URL or file) and returns its
+/// audio and video files as queue entries, in path order. Only those files
+/// are downloaded.
+pub async fn media_entries(source: &str) -> anyhow::Result<Vec<QueueEntry>> {
+    let session = Session::new(std::env::temp_dir().join(DOWNLOAD_DIR)).await?;
+    let options = AddTorrentOptions {
+        overwrite: true,
+        // Started once the files to download are known.
+        paused: true,
+        ..Default::default()
+    };
+    let handle = session
+        .add_torrent(AddTorrent::from_cli_argument(source)?, Some(options))
+        .await?
+        .into_handle()
+        .ok_or_else(|| anyhow::anyhow!("{source} was not added"))?;
+    handle.wait_until_initialized().await?;
+
+    let mut files = handle.with_metadata(|metadata| {
+        metadata
+            .file_infos
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| {
+                !file.attrs.padding
+                    && metadata::content_type_for_path(&file.relative_filename).is_some()
+            })
+            .map(|(id, file)| (id, file.relative_filename.clone(), file.len))
+            .collect::<Vec<_>>()
+    })?;
+    if files.is_empty() {
+        return Err(anyhow::anyhow!("No audio or video files in {source}"));
+    }
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let only_files: HashSet<usize> = files.iter().map(|(id, _, _)| *id).collect();
+    session.update_only_files(&handle, &only_files).await?;
+    session.unpause(&handle).await?;
+
+    Ok(files
+        .into_iter()
+        .map(|(file_id, path, len)| {
+            let name = path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .to_string();
+            let source = Arc::new(TorrentStream {
+                _session: session.clone(),
+                handle: handle.clone(),
+                file_id,
+                len,
+            });
+            QueueEntry::new(MediaSource::Stream { name, source })
+        })
+        .collect())
+}
@@ -5,10 +5,26 @@ pub fn merge_settings(cli: Settings, file_and_env: Settings) -> Settings {
         address: cli.address.or(file_and_env.address),
         device: cli.device.or(file_and_env.device),
         subtitles: cli.subtitles.or(file_and_env.subtitles),
+        subtitle_tracks: cli.subtitle_tracks.or(file_and_env.subtitle_tracks),
+        subtitle_extractor: cli.subtitle_extractor.or(file_and_env.subtitle_extractor),
+        subtitle_scale: cli.subtitle_scale.or(file_and_env.subtitle_scale),
+        subtitle_color: cli.subtitle_color.or(file_and_env.subtitle_color),
+        subtitle_background: cli.subtitle_background.or(file_and_env.subtitle_background),
+        subtitle_edge: cli.subtitle_edge.or(file_and_env.subtitle_edge),
+        subtitle_font: cli.subtitle_font.or(file_and_env.subtitle_font),
         no_search: cli.no_search || file_and_env.no_search,
         loop_playback: cli.loop_playback || file_and_env.loop_playback,
         shuffle: cli.shuffle || file_and_env.shuffle,
+        shuffle_mode: cli.shuffle_mode.or(file_and_env.shuffle_mode),
+        shuffle_seed: cli.shuffle_seed.or(file_and_env.shuffle_seed),
+        native_queue: cli.native_queue || file_and_env.native_queue,
+        save_playlist: cli.save_playlist.or(file_and_env.save_playlist),
+        absolute_paths: cli.absolute_paths || file_and_env.absolute_paths,
+        resume: cli.resume || file_and_env.resume,
+        resume_file: cli.resume_file.or(file_and_env.resume_file),
         seek: cli.seek.or(file_and_env.seek),
+        start: cli.start.or(file_and_env.start),
+        end: cli.end.or(file_and_env.end),
         volume_step: cli.volume_step.or(file_and_env.volume_step),
         tomp4: cli.tomp4 || file_and_env.tomp4,
         media_type: cli.media_type.or(file_and_env.media_type),
@@ -33,10 +49,26 @@ mod tests {
             address: Some("192.168.1.100".to_string()),
             device: None,
             subtitles: None,
+            subtitle_tracks: None,
+            subtitle_extractor: None,
+            subtitle_scale: None,
+            subtitle_color: None,
+            subtitle_background: None,
+            subtitle_edge: None,
+            subtitle_font: None,
             no_search: true,
             loop_playback: false,
             shuffle: false,
+            shuffle_mode: None,
+            shuffle_seed: None,
+            native_queue: false,
+            save_playlist: None,
+            absolute_paths: false,
+            resume: false,
+            resume_file: None,
             seek: None,
+            start: None,
+            end: None,
             volume_step: Some(0.05),
             tomp4: false,
             media_type: None,
@@ -53,10 +85,26 @@ mod tests {
             address: Some("192.168.1.101".to_string()),
             device: Some("Bedroom TV".to_string()),
             subtitles: Some("sub.srt".to_string()),
+            subtitle_tracks: None,
+            subtitle_extractor: None,
+            subtitle_scale: None,
+            subtitle_color: None,
+            subtitle_background: None,
+            subtitle_edge: None,
+            subtitle_font: None,
             no_search: false,
             loop_playback: true,
             shuffle: false,
+            shuffle_mode: None,
+            shuffle_seed: None,
+            native_queue: false,
+            save_playlist: None,
+            absolute_paths: false,
+            resume: false,
+            resume_file: None,
             seek: None,
+            start: None,
+            end: None,
             volume_step: Some(0.1),
             tomp4: true,
             media_type: None,
@@ -74,10 +122,10 @@ mod tests {
         assert_eq!(merged.address, Some("192.168.1.100".to_string())); // CLI overrides
         assert_eq!(merged.device, Some("Bedroom TV".to_string())); // File/Env is present, CLI is None
         assert_eq!(merged.subtitles, Some("sub.srt".to_string()));
-        assert_eq!(merged.no_search, true); // CLI overrides
-        assert_eq!(merged.loop_playback, true); // File/Env is true, CLI is false
+        assert!(merged.no_search); // CLI overrides
+        assert!(merged.loop_playback); // File/Env is true, CLI is false
         assert_eq!(merged.volume_step, Some(0.05)); // CLI overrides
-        assert_eq!(merged.tomp4, true); // File/Env is true, CLI is false
+        assert!(merged.tomp4); // File/Env is true, CLI is false
     }
 
     #[test]
@@ -86,10 +134,26 @@ mod tests {
             address: None,
             device: None,
             subtitles: None,
+            subtitle_tracks: None,
+            subtitle_extractor: None,
+            subtitle_scale: None,
+            subtitle_color: None,
+            subtitle_background: None,
+            subtitle_edge: None,
+            subtitle_font: None,
             no_search: false,
             loop_playback: false,
             shuffle: false,
+            shuffle_mode: None,
+            shuffle_seed: None,
+            native_queue: false,
+            save_playlist: None,
+            absolute_paths: false,
+            resume: false,
+            resume_file: None,
             seek: None,
+            start: None,
+            end: None,
             volume_step: None,
             tomp4: false,
             media_type: None,
@@ -106,10 +170,26 @@ mod tests {
             address: Some("192.168.1.101".to_string()),
             device: Some("Bedroom TV".to_string()),
             subtitles: Some("sub.srt".to_string()),
+            subtitle_tracks: Some("all".to_string()),
+            subtitle_extractor: Some("ffmpeg".to_string()),
+            subtitle_scale: Some(1.5),
+            subtitle_color: Some("yellow".to_string()),
+            subtitle_background: Some("#00000080".to_string()),
+            subtitle_edge: Some("outline".to_string()),
+            subtitle_font: Some("serif".to_string()),
             no_search: true,
             loop_playback: true,
             shuffle: true,
+            shuffle_mode: None,
+            shuffle_seed: None,
+            native_queue: false,
+            save_playlist: Some("queue.xspf".to_string()),
+            absolute_paths: true,
+            resume: false,
+            resume_file: None,
             seek: Some("10s".to_string()),
+            start: None,
+            end: None,
             volume_step: Some(0.1),
             tomp4: true,
             media_type: Some("video/mp4".to_string()),
@@ -127,18 +207,27 @@ mod tests {
         assert_eq!(merged.address, Some("192.168.1.101".to_string()));
         assert_eq!(merged.device, Some("Bedroom TV".to_string()));
         assert_eq!(merged.subtitles, Some("sub.srt".to_string()));
-        assert_eq!(merged.no_search, true);
-        assert_eq!(merged.loop_playback, true);
-        assert_eq!(merged.shuffle, true);
+        assert_eq!(merged.subtitle_tracks, Some("all".to_string()));
+        assert_eq!(merged.subtitle_extractor, Some("ffmpeg".to_string()));
+        assert_eq!(merged.subtitle_scale, Some(1.5));
+        assert_eq!(merged.subtitle_color, Some("yellow".to_string()));
+        assert_eq!(merged.subtitle_background, Some("#00000080".to_string()));
+        assert_eq!(merged.subtitle_edge, Some("outline".to_string()));
+        assert_eq!(merged.subtitle_font, Some("serif".to_string()));
+        assert!(merged.no_search);
+        assert!(merged.loop_playback);
+        assert!(merged.shuffle);
+        assert_eq!(merged.save_playlist, Some("queue.xspf".to_string()));
+        assert!(merged.absolute_paths);
         assert_eq!(merged.seek, Some("10s".to_string()));
         assert_eq!(merged.volume_step, Some(0.1));
-        assert_eq!(merged.tomp4, true);
+        assert!(merged.tomp4);
         assert_eq!(merged.media_type, Some("video/mp4".to_string()));
-        assert_eq!(merged.quiet, true);
-        assert_eq!(merged.no_metadata, true);
-        assert_eq!(merged.no_cover, true);
-        assert_eq!(merged.show_options, true);
-        assert_eq!(merged.exit, true);
+        assert!(merged.quiet);
+        assert!(merged.no_metadata);
+        assert!(merged.no_cover);
+        assert!(merged.show_options);
+        assert!(merged.exit);
         assert_eq!(merged.command, Some("play".to_string()));
         assert_eq!(merged.media_path, Some("file.mp4".to_string()));
     }
@@ -0,0 +1,19 @@
+﻿REM GENRE Rock
+REM DATE 1999
+REM COMMENT "ExactAudioCopy"
+PERFORMER "The Band"
+TITLE "Live at the Hall"
+FILE "Live Disc 1.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "Opening"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Ballad"
+    PERFORMER "Guest Singer"
+    INDEX 00 03:58:00
+    INDEX 01 04:00:00
+  TRACK 03 AUDIO
+    TITLE "Encore"
+    INDEX 00 07:59:00
+FILE disc2.wav WAVE
+    INDEX 01 00:00:00
@@ -29,3 +29,197 @@ async fn test_start_server() {
 
     tokio::fs::remove_file(&file_path).await.unwrap();
 }
+
+#[tokio::test]
+async fn test_server_serves_resources() {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let file_path = PathBuf::from("test_media_resources.mp4");
+    tokio::fs::File::create(&file_path).await.unwrap();
+
+    let resources = gemini_castnow::server::Resources::default();
+    resources.write().unwrap().insert(
+        "/subtitles/1.vtt".to_string(),
+        gemini_castnow::server::Resource::new("text/vtt", "WEBVTT\n\n"),
+    );
+
+    let (addr, handle) =
+        gemini_castnow::server::start_server_with_resources(file_path.clone(), resources, rx)
+            .await
+            .unwrap();
+
+    let connector = HttpConnector::new();
+    let client: Client<HttpConnector, Full<Bytes>> =
+        Client::builder(TokioExecutor::new()).build(connector);
+
+    let uri = format!("http://{addr}/subtitles/1.vtt").parse().unwrap();
+    let response = client.get(uri).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/vtt");
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+
+    let uri = format!("http://{addr}/subtitles/2.vtt").parse().unwrap();
+    let response = client.get(uri).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    tx.send(()).unwrap();
+    handle.await.unwrap();
+
+    tokio::fs::remove_file(&file_path).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_resource_server_serves_files() {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let file_path = PathBuf::from("test_media_queue.mp3");
+    tokio::fs::write(&file_path, b"ID3").await.unwrap();
+
+    let resources = gemini_castnow::server::Resources::default();
+    resources.write().unwrap().insert(
+        "/media/0.mp3".to_string(),
+        gemini_castnow::server::Resource::file("audio/mpeg", &file_path),
+    );
+
+    let (addr, handle) = gemini_castnow::server::start_resource_server(resources, rx)
+        .await
+        .unwrap();
+
+    let connector = HttpConnector::new();
+    let client: Client<HttpConnector, Full<Bytes>> =
+        Client::builder(TokioExecutor::new()).build(connector);
+
+    let uri = format!("http://{addr}/media/0.mp3").parse().unwrap();
+    let response = client.get(uri).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "audio/mpeg");
+    let body = http_body_util::BodyExt::collect(response.into_body())
+        .await
+        .unwrap()
+        .to_bytes();
+    assert_eq!(body, Bytes::from_static(b"ID3"));
+
+    // Without a media file there is nothing at the root.
+    let uri = format!("http://{addr}/").parse().unwrap();
+    let response = client.get(uri).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    tx.send(()).unwrap();
+    handle.await.unwrap();
+
+    tokio::fs::remove_file(&file_path).await.unwrap();
+}
+
+async fn get_range(
+    client: &Client<HttpConnector, Full<Bytes>>,
+    uri: &str,
+    range: &str,
+) -> hyper::Response<hyper::body::Incoming> {
+    let request = hyper::Request::get(uri)
+        .header("range", range)
+        .body(Full::default())
+        .unwrap();
+    client.request(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_server_serves_ranges() {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let file_path = PathBuf::from("test_media_ranges.mp3");
+    tokio::fs::write(&file_path, b"0123456789").await.unwrap();
+
+    let resources = gemini_castnow::server::Resources::default();
+    resources.write().unwrap().insert(
+        "/media/0.mp3".to_string(),
+        gemini_castnow::server::Resource::file("audio/mpeg", &file_path),
+    );
+    let (addr, handle) = gemini_castnow::server::start_resource_server(resources, rx)
+        .await
+        .unwrap();
+
+    let client: Client<HttpConnector, Full<Bytes>> =
+        Client::builder(TokioExecutor::new()).build(HttpConnector::new());
+    let uri = format!("http://{addr}/media/0.mp3");
+
+    for (range, content_range, expected) in [
+        ("bytes=2-5", "bytes 2-5/10", &b"2345"[..]),
+        ("bytes=7-", "bytes 7-9/10", b"789"),
+        ("bytes=-2", "bytes 8-9/10", b"89"),
+        ("bytes=8-100", "bytes 8-9/10", b"89"),
+    ] {
+        let response = get_range(&client, &uri, range).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT, "{range}");
+        assert_eq!(response.headers()["content-range"], content_range);
+        assert_eq!(response.headers()["accept-ranges"], "bytes");
+        let body = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes();
+        assert_eq!(body, Bytes::from_static(expected), "{range}");
+    }
+
+    let response = get_range(&client, &uri, "bytes=10-").await;
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers()["content-range"], "bytes */10");
+
+    // Multiple ranges aren't supported; the whole file is sent instead.
+    let response = get_range(&client, &uri, "bytes=0-1,4-5").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-length"], "10");
+
+    tx.send(()).unwrap();
+    handle.await.unwrap();
+
+    tokio::fs::remove_file(&file_path).await.unwrap();
+}
+
+#[derive(Debug)]
+struct MemoryStream(&'static [u8]);
+
+impl gemini_castnow::server::StreamSource for MemoryStream {
+    fn size(&self) -> u64 {
+        self.0.len() as u64
+    }
+
+    fn open(&self) -> std::io::Result<Box<dyn gemini_castnow::server::MediaReader>> {
+        Ok(Box::new(std::io::Cursor::new(self.0)))
+    }
+}
+
+#[tokio::test]
+async fn test_server_serves_streams() {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let resources = gemini_castnow::server::Resources::default();
+    resources.write().unwrap().insert(
+        "/media/0.mkv".to_string(),
+        gemini_castnow::server::Resource::stream(
+            "video/x-matroska",
+            std::sync::Arc::new(MemoryStream(b"matroska data")),
+        ),
+    );
+    let (addr, handle) = gemini_castnow::server::start_resource_server(resources, rx)
+        .await
+        .unwrap();
+
+    let client: Client<HttpConnector, Full<Bytes>> =
+        Client::builder(TokioExecutor::new()).build(HttpConnector::new());
+    let uri = format!("http://{addr}/media/0.mkv");
+
+    let response = client.get(uri.parse().unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-length"], "13");
+    let body = http_body_util::BodyExt::collect(response.into_body())
+        .await
+        .unwrap()
+        .to_bytes();
+    assert_eq!(body, Bytes::from_static(b"matroska data"));
+
+    let response = get_range(&client, &uri, "bytes=9-").await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    let body = http_body_util::BodyExt::collect(response.into_body())
+        .await
+        .unwrap()
+        .to_bytes();
+    assert_eq!(body, Bytes::from_static(b"data"));
+
+    tx.send(()).unwrap();
+    handle.await.unwrap();
+}
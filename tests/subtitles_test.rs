@@ -0,0 +1,24 @@
+use std::process::Command;
+
+#[test]
+fn test_subtitles_missing_file_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing.mkv");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gemini_castnow"))
+        .args(["subtitles", "--file"])
+        .arg(&missing)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Video file not found"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gemini_castnow"))
+        .args(["--format", "json", "subtitles", "--file"])
+        .arg(&missing)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "not_found");
+}